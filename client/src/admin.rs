@@ -0,0 +1,287 @@
+use crate::client::{deserialize_anchor_account, get_accounts, get_anchor_account};
+use crate::error::{ClientError, Result};
+use crate::pda::get_operation_key;
+use crate::utils::{serialize_pubkey, serialize_pubkeys};
+use raydium_amm_v3::states::{OperationState, PoolState};
+use serde::{Serialize, Serializer};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+use std::str::FromStr;
+
+/// Operations of a pool that `PoolState.status` can disable, one bit each in the order of the
+/// program's `PoolStatusBitIndex`. A set bit disables the operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PoolOperation {
+    OpenPosition,
+    DecreaseLiquidity,
+    CollectFee,
+    CollectReward,
+    Swap,
+}
+
+impl PoolOperation {
+    pub const ALL: [PoolOperation; 5] = [
+        PoolOperation::OpenPosition,
+        PoolOperation::DecreaseLiquidity,
+        PoolOperation::CollectFee,
+        PoolOperation::CollectReward,
+        PoolOperation::Swap,
+    ];
+
+    pub fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PoolOperation::OpenPosition => "open-position",
+            PoolOperation::DecreaseLiquidity => "decrease-liquidity",
+            PoolOperation::CollectFee => "collect-fee",
+            PoolOperation::CollectReward => "collect-reward",
+            PoolOperation::Swap => "swap",
+        }
+    }
+
+    pub fn is_enabled(self, status: u8) -> bool {
+        status & self.bit() == 0
+    }
+}
+
+impl fmt::Display for PoolOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PoolOperation {
+    type Err = ClientError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        PoolOperation::ALL
+            .into_iter()
+            .find(|operation| operation.name() == name)
+            .ok_or_else(|| {
+                ClientError::InvalidArgument(format!(
+                    "unknown pool operation {}, expected one of {}",
+                    name,
+                    PoolOperation::ALL.map(PoolOperation::name).join(", ")
+                ))
+            })
+    }
+}
+
+/// Each operation with whether `status` enables it.
+pub fn decode_pool_status(status: u8) -> Vec<(PoolOperation, bool)> {
+    PoolOperation::ALL
+        .into_iter()
+        .map(|operation| (operation, operation.is_enabled(status)))
+        .collect()
+}
+
+/// The operations of a pool status, serialized as a map from each operation name to whether
+/// it is enabled, in bit order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperationFlags(pub u8);
+
+impl Serialize for OperationFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            decode_pool_status(self.0)
+                .into_iter()
+                .map(|(operation, enabled)| (operation.name(), enabled)),
+        )
+    }
+}
+
+/// `status` with the `enable` operations enabled and the `disable` ones disabled. An operation
+/// in both is an error.
+pub fn update_pool_status(
+    status: u8,
+    enable: &[PoolOperation],
+    disable: &[PoolOperation],
+) -> Result<u8> {
+    if let Some(operation) = enable.iter().find(|operation| disable.contains(operation)) {
+        return Err(ClientError::InvalidArgument(format!(
+            "{} is both enabled and disabled",
+            operation
+        )));
+    }
+    let status = enable
+        .iter()
+        .fold(status, |status, operation| status & !operation.bit());
+    Ok(disable
+        .iter()
+        .fold(status, |status, operation| status | operation.bit()))
+}
+
+/// Changes `UpdateOperationAccount` makes to the operation account, by its `param`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperationUpdate {
+    AddOwners = 0,
+    RemoveOwners = 1,
+    AddWhitelistMints = 2,
+    RemoveWhitelistMints = 3,
+}
+
+impl FromStr for OperationUpdate {
+    type Err = ClientError;
+
+    fn from_str(update: &str) -> Result<Self> {
+        match update {
+            "add-owners" => Ok(OperationUpdate::AddOwners),
+            "remove-owners" => Ok(OperationUpdate::RemoveOwners),
+            "add-whitelist-mints" => Ok(OperationUpdate::AddWhitelistMints),
+            "remove-whitelist-mints" => Ok(OperationUpdate::RemoveWhitelistMints),
+            _ => Err(ClientError::InvalidArgument(format!(
+                "unknown operation update {}, expected add-owners, remove-owners, \
+                 add-whitelist-mints or remove-whitelist-mints",
+                update
+            ))),
+        }
+    }
+}
+
+/// Owners and whitelisted mints of the operation account.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OperationLists {
+    #[serde(serialize_with = "serialize_pubkeys")]
+    pub operation_owners: Vec<Pubkey>,
+    #[serde(serialize_with = "serialize_pubkeys")]
+    pub whitelist_mints: Vec<Pubkey>,
+}
+
+/// Owners and whitelisted mints of the operation account, `None` when it has not been created.
+pub async fn get_operation_lists(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+) -> Result<Option<OperationLists>> {
+    let operation_key = get_operation_key(raydium_v3_program);
+    let Some(account) = get_accounts(rpc_client, &[operation_key]).await?.remove(0) else {
+        return Ok(None);
+    };
+    let operation_state = deserialize_anchor_account::<OperationState>(&account)?;
+    // copied out of the packed account
+    let (operation_owners, whitelist_mints) = (
+        operation_state.operation_owners,
+        operation_state.whitelist_mints,
+    );
+    let non_default = |keys: &[Pubkey]| {
+        keys.iter()
+            .filter(|key| **key != Pubkey::default())
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    Ok(Some(OperationLists {
+        operation_owners: non_default(&operation_owners),
+        whitelist_mints: non_default(&whitelist_mints),
+    }))
+}
+
+/// Status of a pool, decoded into its operations, and its open time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PoolStatus {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    pub status: u8,
+    pub operations: OperationFlags,
+    pub open_time: u64,
+}
+
+/// Status and open time of a pool. The open time is fixed when the pool is created.
+pub async fn get_pool_status(rpc_client: &RpcClient, pool_id: &Pubkey) -> Result<PoolStatus> {
+    let pool_state = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    Ok(PoolStatus {
+        pool_id: *pool_id,
+        status: pool_state.status,
+        operations: OperationFlags(pool_state.status),
+        open_time: pool_state.open_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_map_to_status_bits() {
+        assert_eq!(PoolOperation::ALL.map(PoolOperation::bit), [1, 2, 4, 8, 16]);
+        for operation in PoolOperation::ALL {
+            assert_eq!(
+                operation.name().parse::<PoolOperation>().unwrap(),
+                operation
+            );
+            assert!(operation.is_enabled(0));
+            assert!(!operation.is_enabled(operation.bit()));
+            assert!(operation.is_enabled(!operation.bit()));
+        }
+        assert!("deposit".parse::<PoolOperation>().is_err());
+        assert_eq!(
+            decode_pool_status(0b10010),
+            vec![
+                (PoolOperation::OpenPosition, true),
+                (PoolOperation::DecreaseLiquidity, false),
+                (PoolOperation::CollectFee, true),
+                (PoolOperation::CollectReward, true),
+                (PoolOperation::Swap, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_only_the_given_operations() {
+        let status = 0b00110;
+        assert_eq!(
+            update_pool_status(status, &[PoolOperation::CollectFee], &[PoolOperation::Swap])
+                .unwrap(),
+            0b10010
+        );
+        // enabling an enabled and disabling a disabled operation keep the status
+        assert_eq!(
+            update_pool_status(
+                status,
+                &[PoolOperation::OpenPosition],
+                &[PoolOperation::DecreaseLiquidity]
+            )
+            .unwrap(),
+            status
+        );
+        // bits beyond the known operations are left alone
+        assert_eq!(
+            update_pool_status(0b1000_0000, &PoolOperation::ALL, &[]).unwrap(),
+            0b1000_0000
+        );
+        assert_eq!(
+            update_pool_status(0, &[], &PoolOperation::ALL).unwrap(),
+            0b11111
+        );
+        assert!(update_pool_status(
+            status,
+            &[PoolOperation::Swap],
+            &[PoolOperation::CollectFee, PoolOperation::Swap]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn operation_flags_serialize_by_name_in_bit_order() {
+        assert_eq!(
+            serde_json::to_string(&OperationFlags(0b01001)).unwrap(),
+            r#"{"open-position":false,"decrease-liquidity":true,"collect-fee":true,"#.to_owned()
+                + r#""collect-reward":false,"swap":true}"#
+        );
+    }
+
+    #[test]
+    fn operation_updates_parse_to_their_param() {
+        let updates = [
+            "add-owners",
+            "remove-owners",
+            "add-whitelist-mints",
+            "remove-whitelist-mints",
+        ]
+        .map(|update| update.parse::<OperationUpdate>().unwrap() as u8);
+        assert_eq!(updates, [0, 1, 2, 3]);
+        assert!("add-mints".parse::<OperationUpdate>().is_err());
+    }
+}
@@ -0,0 +1,851 @@
+use crate::client::get_anchor_account;
+use crate::error::{ClientError, Result};
+use crate::range::{get_range_ticks, RangeStrategy};
+use crate::rebalance::should_rebalance;
+use crate::utils::{get_zap_in_swap_amount, sqrt_price_x64_to_f64};
+use raydium_amm_v3::libraries::{liquidity_math, swap_math, tick_math};
+use raydium_amm_v3::states::{AmmConfig, PoolState, FEE_RATE_DENOMINATOR_VALUE};
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// State of a pool after a historical swap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapRecord {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub sqrt_price_x64: u128,
+    /// Active liquidity, not counting the simulated position.
+    pub liquidity: u128,
+    pub tick: i32,
+}
+
+fn parse_u128(value: &str, column: &str) -> Result<u128> {
+    value
+        .parse()
+        .map_err(|_| ClientError::InvalidResponse(format!("invalid {} {}", column, value)))
+}
+
+/// Swaps of `pool_id` in a database written by the indexer, in slot order.
+pub fn load_swaps(path: &str, pool_id: &Pubkey) -> Result<Vec<SwapRecord>> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = connection.prepare(
+        "SELECT slot, block_time, sqrt_price_x64, liquidity, tick FROM swaps WHERE pool = ?1 \
+         ORDER BY slot, signature, event_index",
+    )?;
+    let rows = statement.query_map(params![pool_id.to_string()], |row| {
+        Ok((
+            row.get::<_, u64>(0)?,
+            row.get::<_, Option<i64>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i32>(4)?,
+        ))
+    })?;
+    let mut swaps = Vec::new();
+    for row in rows {
+        let (slot, block_time, sqrt_price_x64, liquidity, tick) = row?;
+        swaps.push(SwapRecord {
+            slot,
+            block_time,
+            sqrt_price_x64: parse_u128(&sqrt_price_x64, "sqrt_price_x64")?,
+            liquidity: parse_u128(&liquidity, "liquidity")?,
+            tick,
+        });
+    }
+    Ok(swaps)
+}
+
+/// Swaps of a CSV file with a header naming its `slot`, `sqrt_price_x64`, `liquidity` and
+/// `tick` columns, and optionally `block_time`, in any order. Rows must be in slot order.
+pub fn load_swaps_csv(path: &str) -> Result<Vec<SwapRecord>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut lines = content.lines().enumerate();
+    let header = lines
+        .next()
+        .ok_or_else(|| ClientError::InvalidArgument(format!("{} is empty", path)))?
+        .1;
+    let columns = header.split(',').map(str::trim).collect::<Vec<&str>>();
+    let column = |name: &str| {
+        columns
+            .iter()
+            .position(|column| *column == name)
+            .ok_or_else(|| ClientError::InvalidArgument(format!("{} has no {} column", path, name)))
+    };
+    let (slot_column, sqrt_price_column, liquidity_column, tick_column) = (
+        column("slot")?,
+        column("sqrt_price_x64")?,
+        column("liquidity")?,
+        column("tick")?,
+    );
+    let block_time_column = column("block_time").ok();
+
+    let mut swaps = Vec::new();
+    for (line_index, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line.split(',').map(str::trim).collect::<Vec<&str>>();
+        let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+        let invalid = |name: &str| {
+            ClientError::InvalidArgument(format!(
+                "{} line {}: invalid {} {:?}",
+                path,
+                line_index + 1,
+                name,
+                line
+            ))
+        };
+        let block_time = match block_time_column.map(field).filter(|time| !time.is_empty()) {
+            Some(time) => Some(time.parse().map_err(|_| invalid("block_time"))?),
+            None => None,
+        };
+        swaps.push(SwapRecord {
+            slot: field(slot_column).parse().map_err(|_| invalid("slot"))?,
+            block_time,
+            sqrt_price_x64: field(sqrt_price_column)
+                .parse()
+                .map_err(|_| invalid("sqrt_price_x64"))?,
+            liquidity: field(liquidity_column)
+                .parse()
+                .map_err(|_| invalid("liquidity"))?,
+            tick: field(tick_column).parse().map_err(|_| invalid("tick"))?,
+        });
+    }
+    Ok(swaps)
+}
+
+/// Decides the range of the simulated position.
+pub trait Strategy {
+    /// Short description for the report.
+    fn name(&self) -> String;
+
+    /// Range of the first position, opened at `tick_current`.
+    fn initial_range(&mut self, tick_current: i32, tick_spacing: u16) -> Result<(i32, i32)>;
+
+    /// Called after every swap with the range the position is on. A different range moves the
+    /// position there.
+    fn next_range(
+        &mut self,
+        swap: &SwapRecord,
+        tick_spacing: u16,
+        range: (i32, i32),
+    ) -> Result<Option<(i32, i32)>>;
+}
+
+/// Open one range around the first price and never move it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedRange {
+    pub strategy: RangeStrategy,
+}
+
+impl Strategy for FixedRange {
+    fn name(&self) -> String {
+        format!("fixed {:?}", self.strategy)
+    }
+
+    fn initial_range(&mut self, tick_current: i32, tick_spacing: u16) -> Result<(i32, i32)> {
+        get_range_ticks(self.strategy, tick_current, tick_spacing)
+    }
+
+    fn next_range(
+        &mut self,
+        _swap: &SwapRecord,
+        _tick_spacing: u16,
+        _range: (i32, i32),
+    ) -> Result<Option<(i32, i32)>> {
+        Ok(None)
+    }
+}
+
+/// Move the range around the current price when it leaves the range or, with a drift
+/// threshold, drifts too far from its middle, like the rebalancer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RebalanceOnExit {
+    pub strategy: RangeStrategy,
+    pub drift_threshold: Option<f64>,
+}
+
+impl Strategy for RebalanceOnExit {
+    fn name(&self) -> String {
+        match self.drift_threshold {
+            Some(drift_threshold) => format!(
+                "rebalance {:?}, drift threshold {}",
+                self.strategy, drift_threshold
+            ),
+            None => format!("rebalance {:?}", self.strategy),
+        }
+    }
+
+    fn initial_range(&mut self, tick_current: i32, tick_spacing: u16) -> Result<(i32, i32)> {
+        get_range_ticks(self.strategy, tick_current, tick_spacing)
+    }
+
+    fn next_range(
+        &mut self,
+        swap: &SwapRecord,
+        tick_spacing: u16,
+        range: (i32, i32),
+    ) -> Result<Option<(i32, i32)>> {
+        should_rebalance(swap.tick, range.0, range.1, self.drift_threshold)
+            .map(|_| get_range_ticks(self.strategy, swap.tick, tick_spacing))
+            .transpose()
+    }
+}
+
+/// Ranges of `±multiplier` standard deviations of the tick over the last `window` swaps,
+/// placed again when the price leaves them. The deviation is taken to be at least one tick
+/// spacing, which also sizes the first range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VolatilityScaled {
+    multiplier: f64,
+    window: usize,
+    ticks: VecDeque<i32>,
+}
+
+impl VolatilityScaled {
+    pub fn new(multiplier: f64, window: usize) -> Result<Self> {
+        if multiplier.is_nan() || multiplier <= 0.0 {
+            return Err(ClientError::InvalidArgument(format!(
+                "multiplier must be positive, got {}",
+                multiplier
+            )));
+        }
+        if window < 2 {
+            return Err(ClientError::InvalidArgument(format!(
+                "window must hold at least 2 swaps, got {}",
+                window
+            )));
+        }
+        Ok(Self {
+            multiplier,
+            window,
+            ticks: VecDeque::with_capacity(window),
+        })
+    }
+
+    fn observe(&mut self, tick: i32) {
+        if self.ticks.len() == self.window {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(tick);
+    }
+
+    fn range(&self, tick_current: i32, tick_spacing: u16) -> Result<(i32, i32)> {
+        let count = self.ticks.len() as f64;
+        let mean = self.ticks.iter().map(|tick| f64::from(*tick)).sum::<f64>() / count;
+        let variance = self
+            .ticks
+            .iter()
+            .map(|tick| (f64::from(*tick) - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let strategy = RangeStrategy::StdDev {
+            multiplier: self.multiplier,
+            sigma: variance.sqrt().max(f64::from(tick_spacing)),
+        };
+        get_range_ticks(strategy, tick_current, tick_spacing)
+    }
+}
+
+impl Strategy for VolatilityScaled {
+    fn name(&self) -> String {
+        format!(
+            "volatility {} std dev over {} swaps",
+            self.multiplier, self.window
+        )
+    }
+
+    fn initial_range(&mut self, tick_current: i32, tick_spacing: u16) -> Result<(i32, i32)> {
+        self.observe(tick_current);
+        self.range(tick_current, tick_spacing)
+    }
+
+    fn next_range(
+        &mut self,
+        swap: &SwapRecord,
+        tick_spacing: u16,
+        range: (i32, i32),
+    ) -> Result<Option<(i32, i32)>> {
+        self.observe(swap.tick);
+        if should_rebalance(swap.tick, range.0, range.1, None).is_none() {
+            return Ok(None);
+        }
+        self.range(swap.tick, tick_spacing).map(Some)
+    }
+}
+
+/// The built-in strategies, for the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrategyKind {
+    Fixed,
+    Exit,
+    Volatility,
+}
+
+impl FromStr for StrategyKind {
+    type Err = ClientError;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "fixed" => Ok(StrategyKind::Fixed),
+            "exit" => Ok(StrategyKind::Exit),
+            "volatility" => Ok(StrategyKind::Volatility),
+            _ => Err(ClientError::InvalidArgument(format!(
+                "unknown strategy {}, expected fixed, exit or volatility",
+                kind
+            ))),
+        }
+    }
+}
+
+/// Pool parameters and starting amounts of a backtest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BacktestConfig {
+    pub tick_spacing: u16,
+    pub trade_fee_rate: u32,
+    /// Shares of the trade fee kept by the protocol and the fund, not paid to positions.
+    pub protocol_fee_rate: u32,
+    pub fund_fee_rate: u32,
+    /// Raw amounts deposited at the start, swapped to the ratio of the first range.
+    pub amount_0: u64,
+    pub amount_1: u64,
+}
+
+/// Tick spacing and fee rates of a pool given for a backtest, the missing ones being read
+/// from the pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolParams {
+    pub tick_spacing: Option<u16>,
+    pub trade_fee_rate: Option<u32>,
+    pub protocol_fee_rate: Option<u32>,
+    pub fund_fee_rate: Option<u32>,
+}
+
+impl PoolParams {
+    /// The backtest config of these parameters, `None` when some are missing.
+    pub fn config(&self, amount_0: u64, amount_1: u64) -> Option<BacktestConfig> {
+        Some(BacktestConfig {
+            tick_spacing: self.tick_spacing?,
+            trade_fee_rate: self.trade_fee_rate?,
+            protocol_fee_rate: self.protocol_fee_rate?,
+            fund_fee_rate: self.fund_fee_rate?,
+            amount_0,
+            amount_1,
+        })
+    }
+}
+
+/// Take the tick spacing and fee rates of `pool_id` not in `params` for a backtest of its
+/// swaps, without reading the pool when they are all given.
+pub async fn get_backtest_config(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    params: PoolParams,
+    amount_0: u64,
+    amount_1: u64,
+) -> Result<BacktestConfig> {
+    if let Some(config) = params.config(amount_0, amount_1) {
+        return Ok(config);
+    }
+    let pool = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    let amm_config = get_anchor_account::<AmmConfig>(rpc_client, &pool.amm_config).await?;
+    Ok(BacktestConfig {
+        tick_spacing: params.tick_spacing.unwrap_or(pool.tick_spacing),
+        trade_fee_rate: params.trade_fee_rate.unwrap_or(amm_config.trade_fee_rate),
+        protocol_fee_rate: params
+            .protocol_fee_rate
+            .unwrap_or(amm_config.protocol_fee_rate),
+        fund_fee_rate: params.fund_fee_rate.unwrap_or(amm_config.fund_fee_rate),
+        amount_0,
+        amount_1,
+    })
+}
+
+/// Value after a swap. Values are raw token1 amounts at the price after the swap.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct EquityPoint {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub tick: i32,
+    /// Tokens in the position and left over from the last deposit.
+    pub position_value: f64,
+    pub fees_value: f64,
+    /// The starting amounts, held instead.
+    pub hodl_value: f64,
+}
+
+/// Outcome of a backtest. Values are raw token1 amounts at the last price.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BacktestReport {
+    pub strategy: String,
+    pub swaps: usize,
+    /// Share of the swaps after which the position was in range.
+    pub time_in_range: f64,
+    pub rebalances: usize,
+    /// Trade fees paid swapping to the ratio of each new range, valued when paid.
+    pub rebalance_costs: f64,
+    pub fees_0: u64,
+    pub fees_1: u64,
+    pub fee_income: f64,
+    pub initial_value: f64,
+    pub position_value: f64,
+    pub hodl_value: f64,
+    /// `position_value / hodl_value - 1`, fees excluded and rebalance costs included.
+    pub impermanent_loss: f64,
+    /// Position and fees less the initial value.
+    pub net_pnl: f64,
+    /// One point per swap, left out of the serialized report and written with
+    /// [`write_equity_curve`].
+    #[serde(skip_serializing)]
+    pub equity_curve: Vec<EquityPoint>,
+}
+
+/// The simulated position and the tokens it could not take.
+#[derive(Clone, Copy, Debug)]
+struct SimulatedPosition {
+    tick_lower: i32,
+    tick_upper: i32,
+    sqrt_price_lower_x64: u128,
+    sqrt_price_upper_x64: u128,
+    liquidity: u128,
+    idle_0: u64,
+    idle_1: u64,
+}
+
+impl SimulatedPosition {
+    /// Swap `amount_0` and `amount_1` to the ratio of `[tick_lower, tick_upper)` at the spot
+    /// price, paying the trade fee, and deposit them. Returns the position and the fee paid,
+    /// in raw token1.
+    fn open(
+        sqrt_price_x64: u128,
+        tick_current: i32,
+        (tick_lower, tick_upper): (i32, i32),
+        amount_0: u64,
+        amount_1: u64,
+        trade_fee_rate: u32,
+    ) -> Result<(Self, f64)> {
+        let sqrt_price_lower_x64 = tick_math::get_sqrt_price_at_tick(tick_lower)?;
+        let sqrt_price_upper_x64 = tick_math::get_sqrt_price_at_tick(tick_upper)?;
+        let sqrt_price = sqrt_price_x64_to_f64(sqrt_price_x64);
+        let price = sqrt_price * sqrt_price;
+        let fee_rate = trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64;
+        let (mut amount_0, mut amount_1, mut swap_cost) = (amount_0, amount_1, 0.0);
+        match get_zap_in_swap_amount(
+            sqrt_price_x64,
+            sqrt_price_lower_x64,
+            sqrt_price_upper_x64,
+            trade_fee_rate,
+            amount_0,
+            amount_1,
+        ) {
+            Some((true, amount_in)) => {
+                let fee = amount_in as f64 * fee_rate;
+                amount_0 = amount_0.saturating_sub(amount_in);
+                amount_1 += ((amount_in as f64 - fee) * price) as u64;
+                swap_cost = fee * price;
+            }
+            Some((false, amount_in)) => {
+                let fee = amount_in as f64 * fee_rate;
+                amount_1 = amount_1.saturating_sub(amount_in);
+                amount_0 += ((amount_in as f64 - fee) / price) as u64;
+                swap_cost = fee;
+            }
+            None => {}
+        }
+        let liquidity = liquidity_math::get_liquidity_from_amounts(
+            sqrt_price_x64,
+            sqrt_price_lower_x64,
+            sqrt_price_upper_x64,
+            amount_0,
+            amount_1,
+        );
+        let (used_amount_0, used_amount_1) = liquidity_math::get_delta_amounts_signed(
+            tick_current,
+            sqrt_price_x64,
+            tick_lower,
+            tick_upper,
+            liquidity as i128,
+        )?;
+        Ok((
+            Self {
+                tick_lower,
+                tick_upper,
+                sqrt_price_lower_x64,
+                sqrt_price_upper_x64,
+                liquidity,
+                idle_0: amount_0.saturating_sub(used_amount_0),
+                idle_1: amount_1.saturating_sub(used_amount_1),
+            },
+            swap_cost,
+        ))
+    }
+
+    /// Tokens of the position and left over at a price.
+    fn amounts(&self, tick_current: i32, sqrt_price_x64: u128) -> Result<(u64, u64)> {
+        let (amount_0, amount_1) = liquidity_math::get_delta_amounts_signed(
+            tick_current,
+            sqrt_price_x64,
+            self.tick_lower,
+            self.tick_upper,
+            -(self.liquidity as i128),
+        )?;
+        Ok((amount_0 + self.idle_0, amount_1 + self.idle_1))
+    }
+
+    fn in_range(&self, tick_current: i32) -> bool {
+        self.tick_lower <= tick_current && tick_current < self.tick_upper
+    }
+
+    /// Fee earned while the price moved from `sqrt_price_from_x64` to `sqrt_price_to_x64` over
+    /// `pool_liquidity`, before the protocol and fund shares, in the input token.
+    fn earned_fee(
+        &self,
+        sqrt_price_from_x64: u128,
+        sqrt_price_to_x64: u128,
+        pool_liquidity: u128,
+        trade_fee_rate: u32,
+    ) -> Result<u64> {
+        let zero_for_one = sqrt_price_to_x64 < sqrt_price_from_x64;
+        let lowest = sqrt_price_from_x64
+            .min(sqrt_price_to_x64)
+            .max(self.sqrt_price_lower_x64);
+        let highest = sqrt_price_from_x64
+            .max(sqrt_price_to_x64)
+            .min(self.sqrt_price_upper_x64);
+        if self.liquidity == 0 || lowest >= highest {
+            return Ok(0);
+        }
+        let (sqrt_price_x64, sqrt_price_target_x64) = if zero_for_one {
+            (highest, lowest)
+        } else {
+            (lowest, highest)
+        };
+        let liquidity = pool_liquidity.saturating_add(self.liquidity);
+        let step = swap_math::compute_swap_step(
+            sqrt_price_x64,
+            sqrt_price_target_x64,
+            liquidity,
+            u64::MAX,
+            trade_fee_rate,
+            true,
+            zero_for_one,
+            1,
+        )?;
+        Ok((step.fee_amount as f64 * self.liquidity as f64 / liquidity as f64) as u64)
+    }
+}
+
+/// Replay `swaps` through a position placed and moved by `strategy`. Between two swaps the
+/// price is taken to move over the active liquidity of the later one plus the position's, and
+/// the position earns its share of the fee of that move. Fees are not compounded. The first
+/// swap only sets the starting price.
+pub fn run_backtest(
+    swaps: &[SwapRecord],
+    strategy: &mut dyn Strategy,
+    config: &BacktestConfig,
+) -> Result<BacktestReport> {
+    let Some((first, swaps)) = swaps.split_first() else {
+        return Err(ClientError::InvalidArgument(
+            "no swaps to replay".to_string(),
+        ));
+    };
+    let lp_fee_share = 1.0
+        - (config.protocol_fee_rate + config.fund_fee_rate) as f64
+            / FEE_RATE_DENOMINATOR_VALUE as f64;
+    let value = |(amount_0, amount_1): (u64, u64), sqrt_price_x64: u128| {
+        let sqrt_price = sqrt_price_x64_to_f64(sqrt_price_x64);
+        amount_0 as f64 * sqrt_price * sqrt_price + amount_1 as f64
+    };
+    let initial_amounts = (config.amount_0, config.amount_1);
+
+    let (mut position, mut rebalance_costs) = SimulatedPosition::open(
+        first.sqrt_price_x64,
+        first.tick,
+        strategy.initial_range(first.tick, config.tick_spacing)?,
+        config.amount_0,
+        config.amount_1,
+        config.trade_fee_rate,
+    )?;
+    let mut sqrt_price_x64 = first.sqrt_price_x64;
+    let (mut fees_0, mut fees_1) = (0u64, 0u64);
+    let mut rebalances = 0;
+    let mut swaps_in_range = 0;
+    let mut equity_curve = Vec::with_capacity(swaps.len());
+    for swap in swaps {
+        let fee = (position.earned_fee(
+            sqrt_price_x64,
+            swap.sqrt_price_x64,
+            swap.liquidity,
+            config.trade_fee_rate,
+        )? as f64
+            * lp_fee_share) as u64;
+        if swap.sqrt_price_x64 < sqrt_price_x64 {
+            fees_0 += fee;
+        } else {
+            fees_1 += fee;
+        }
+        sqrt_price_x64 = swap.sqrt_price_x64;
+
+        let range = (position.tick_lower, position.tick_upper);
+        if let Some(new_range) = strategy.next_range(swap, config.tick_spacing, range)? {
+            if new_range != range {
+                let (amount_0, amount_1) = position.amounts(swap.tick, sqrt_price_x64)?;
+                let (new_position, swap_cost) = SimulatedPosition::open(
+                    sqrt_price_x64,
+                    swap.tick,
+                    new_range,
+                    amount_0,
+                    amount_1,
+                    config.trade_fee_rate,
+                )?;
+                position = new_position;
+                rebalance_costs += swap_cost;
+                rebalances += 1;
+            }
+        }
+        if position.in_range(swap.tick) {
+            swaps_in_range += 1;
+        }
+        equity_curve.push(EquityPoint {
+            slot: swap.slot,
+            block_time: swap.block_time,
+            tick: swap.tick,
+            position_value: value(position.amounts(swap.tick, sqrt_price_x64)?, sqrt_price_x64),
+            fees_value: value((fees_0, fees_1), sqrt_price_x64),
+            hodl_value: value(initial_amounts, sqrt_price_x64),
+        });
+    }
+
+    let last_tick = swaps.last().unwrap_or(first).tick;
+    let position_value = value(position.amounts(last_tick, sqrt_price_x64)?, sqrt_price_x64);
+    let hodl_value = value(initial_amounts, sqrt_price_x64);
+    let fee_income = value((fees_0, fees_1), sqrt_price_x64);
+    let initial_value = value(initial_amounts, first.sqrt_price_x64);
+    Ok(BacktestReport {
+        strategy: strategy.name(),
+        swaps: swaps.len(),
+        time_in_range: if swaps.is_empty() {
+            0.0
+        } else {
+            swaps_in_range as f64 / swaps.len() as f64
+        },
+        rebalances,
+        rebalance_costs,
+        fees_0,
+        fees_1,
+        fee_income,
+        initial_value,
+        position_value,
+        hodl_value,
+        impermanent_loss: if hodl_value > 0.0 {
+            position_value / hodl_value - 1.0
+        } else {
+            0.0
+        },
+        net_pnl: position_value + fee_income - initial_value,
+        equity_curve,
+    })
+}
+
+/// Write an equity curve as CSV.
+pub fn write_equity_curve(path: &str, equity_curve: &[EquityPoint]) -> Result<()> {
+    let mut output = String::from("slot,block_time,tick,position_value,fees_value,hodl_value\n");
+    for point in equity_curve {
+        output.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            point.slot,
+            point
+                .block_time
+                .map(|block_time| block_time.to_string())
+                .unwrap_or_default(),
+            point.tick,
+            point.position_value,
+            point.fees_value,
+            point.hodl_value
+        ));
+    }
+    std::fs::write(path, output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Swaps at ticks 0, 50, -50, 150, 100 and 0 over 1e12 of pool liquidity.
+    const SWAPS: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/backtest-swaps.csv"
+    );
+    /// Hodl value of the starting amounts after each swap but the first, `1e9 * (price + 1)`.
+    const HODL_VALUES: [f64; 5] = [
+        2_005_012_269.623,
+        1_995_012_727.929,
+        2_015_112_303.332,
+        2_010_049_662.093,
+        2_000_000_000.0,
+    ];
+
+    fn config() -> BacktestConfig {
+        BacktestConfig {
+            tick_spacing: 10,
+            trade_fee_rate: 2_500,
+            protocol_fee_rate: 120_000,
+            fund_fee_rate: 0,
+            amount_0: 1_000_000_000,
+            amount_1: 1_000_000_000,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Run `strategy` over the fixture, checking the fees against `(fees_0, fees_1)` and the
+    /// position values of the equity curve against `position_values`.
+    fn backtest(
+        strategy: &mut dyn Strategy,
+        (fees_0, fees_1): (f64, f64),
+        position_values: [f64; 5],
+    ) -> BacktestReport {
+        let swaps = load_swaps_csv(SWAPS).unwrap();
+        let report = run_backtest(&swaps, strategy, &config()).unwrap();
+        assert_eq!(report.swaps, 5);
+        // fees are rounded down on every swap
+        assert_close(report.fees_0 as f64, fees_0, 1e-5);
+        assert_close(report.fees_1 as f64, fees_1, 1e-5);
+
+        let curve = &report.equity_curve;
+        assert_eq!(
+            curve
+                .iter()
+                .map(|point| (point.slot, point.block_time, point.tick))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, Some(1_700_000_060), 50),
+                (3, Some(1_700_000_120), -50),
+                (4, Some(1_700_000_180), 150),
+                (5, Some(1_700_000_240), 100),
+                (6, Some(1_700_000_300), 0),
+            ]
+        );
+        for ((point, position_value), hodl_value) in
+            curve.iter().zip(position_values).zip(HODL_VALUES)
+        {
+            assert_close(point.position_value, position_value, 1e-6);
+            assert_close(point.hodl_value, hodl_value, 1e-6);
+        }
+        // fees are valued at the last price, which is 1
+        let last = curve.last().unwrap();
+        assert_close(last.fees_value, fees_0 + fees_1, 1e-5);
+        assert_eq!(report.position_value, last.position_value);
+        assert_close(report.initial_value, 2e9, 1e-9);
+        report
+    }
+
+    #[test]
+    fn fixed_range() {
+        // the deposit is already at the ratio of the range, `L = 1e9 / (1 - 1.0001^-50)`, and
+        // earns `L * Δsqrt(p) * 0.0025 / (1 - 0.0025) * 0.88` on every move within the range
+        let report = backtest(
+            &mut FixedRange {
+                strategy: RangeStrategy::Ticks(100),
+            },
+            (4_416_548.0, 4_427_602.6),
+            [
+                2_003_756_067.749,
+                1_993_762_791.076,
+                2_005_012_269.623,
+                2_005_012_269.623,
+                2_000_000_000.0,
+            ],
+        );
+        assert_eq!(report.rebalances, 0);
+        assert_eq!(report.rebalance_costs, 0.0);
+        // out of range at ticks 150 and 100
+        assert_close(report.time_in_range, 0.6, 1e-9);
+        assert_close(report.impermanent_loss, 0.0, 1e-6);
+    }
+
+    #[test]
+    fn rebalance_on_exit() {
+        // moves to [50, 250) at tick 150, swapping half of the token1 it then holds, and back
+        // to [-100, 100) at tick 0
+        let report = backtest(
+            &mut RebalanceOnExit {
+                strategy: RangeStrategy::Ticks(100),
+                drift_threshold: None,
+            },
+            (4_397_336.4, 4_427_602.6),
+            [
+                2_003_756_067.749,
+                1_993_762_791.076,
+                2_002_502_866.707,
+                1_996_257_852.340,
+                1_975_159_654.026,
+            ],
+        );
+        assert_eq!(report.rebalances, 2);
+        assert_close(report.rebalance_costs, 4_984_539.5, 1e-6);
+        assert_close(report.time_in_range, 1.0, 1e-9);
+        // the first move only costs the trade fee of its swap
+        let curve = &report.equity_curve;
+        assert_close(
+            curve[2].position_value,
+            2_005_012_269.623 - 2_509_402.1,
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn volatility_scaled() {
+        // ranges of ±2 standard deviations of the last 3 ticks: [-20, 20) at first, then
+        // [0, 100) at tick 50, [-140, 30) at tick -50 and [-20, 310) at tick 150
+        let report = backtest(
+            &mut VolatilityScaled::new(2.0, 3).unwrap(),
+            (4_169_019.8, 4_269_647.5),
+            [
+                1_998_496_068.931,
+                1_978_463_115.631,
+                1_979_788_977.679,
+                1_974_249_987.941,
+                1_958_770_591.499,
+            ],
+        );
+        assert_eq!(report.rebalances, 3);
+        assert_close(report.rebalance_costs, 7_535_257.3, 1e-6);
+        assert_close(report.time_in_range, 1.0, 1e-9);
+    }
+
+    #[test]
+    fn pool_params_make_a_config_once_complete() {
+        let mut params = PoolParams {
+            tick_spacing: Some(10),
+            trade_fee_rate: Some(2_500),
+            protocol_fee_rate: Some(120_000),
+            fund_fee_rate: None,
+        };
+        assert_eq!(params.config(1, 2), None);
+        params.fund_fee_rate = Some(40_000);
+        assert_eq!(
+            params.config(1, 2),
+            Some(BacktestConfig {
+                tick_spacing: 10,
+                trade_fee_rate: 2_500,
+                protocol_fee_rate: 120_000,
+                fund_fee_rate: 40_000,
+                amount_0: 1,
+                amount_1: 2,
+            })
+        );
+    }
+}
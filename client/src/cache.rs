@@ -0,0 +1,219 @@
+use crate::client::{deserialize_anchor_account, get_accounts_with_slot};
+use crate::error::{ClientError, Result};
+use anchor_client::anchor_lang::AccountDeserialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    /// `None` when the account did not exist at `slot`.
+    account: Option<Account>,
+    slot: u64,
+    fetched_at: Instant,
+}
+
+/// Accounts keyed by pubkey, with the slot they were read at. Entries are refetched once
+/// older than `ttl`, or than the slot a caller asks for; without `ttl` they are kept until
+/// invalidated, e.g. to work on a frozen [`crate::snapshot::PoolSnapshot`].
+#[derive(Debug, Default)]
+pub struct AccountCache {
+    ttl: Option<Duration>,
+    entries: RwLock<HashMap<Pubkey, CacheEntry>>,
+}
+
+impl AccountCache {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn read_entries(&self) -> std::sync::RwLockReadGuard<'_, HashMap<Pubkey, CacheEntry>> {
+        self.entries.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn write_entries(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<Pubkey, CacheEntry>> {
+        self.entries.write().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn is_fresh(&self, entry: &CacheEntry, min_slot: Option<u64>) -> bool {
+        self.ttl.is_none_or(|ttl| entry.fetched_at.elapsed() < ttl)
+            && min_slot.is_none_or(|min_slot| entry.slot >= min_slot)
+    }
+
+    /// Cached account of `key`, without fetching it.
+    pub fn get_cached(&self, key: &Pubkey) -> Option<(Option<Account>, u64)> {
+        self.read_entries()
+            .get(key)
+            .filter(|entry| self.is_fresh(entry, None))
+            .map(|entry| (entry.account.clone(), entry.slot))
+    }
+
+    /// Accounts of `keys`, in the same order, fetching those missing from the cache, expired,
+    /// or read before `min_slot`.
+    pub async fn get_accounts(
+        &self,
+        rpc_client: &RpcClient,
+        keys: &[Pubkey],
+        min_slot: Option<u64>,
+    ) -> Result<Vec<Option<Account>>> {
+        let stale_keys = {
+            let entries = self.read_entries();
+            keys.iter()
+                .filter(|key| {
+                    !entries
+                        .get(key)
+                        .is_some_and(|entry| self.is_fresh(entry, min_slot))
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        if !stale_keys.is_empty() {
+            let (slot, accounts) =
+                get_accounts_with_slot(rpc_client, &stale_keys, min_slot).await?;
+            for (key, account) in stale_keys.iter().zip(accounts) {
+                self.insert(*key, account, slot);
+            }
+        }
+
+        let entries = self.read_entries();
+        Ok(keys
+            .iter()
+            .map(|key| entries.get(key).and_then(|entry| entry.account.clone()))
+            .collect())
+    }
+
+    pub async fn get_account(&self, rpc_client: &RpcClient, key: &Pubkey) -> Result<Account> {
+        self.get_accounts(rpc_client, &[*key], None)
+            .await?
+            .pop()
+            .flatten()
+            .ok_or(ClientError::AccountNotFound(*key))
+    }
+
+    pub async fn get_anchor_account<T: AccountDeserialize>(
+        &self,
+        rpc_client: &RpcClient,
+        key: &Pubkey,
+    ) -> Result<T> {
+        deserialize_anchor_account(&self.get_account(rpc_client, key).await?)
+    }
+
+    /// Store `account` as read at `slot`, unless a later read is already cached.
+    pub fn insert(&self, key: Pubkey, account: Option<Account>, slot: u64) {
+        let mut entries = self.write_entries();
+        if entries.get(&key).is_some_and(|entry| entry.slot > slot) {
+            return;
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                account,
+                slot,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, key: &Pubkey) {
+        self.write_entries().remove(key);
+    }
+
+    /// Drop every entry read before `slot`.
+    pub fn invalidate_before(&self, slot: u64) {
+        self.write_entries().retain(|_, entry| entry.slot >= slot);
+    }
+
+    pub fn clear(&self) {
+        self.write_entries().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.read_entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read_entries().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            ..Account::default()
+        }
+    }
+
+    #[test]
+    fn keeps_the_latest_read() {
+        let cache = AccountCache::new(None);
+        let key = Pubkey::new_unique();
+        cache.insert(key, Some(account(1)), 10);
+        cache.insert(key, Some(account(2)), 9);
+        assert_eq!(cache.get_cached(&key), Some((Some(account(1)), 10)));
+
+        cache.insert(key, None, 10);
+        assert_eq!(cache.get_cached(&key), Some((None, 10)));
+        cache.insert(key, Some(account(3)), 11);
+        assert_eq!(cache.get_cached(&key), Some((Some(account(3)), 11)));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn expires_entries_after_the_ttl() {
+        let key = Pubkey::new_unique();
+        let expired = AccountCache::new(Some(Duration::ZERO));
+        expired.insert(key, Some(account(1)), 10);
+        assert_eq!(expired.get_cached(&key), None);
+        // expired entries are refetched, not dropped
+        assert_eq!(expired.len(), 1);
+
+        let fresh = AccountCache::new(Some(Duration::from_secs(3600)));
+        fresh.insert(key, Some(account(1)), 10);
+        assert_eq!(fresh.get_cached(&key), Some((Some(account(1)), 10)));
+    }
+
+    #[test]
+    fn entries_read_before_the_min_slot_are_stale() {
+        let cache = AccountCache::new(None);
+        let key = Pubkey::new_unique();
+        cache.insert(key, Some(account(1)), 10);
+        let entry = cache.read_entries().get(&key).cloned().unwrap();
+        assert!(cache.is_fresh(&entry, None));
+        assert!(cache.is_fresh(&entry, Some(10)));
+        assert!(!cache.is_fresh(&entry, Some(11)));
+    }
+
+    #[test]
+    fn invalidates_by_key_and_slot() {
+        let cache = AccountCache::new(None);
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        for (slot, key) in (10..).zip(keys) {
+            cache.insert(key, Some(account(slot)), slot);
+        }
+
+        cache.invalidate(&keys[2]);
+        assert_eq!(cache.get_cached(&keys[2]), None);
+        assert_eq!(cache.len(), 2);
+
+        cache.invalidate_before(11);
+        assert_eq!(cache.get_cached(&keys[0]), None);
+        assert_eq!(cache.get_cached(&keys[1]), Some((Some(account(11)), 11)));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -1,94 +1,227 @@
-use anchor_client::anchor_lang::AccountDeserialize;
-use anyhow::Result;
-use solana_account_decoder::{
-    parse_token::{TokenAccountType, UiAccountState},
-    UiAccountData,
+use crate::error::{ClientError, Result};
+use crate::pda::{get_personal_position_key, get_tick_array_key};
+use anchor_client::anchor_lang::{AccountDeserialize, Discriminator};
+use futures::future::try_join_all;
+use raydium_amm_v3::states::{PoolState, TickArrayBitmapExtension};
+use solana_account_decoder::{parse_token::TokenAccountType, UiAccountData, UiAccountEncoding};
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSendTransactionConfig,
 };
-use solana_client::rpc_client::RpcClient;
-use solana_client::{rpc_config::RpcSendTransactionConfig, rpc_request::TokenAccountsFilter};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::account::Account;
 use solana_sdk::{
     commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
     transaction::Transaction,
 };
+use spl_associated_token_account::get_associated_token_address;
+use spl_token_2022::extension::StateWithExtensions;
+
+/// Most accounts `getMultipleAccounts` returns per request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
 
 pub fn deserialize_anchor_account<T: AccountDeserialize>(account: &Account) -> Result<T> {
     let mut data: &[u8] = &account.data;
     T::try_deserialize(&mut data).map_err(Into::into)
 }
 
-pub fn send_txn(
+pub async fn send_txn(
     client: &RpcClient,
     txn: &Transaction,
     wait_confirm: bool,
-) -> anyhow::Result<Signature> {
-    Ok(client.send_and_confirm_transaction_with_spinner_and_config(
-        txn,
-        if wait_confirm {
-            CommitmentConfig::confirmed()
-        } else {
-            CommitmentConfig::processed()
-        },
-        RpcSendTransactionConfig {
-            skip_preflight: true,
-            ..RpcSendTransactionConfig::default()
-        },
-    )?)
+) -> Result<Signature> {
+    Ok(client
+        .send_and_confirm_transaction_with_spinner_and_config(
+            txn,
+            if wait_confirm {
+                CommitmentConfig::confirmed()
+            } else {
+                CommitmentConfig::processed()
+            },
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..RpcSendTransactionConfig::default()
+            },
+        )
+        .await?)
+}
+
+pub async fn get_epoch(client: &RpcClient) -> Result<u64> {
+    Ok(client.get_epoch_info().await?.epoch)
+}
+
+/// Load any number of accounts, in the same order, requesting chunks of them concurrently.
+pub async fn get_accounts(client: &RpcClient, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+    let chunks = try_join_all(
+        keys.chunks(MAX_MULTIPLE_ACCOUNTS)
+            .map(|chunk| client.get_multiple_accounts(chunk)),
+    )
+    .await?;
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+/// Like [`get_accounts`], also returning the lowest slot the chunks were read at. Every chunk
+/// is read at `min_context_slot` or later.
+pub async fn get_accounts_with_slot(
+    client: &RpcClient,
+    keys: &[Pubkey],
+    min_context_slot: Option<u64>,
+) -> Result<(u64, Vec<Option<Account>>)> {
+    let responses = try_join_all(keys.chunks(MAX_MULTIPLE_ACCOUNTS).map(|chunk| {
+        client.get_multiple_accounts_with_config(
+            chunk,
+            RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                min_context_slot,
+                ..RpcAccountInfoConfig::default()
+            },
+        )
+    }))
+    .await?;
+    let slot = responses
+        .iter()
+        .map(|response| response.context.slot)
+        .min()
+        .unwrap_or_default();
+    Ok((
+        slot,
+        responses
+            .into_iter()
+            .flat_map(|response| response.value)
+            .collect(),
+    ))
+}
+
+pub async fn get_anchor_account<T: AccountDeserialize>(
+    client: &RpcClient,
+    key: &Pubkey,
+) -> Result<T> {
+    deserialize_anchor_account(&client.get_account(key).await?)
+}
+
+/// Load and decode accounts of the same type, `None` for the missing ones.
+pub async fn get_anchor_accounts<T: AccountDeserialize>(
+    client: &RpcClient,
+    keys: &[Pubkey],
+) -> Result<Vec<Option<T>>> {
+    get_accounts(client, keys)
+        .await?
+        .iter()
+        .map(|account| account.as_ref().map(deserialize_anchor_account).transpose())
+        .collect()
+}
+
+/// Every account of type `T` owned by `program_id` which also matches `filters`.
+pub async fn get_program_anchor_accounts<T: AccountDeserialize + Discriminator>(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    mut filters: Vec<RpcFilterType>,
+) -> Result<Vec<(Pubkey, T)>> {
+    filters.insert(
+        0,
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &T::discriminator())),
+    );
+    client
+        .get_program_accounts_with_config(
+            program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .await?
+        .iter()
+        .map(|(key, account)| Ok((*key, deserialize_anchor_account(account)?)))
+        .collect()
+}
+
+/// Balance of `token_account`, zero when it does not exist yet.
+pub async fn get_token_balance(client: &RpcClient, token_account: &Pubkey) -> Result<u64> {
+    if client
+        .get_account_with_commitment(token_account, client.commitment())
+        .await?
+        .value
+        .is_none()
+    {
+        return Ok(0);
+    }
+    Ok(client
+        .get_token_account_balance(token_account)
+        .await?
+        .amount
+        .parse::<u64>()
+        .map_err(|err| ClientError::InvalidResponse(format!("token amount: {}", err)))?)
+}
+
+/// Balance of `owner`'s WSOL ATA, `None` when it does not exist. Flows wrap only what it
+/// lacks, and only close it when they created it.
+pub async fn get_wsol_balance(client: &RpcClient, owner: &Pubkey) -> Result<Option<u64>> {
+    let wsol_account = get_associated_token_address(owner, &spl_token::native_mint::id());
+    match client
+        .get_account_with_commitment(&wsol_account, client.commitment())
+        .await?
+        .value
+    {
+        Some(account) => Ok(Some(
+            StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?
+                .base
+                .amount,
+        )),
+        None => Ok(None),
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) struct TokenInfo {
-    key: Pubkey,
-    mint: Pubkey,
-    amount: u64,
-    decimals: u8,
+pub struct TokenInfo {
+    pub key: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
 }
 
-pub(crate) fn get_nft_account_and_position_by_owner(
+pub async fn get_nft_account_and_position_by_owner(
     client: &RpcClient,
     owner: &Pubkey,
     raydium_amm_v3_program: &Pubkey,
-) -> (Vec<TokenInfo>, Vec<Pubkey>) {
-    let all_tokens = client
-        .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(spl_token::id()))
-        .unwrap();
+) -> Result<(Vec<TokenInfo>, Vec<Pubkey>)> {
+    // position NFTs may be minted under either token program
+    let (tokens, tokens_2022) = tokio::try_join!(
+        client.get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(spl_token::id())),
+        client.get_token_accounts_by_owner(
+            owner,
+            TokenAccountsFilter::ProgramId(spl_token_2022::id())
+        )
+    )?;
     let mut nft_account = Vec::new();
     let mut user_position_account = Vec::new();
-    for keyed_account in all_tokens {
+    for keyed_account in tokens.into_iter().chain(tokens_2022) {
         if let UiAccountData::Json(parsed_account) = keyed_account.account.data {
-            if parsed_account.program == "spl-token" {
+            if parsed_account.program == "spl-token" || parsed_account.program == "spl-token-2022" {
                 if let Ok(TokenAccountType::Account(ui_token_account)) =
                     serde_json::from_value(parsed_account.parsed)
                 {
-                    let _frozen = ui_token_account.state == UiAccountState::Frozen;
-
-                    let token = ui_token_account
-                        .mint
-                        .parse::<Pubkey>()
-                        .unwrap_or_else(|err| panic!("Invalid mint: {}", err));
-                    let token_account = keyed_account
-                        .pubkey
-                        .parse::<Pubkey>()
-                        .unwrap_or_else(|err| panic!("Invalid token account: {}", err));
+                    let token = ui_token_account.mint.parse::<Pubkey>().map_err(|err| {
+                        ClientError::InvalidResponse(format!("Invalid mint: {}", err))
+                    })?;
+                    let token_account = keyed_account.pubkey.parse::<Pubkey>().map_err(|err| {
+                        ClientError::InvalidResponse(format!("Invalid token account: {}", err))
+                    })?;
                     let token_amount = ui_token_account
                         .token_amount
                         .amount
                         .parse::<u64>()
-                        .unwrap_or_else(|err| panic!("Invalid token amount: {}", err));
-
-                    let _close_authority = ui_token_account.close_authority.map_or(*owner, |s| {
-                        s.parse::<Pubkey>()
-                            .unwrap_or_else(|err| panic!("Invalid close authority: {}", err))
-                    });
+                        .map_err(|err| {
+                            ClientError::InvalidResponse(format!("Invalid token amount: {}", err))
+                        })?;
 
                     if ui_token_account.token_amount.decimals == 0 && token_amount == 1 {
-                        let (position_pda, _) = Pubkey::find_program_address(
-                            &[
-                                raydium_amm_v3::states::POSITION_SEED.as_bytes(),
-                                token.to_bytes().as_ref(),
-                            ],
-                            &raydium_amm_v3_program,
-                        );
+                        let position_pda =
+                            get_personal_position_key(raydium_amm_v3_program, &token);
                         nft_account.push(TokenInfo {
                             key: token_account,
                             mint: token,
@@ -101,5 +234,36 @@ pub(crate) fn get_nft_account_and_position_by_owner(
             }
         }
     }
-    (nft_account, user_position_account)
+    Ok((nft_account, user_position_account))
+}
+
+/// Keys of the tick array holding the current tick and of up to `count - 1` following
+/// initialized tick arrays in the swap direction.
+pub fn get_cur_and_next_tick_array_keys(
+    raydium_amm_v3_program: &Pubkey,
+    pool_account_key: &Pubkey,
+    pool_state: &PoolState,
+    tickarray_bitmap_extension: &TickArrayBitmapExtension,
+    zero_for_one: bool,
+    count: usize,
+) -> Result<Vec<Pubkey>> {
+    let (_, mut current_valid_tick_array_start_index) = pool_state
+        .get_first_initialized_tick_array(&Some(tickarray_bitmap_extension), zero_for_one)?;
+    let mut tick_array_keys = Vec::new();
+    while tick_array_keys.len() < count {
+        tick_array_keys.push(get_tick_array_key(
+            raydium_amm_v3_program,
+            pool_account_key,
+            current_valid_tick_array_start_index,
+        ));
+        match pool_state.next_initialized_tick_array_start_index(
+            &Some(tickarray_bitmap_extension),
+            current_valid_tick_array_start_index,
+            zero_for_one,
+        )? {
+            Some(next_index) => current_valid_tick_array_start_index = next_index,
+            None => break,
+        }
+    }
+    Ok(tick_array_keys)
 }
@@ -0,0 +1,147 @@
+use crate::error::{ClientError, Result};
+use crate::pda::{get_amm_config_key, get_pool_key, get_tickarray_bitmap_extension_key};
+use ini::{Ini, Properties};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientConfig {
+    pub http_url: String,
+    pub ws_url: String,
+    pub payer_path: String,
+    pub admin_path: String,
+    pub raydium_v3_program: Pubkey,
+    pub slippage: f64,
+    pub amm_config_key: Pubkey,
+    pub mint0: Option<Pubkey>,
+    pub mint1: Option<Pubkey>,
+    pub pool_id_account: Option<Pubkey>,
+    pub tickarray_bitmap_extension: Option<Pubkey>,
+    pub amm_config_index: u16,
+    /// Owners of the ATAs swept protocol and fund fees go to, the admin by default.
+    pub protocol_fee_recipient: Option<Pubkey>,
+    pub fund_fee_recipient: Option<Pubkey>,
+}
+
+impl ClientConfig {
+    /// Pool from the `[Pool]` section.
+    pub fn pool_id(&self) -> Result<Pubkey> {
+        self.pool_id_account
+            .ok_or_else(|| ClientError::Config("mint0 and mint1 must be set".to_string()))
+    }
+
+    pub fn tickarray_bitmap_extension(&self) -> Result<Pubkey> {
+        self.tickarray_bitmap_extension
+            .ok_or_else(|| ClientError::Config("mint0 and mint1 must be set".to_string()))
+    }
+}
+
+fn get_value<'a>(section: &'a Properties, section_name: &str, key: &str) -> Result<&'a str> {
+    section
+        .get(key)
+        .ok_or_else(|| ClientError::Config(format!("{} is missing in [{}]", key, section_name)))
+}
+
+fn get_non_empty_value<'a>(
+    section: &'a Properties,
+    section_name: &str,
+    key: &str,
+) -> Result<&'a str> {
+    let value = get_value(section, section_name, key)?;
+    if value.is_empty() {
+        return Err(ClientError::Config(format!("{} must not be empty", key)));
+    }
+    Ok(value)
+}
+
+fn parse_pubkey(key: &str, value: &str) -> Result<Pubkey> {
+    Pubkey::from_str(value)
+        .map_err(|err| ClientError::Config(format!("invalid {} {}: {}", key, value, err)))
+}
+
+/// An optional pubkey, unset when missing or empty.
+fn get_optional_pubkey(section: &Properties, key: &str) -> Result<Option<Pubkey>> {
+    match section.get(key) {
+        Some(value) if !value.is_empty() => Ok(Some(parse_pubkey(key, value)?)),
+        _ => Ok(None),
+    }
+}
+
+pub fn load_cfg(client_config: &str) -> Result<ClientConfig> {
+    let config = Ini::load_from_file(client_config)
+        .map_err(|err| ClientError::Config(format!("failed to load {}: {}", client_config, err)))?;
+    let global_section = config
+        .section(Some("Global"))
+        .ok_or_else(|| ClientError::Config("Didn't define Global section".to_string()))?;
+    let http_url = get_non_empty_value(global_section, "Global", "http_url")?.to_string();
+    let ws_url = get_non_empty_value(global_section, "Global", "ws_url")?.to_string();
+    let payer_path = get_non_empty_value(global_section, "Global", "payer_path")?.to_string();
+    let admin_path = get_non_empty_value(global_section, "Global", "admin_path")?.to_string();
+
+    let raydium_v3_program = parse_pubkey(
+        "raydium_v3_program",
+        get_non_empty_value(global_section, "Global", "raydium_v3_program")?,
+    )?;
+    let slippage = get_value(global_section, "Global", "slippage")?
+        .parse::<f64>()
+        .map_err(|err| ClientError::Config(format!("invalid slippage: {}", err)))?;
+    let protocol_fee_recipient = get_optional_pubkey(global_section, "protocol_fee_recipient")?;
+    let fund_fee_recipient = get_optional_pubkey(global_section, "fund_fee_recipient")?;
+
+    let pool_section = config
+        .section(Some("Pool"))
+        .ok_or_else(|| ClientError::Config("Didn't define Pool section".to_string()))?;
+    let mut mint0 = None;
+    let mint0_str = get_value(pool_section, "Pool", "mint0")?;
+    if !mint0_str.is_empty() {
+        mint0 = Some(parse_pubkey("mint0", mint0_str)?);
+    }
+    let mut mint1 = None;
+    let mint1_str = get_value(pool_section, "Pool", "mint1")?;
+    if !mint1_str.is_empty() {
+        mint1 = Some(parse_pubkey("mint1", mint1_str)?);
+    }
+    let amm_config_index = get_value(pool_section, "Pool", "amm_config_index")?
+        .parse::<u16>()
+        .map_err(|err| ClientError::Config(format!("invalid amm_config_index: {}", err)))?;
+
+    let amm_config_key = get_amm_config_key(&raydium_v3_program, amm_config_index);
+
+    let pool_id_account = match (mint0, mint1) {
+        (Some(mint_a), Some(mint_b)) => {
+            let (token_mint_0, token_mint_1) = if mint_a > mint_b {
+                (mint_b, mint_a)
+            } else {
+                (mint_a, mint_b)
+            };
+            mint0 = Some(token_mint_0);
+            mint1 = Some(token_mint_1);
+            Some(get_pool_key(
+                &raydium_v3_program,
+                &amm_config_key,
+                &token_mint_0,
+                &token_mint_1,
+            ))
+        }
+        _ => None,
+    };
+    let tickarray_bitmap_extension = pool_id_account
+        .map(|pool_id| get_tickarray_bitmap_extension_key(&raydium_v3_program, &pool_id));
+
+    Ok(ClientConfig {
+        http_url,
+        ws_url,
+        payer_path,
+        admin_path,
+        raydium_v3_program,
+        slippage,
+        amm_config_key,
+        mint0,
+        mint1,
+        pool_id_account,
+        tickarray_bitmap_extension,
+        amm_config_index,
+        protocol_fee_recipient,
+        fund_fee_recipient,
+    })
+}
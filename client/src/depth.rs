@@ -0,0 +1,367 @@
+use crate::error::{ClientError, Result};
+use crate::range::ticks_for_price_ratio;
+use crate::utils::{multipler, serialize_display, sqrt_price_x64_to_f64};
+use raydium_amm_v3::libraries::tick_math;
+use raydium_amm_v3::states::{PoolState, TickArrayState};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Width of the longest bar of the text histogram.
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// Liquidity active between two consecutive initialized ticks, with the token amounts it
+/// holds within the requested window. Prices and amounts are in UI units.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LiquidityBand {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub price_lower: f64,
+    pub price_upper: f64,
+    #[serde(serialize_with = "serialize_display")]
+    pub liquidity: u128,
+    pub amount_0: f64,
+    pub amount_1: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for DepthFormat {
+    type Err = ClientError;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "text" => Ok(DepthFormat::Text),
+            "csv" => Ok(DepthFormat::Csv),
+            "json" => Ok(DepthFormat::Json),
+            _ => Err(ClientError::InvalidArgument(format!(
+                "unknown depth format {}, expected text, csv or json",
+                format
+            ))),
+        }
+    }
+}
+
+/// Initialized ticks of `tick_arrays` with their `liquidity_net`, ascending.
+pub(crate) fn get_initialized_ticks<'a>(
+    tick_arrays: impl IntoIterator<Item = &'a TickArrayState>,
+) -> Vec<(i32, i128)> {
+    let mut ticks = tick_arrays
+        .into_iter()
+        .flat_map(|tick_array| {
+            // copied out of the packed account
+            let tick_states = tick_array.ticks;
+            tick_states
+                .into_iter()
+                .filter(|tick_state| tick_state.liquidity_gross != 0)
+                .map(|tick_state| (tick_state.tick, tick_state.liquidity_net))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    ticks.sort_by_key(|(tick, _)| *tick);
+    ticks
+}
+
+fn sqrt_price_at_tick(tick: i32) -> Result<f64> {
+    let sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(tick)?;
+    Ok(sqrt_price_x64_to_f64(sqrt_price_x64))
+}
+
+/// Rebuild the liquidity active in every range between initialized ticks, within `±percent`
+/// (e.g. `0.1` for ±10%) of the current price, by applying each tick's `liquidity_net` from
+/// the lowest one. Ranges are clipped to the window and ranges without liquidity skipped.
+pub fn get_liquidity_depth<'a>(
+    pool_state: &PoolState,
+    tick_arrays: impl IntoIterator<Item = &'a TickArrayState>,
+    percent: f64,
+) -> Result<Vec<LiquidityBand>> {
+    if percent <= 0.0 || percent >= 1.0 {
+        return Err(ClientError::InvalidArgument(format!(
+            "percent must be within (0, 1), got {}",
+            percent
+        )));
+    }
+    let tick_current = pool_state.tick_current;
+    let window_lower =
+        (tick_current + ticks_for_price_ratio(1.0 - percent)).max(tick_math::MIN_TICK);
+    let window_upper =
+        (tick_current + ticks_for_price_ratio(1.0 + percent)).min(tick_math::MAX_TICK);
+    let sqrt_price_current = sqrt_price_x64_to_f64(pool_state.sqrt_price_x64);
+    // raw to UI
+    let price_multiplier =
+        multipler(pool_state.mint_decimals_0) / multipler(pool_state.mint_decimals_1);
+    let amount_0_divisor = multipler(pool_state.mint_decimals_0);
+    let amount_1_divisor = multipler(pool_state.mint_decimals_1);
+
+    let ticks = get_initialized_ticks(tick_arrays);
+    let mut bands = Vec::new();
+    let mut liquidity: i128 = 0;
+    for pair in ticks.windows(2) {
+        let (tick_lower, liquidity_net) = pair[0];
+        let tick_upper = pair[1].0;
+        liquidity = liquidity.checked_add(liquidity_net).ok_or_else(|| {
+            ClientError::MathOverflow(format!("liquidity at tick {}", tick_lower))
+        })?;
+        let tick_lower = tick_lower.max(window_lower);
+        let tick_upper = tick_upper.min(window_upper);
+        if liquidity <= 0 || tick_lower >= tick_upper {
+            continue;
+        }
+
+        let sqrt_price_lower = sqrt_price_at_tick(tick_lower)?;
+        let sqrt_price_upper = sqrt_price_at_tick(tick_upper)?;
+        // token0 is held above the current price and token1 below it
+        let sqrt_price = sqrt_price_current.clamp(sqrt_price_lower, sqrt_price_upper);
+        let liquidity_f64 = liquidity as f64;
+        bands.push(LiquidityBand {
+            tick_lower,
+            tick_upper,
+            price_lower: sqrt_price_lower * sqrt_price_lower * price_multiplier,
+            price_upper: sqrt_price_upper * sqrt_price_upper * price_multiplier,
+            liquidity: liquidity as u128,
+            amount_0: liquidity_f64 * (1.0 / sqrt_price - 1.0 / sqrt_price_upper)
+                / amount_0_divisor,
+            amount_1: liquidity_f64 * (sqrt_price - sqrt_price_lower) / amount_1_divisor,
+        });
+    }
+    Ok(bands)
+}
+
+/// Render `bands` as a text histogram of liquidity, or as CSV or JSON for plotting.
+pub fn render_depth(bands: &[LiquidityBand], format: DepthFormat) -> Result<String> {
+    let mut output = String::new();
+    match format {
+        DepthFormat::Text => {
+            let max_liquidity = bands
+                .iter()
+                .map(|band| band.liquidity)
+                .max()
+                .unwrap_or_default();
+            // bands are listed from the highest price down, like an order book
+            for band in bands.iter().rev() {
+                let bar_len = if max_liquidity == 0 {
+                    0
+                } else {
+                    (band.liquidity as f64 / max_liquidity as f64 * HISTOGRAM_WIDTH as f64).ceil()
+                        as usize
+                };
+                output.push_str(&format!(
+                    "{:>14.6} - {:<14.6} {:<width$} amount_0:{:.6}, amount_1:{:.6}\n",
+                    band.price_lower,
+                    band.price_upper,
+                    "#".repeat(bar_len),
+                    band.amount_0,
+                    band.amount_1,
+                    width = HISTOGRAM_WIDTH
+                ));
+            }
+            output.push_str(&format!(
+                "total amount_0:{:.6}, amount_1:{:.6}\n",
+                bands.iter().map(|band| band.amount_0).sum::<f64>(),
+                bands.iter().map(|band| band.amount_1).sum::<f64>()
+            ));
+        }
+        DepthFormat::Csv => {
+            output.push_str(
+                "tick_lower,tick_upper,price_lower,price_upper,liquidity,amount_0,amount_1\n",
+            );
+            for band in bands {
+                output.push_str(&format!(
+                    "{},{},{},{},{},{},{}\n",
+                    band.tick_lower,
+                    band.tick_upper,
+                    band.price_lower,
+                    band.price_upper,
+                    band.liquidity,
+                    band.amount_0,
+                    band.amount_1
+                ));
+            }
+        }
+        DepthFormat::Json => {
+            output = serde_json::to_string_pretty(bands)
+                .map_err(|err| ClientError::InvalidArgument(err.to_string()))?;
+            output.push('\n');
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool_state(tick_current: i32) -> PoolState {
+        let mut pool_state = PoolState::default();
+        pool_state.tick_current = tick_current;
+        pool_state.sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(tick_current).unwrap();
+        pool_state.tick_spacing = 10;
+        pool_state
+    }
+
+    fn tick_array(ticks: &[(i32, i128)]) -> TickArrayState {
+        let mut tick_array = TickArrayState::default();
+        // copied out of the packed account
+        let mut tick_states = tick_array.ticks;
+        for (tick_state, (tick, liquidity_net)) in tick_states.iter_mut().zip(ticks) {
+            tick_state.tick = *tick;
+            tick_state.liquidity_net = *liquidity_net;
+            tick_state.liquidity_gross = liquidity_net.unsigned_abs();
+        }
+        tick_array.ticks = tick_states;
+        tick_array
+    }
+
+    fn sqrt_price(tick: i32) -> f64 {
+        1.0001_f64.powf(tick as f64 / 2.0)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-6 * expected.abs().max(1e-6),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn liquidity_accumulates_across_ticks() {
+        // the range without liquidity between 200 and 300 is skipped
+        let tick_arrays = [tick_array(&[
+            (-200, 1_000),
+            (-100, 500),
+            (100, -500),
+            (200, -1_000),
+            (300, 200),
+            (400, -200),
+        ])];
+        let bands = get_liquidity_depth(&pool_state(0), &tick_arrays, 0.5).unwrap();
+        assert_eq!(
+            bands
+                .iter()
+                .map(|band| (band.tick_lower, band.tick_upper, band.liquidity))
+                .collect::<Vec<_>>(),
+            vec![
+                (-200, -100, 1_000),
+                (-100, 100, 1_500),
+                (100, 200, 1_000),
+                (300, 400, 200)
+            ]
+        );
+
+        // token1 below the current price, token0 above it and both in the current range
+        assert_close(bands[0].amount_0, 0.0);
+        assert_close(
+            bands[0].amount_1,
+            1_000.0 * (sqrt_price(-100) - sqrt_price(-200)),
+        );
+        assert_close(bands[1].amount_0, 1_500.0 * (1.0 - 1.0 / sqrt_price(100)));
+        assert_close(bands[1].amount_1, 1_500.0 * (1.0 - sqrt_price(-100)));
+        assert_close(
+            bands[2].amount_0,
+            1_000.0 * (1.0 / sqrt_price(100) - 1.0 / sqrt_price(200)),
+        );
+        assert_close(bands[2].amount_1, 0.0);
+        assert_close(bands[1].price_lower, 1.0001_f64.powi(-100));
+        assert_close(bands[1].price_upper, 1.0001_f64.powi(100));
+    }
+
+    #[test]
+    fn bands_are_clipped_to_the_window() {
+        let tick_arrays = [tick_array(&[(-200, 1_000), (200, -1_000)])];
+        let bands = get_liquidity_depth(&pool_state(0), &tick_arrays, 0.005).unwrap();
+        assert_eq!(bands.len(), 1);
+        assert_eq!(bands[0].tick_lower, ticks_for_price_ratio(0.995));
+        assert_eq!(bands[0].tick_upper, ticks_for_price_ratio(1.005));
+        assert_eq!(bands[0].liquidity, 1_000);
+    }
+
+    #[test]
+    fn amounts_are_in_ui_units() {
+        let mut pool_state = pool_state(0);
+        pool_state.mint_decimals_0 = 6;
+        pool_state.mint_decimals_1 = 9;
+        let tick_arrays = [tick_array(&[(-100, 1_000_000_000), (100, -1_000_000_000)])];
+        let bands = get_liquidity_depth(&pool_state, &tick_arrays, 0.5).unwrap();
+        assert_close(bands[0].amount_0, 1e9 * (1.0 - 1.0 / sqrt_price(100)) / 1e6);
+        assert_close(bands[0].amount_1, 1e9 * (1.0 - sqrt_price(-100)) / 1e9);
+        assert_close(bands[0].price_lower, 1.0001_f64.powi(-100) * 1e6 / 1e9);
+    }
+
+    #[test]
+    fn percent_must_be_within_the_unit_interval() {
+        let tick_arrays = [tick_array(&[(-100, 1), (100, -1)])];
+        for percent in [0.0, -0.1, 1.0] {
+            assert!(get_liquidity_depth(&pool_state(0), &tick_arrays, percent).is_err());
+        }
+    }
+
+    fn bands() -> Vec<LiquidityBand> {
+        vec![
+            LiquidityBand {
+                tick_lower: -10,
+                tick_upper: 0,
+                price_lower: 0.5,
+                price_upper: 1.0,
+                liquidity: 100,
+                amount_0: 0.0,
+                amount_1: 2.5,
+            },
+            LiquidityBand {
+                tick_lower: 0,
+                tick_upper: 10,
+                price_lower: 1.0,
+                price_upper: 2.0,
+                liquidity: u128::from(u64::MAX) + 1,
+                amount_0: 1.25,
+                amount_1: 0.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_row_per_band() {
+        assert_eq!(
+            render_depth(&bands(), DepthFormat::Csv).unwrap(),
+            "tick_lower,tick_upper,price_lower,price_upper,liquidity,amount_0,amount_1\n\
+             -10,0,0.5,1,100,0,2.5\n\
+             0,10,1,2,18446744073709551616,1.25,0\n"
+        );
+    }
+
+    #[test]
+    fn json_keeps_liquidity_exact() {
+        let json = render_depth(&bands(), DepthFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value[1],
+            serde_json::json!({
+                "tick_lower": 0,
+                "tick_upper": 10,
+                "price_lower": 1.0,
+                "price_upper": 2.0,
+                "liquidity": "18446744073709551616",
+                "amount_0": 1.25,
+                "amount_1": 0.0,
+            })
+        );
+        assert_eq!(value.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn text_lists_bands_from_the_highest_price_with_totals() {
+        let text = render_depth(&bands(), DepthFormat::Text).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains(&"#".repeat(HISTOGRAM_WIDTH)));
+        assert!(lines[0].trim_start().starts_with("1.000000 - 2.000000"));
+        // 100 of 2^64 still shows one mark
+        assert!(lines[1].contains(" # "));
+        assert_eq!(lines[2], "total amount_0:1.250000, amount_1:2.500000");
+    }
+}
@@ -0,0 +1,315 @@
+use crate::client::{get_accounts, get_anchor_accounts, get_program_anchor_accounts};
+use crate::error::{ClientError, Result};
+use crate::pda::get_pool_key;
+use crate::utils::{multipler, serialize_display, serialize_pubkey, sqrt_price_x64_to_f64};
+use raydium_amm_v3::states::{AmmConfig, PoolState};
+use serde::Serialize;
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::StateWithExtensions;
+use std::collections::HashMap;
+
+/// Offset of `PoolState.amm_config` in the account, after the discriminator and bump.
+const AMM_CONFIG_OFFSET: usize = 8 + 1;
+/// Offsets of `PoolState.token_mint_0/1`, after the amm_config and owner.
+const TOKEN_MINT_0_OFFSET: usize = AMM_CONFIG_OFFSET + 32 + 32;
+const TOKEN_MINT_1_OFFSET: usize = TOKEN_MINT_0_OFFSET + 32;
+
+/// A pool with its fee tier, price and value locked, amounts and prices being in UI units.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolInfo {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub amm_config: Pubkey,
+    pub amm_config_index: u16,
+    pub trade_fee_rate: u32,
+    pub tick_spacing: u16,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_1: Pubkey,
+    /// Price of token0 in token1.
+    pub price: f64,
+    #[serde(serialize_with = "serialize_display")]
+    pub liquidity: u128,
+    /// Vault balances, less the protocol and fund fees they hold.
+    pub amount_0: f64,
+    pub amount_1: f64,
+    /// Value locked, in token1.
+    pub tvl: f64,
+}
+
+/// Every `AmmConfig`, i.e. fee tier, of the program.
+pub async fn get_amm_configs(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+) -> Result<HashMap<Pubkey, AmmConfig>> {
+    Ok(
+        get_program_anchor_accounts::<AmmConfig>(rpc_client, raydium_v3_program, Vec::new())
+            .await?
+            .into_iter()
+            .collect(),
+    )
+}
+
+/// Describe `pools`, loading their vaults.
+pub async fn get_pool_infos(
+    rpc_client: &RpcClient,
+    pools: &[(Pubkey, PoolState)],
+    amm_configs: &HashMap<Pubkey, AmmConfig>,
+) -> Result<Vec<PoolInfo>> {
+    let vault_keys = pools
+        .iter()
+        .flat_map(|(_, pool)| [pool.token_vault_0, pool.token_vault_1])
+        .collect::<Vec<_>>();
+    let vaults = get_accounts(rpc_client, &vault_keys).await?;
+    let mut vault_amounts = Vec::with_capacity(vaults.len());
+    for (key, vault) in vault_keys.iter().zip(vaults) {
+        let vault = vault.ok_or(ClientError::AccountNotFound(*key))?;
+        vault_amounts.push(
+            StateWithExtensions::<spl_token_2022::state::Account>::unpack(&vault.data)?
+                .base
+                .amount,
+        );
+    }
+
+    pools
+        .iter()
+        .zip(vault_amounts.chunks(2))
+        .map(|((pool_id, pool), vault_amounts)| {
+            let amm_config = amm_configs
+                .get(&pool.amm_config)
+                .ok_or(ClientError::AccountNotFound(pool.amm_config))?;
+            Ok(pool_info(
+                *pool_id,
+                pool,
+                amm_config,
+                vault_amounts[0],
+                vault_amounts[1],
+            ))
+        })
+        .collect()
+}
+
+/// Describe `pool` from the balances of its vaults.
+fn pool_info(
+    pool_id: Pubkey,
+    pool: &PoolState,
+    amm_config: &AmmConfig,
+    vault_amount_0: u64,
+    vault_amount_1: u64,
+) -> PoolInfo {
+    let sqrt_price = sqrt_price_x64_to_f64(pool.sqrt_price_x64);
+    let price =
+        sqrt_price * sqrt_price * multipler(pool.mint_decimals_0) / multipler(pool.mint_decimals_1);
+    let amount_0 = vault_amount_0
+        .saturating_sub(pool.protocol_fees_token_0)
+        .saturating_sub(pool.fund_fees_token_0) as f64
+        / multipler(pool.mint_decimals_0);
+    let amount_1 = vault_amount_1
+        .saturating_sub(pool.protocol_fees_token_1)
+        .saturating_sub(pool.fund_fees_token_1) as f64
+        / multipler(pool.mint_decimals_1);
+    PoolInfo {
+        pool_id,
+        amm_config: pool.amm_config,
+        amm_config_index: amm_config.index,
+        trade_fee_rate: amm_config.trade_fee_rate,
+        tick_spacing: amm_config.tick_spacing,
+        token_mint_0: pool.token_mint_0,
+        token_mint_1: pool.token_mint_1,
+        price,
+        liquidity: pool.liquidity,
+        amount_0,
+        amount_1,
+        tvl: amount_0 * price + amount_1,
+    }
+}
+
+/// Keys of the `mint_a`/`mint_b` pools under each of `amm_configs`, in the same order.
+fn pool_keys_by_mints<'a>(
+    raydium_v3_program: &Pubkey,
+    amm_configs: impl IntoIterator<Item = &'a Pubkey>,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+) -> Vec<Pubkey> {
+    let (token_mint_0, token_mint_1) = if mint_a < mint_b {
+        (mint_a, mint_b)
+    } else {
+        (mint_b, mint_a)
+    };
+    amm_configs
+        .into_iter()
+        .map(|amm_config| get_pool_key(raydium_v3_program, amm_config, token_mint_0, token_mint_1))
+        .collect()
+}
+
+/// Pools of the `mint_a`/`mint_b` pair under every fee tier, derived rather than searched.
+pub async fn find_pools_by_mints(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+) -> Result<Vec<PoolInfo>> {
+    let amm_configs = get_amm_configs(rpc_client, raydium_v3_program).await?;
+    let pool_keys = pool_keys_by_mints(raydium_v3_program, amm_configs.keys(), mint_a, mint_b);
+    let pools = pool_keys
+        .iter()
+        .zip(get_anchor_accounts::<PoolState>(rpc_client, &pool_keys).await?)
+        .filter_map(|(pool_id, pool)| pool.map(|pool| (*pool_id, pool)))
+        .collect::<Vec<_>>();
+    get_pool_infos(rpc_client, &pools, &amm_configs).await
+}
+
+/// Every pool trading `mint`, on either side.
+pub async fn find_pools_by_mint(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Vec<PoolInfo>> {
+    let mint_filter = |offset| {
+        vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            offset,
+            mint.as_ref(),
+        ))]
+    };
+    let (amm_configs, mut pools, pools_1) = tokio::try_join!(
+        get_amm_configs(rpc_client, raydium_v3_program),
+        get_program_anchor_accounts::<PoolState>(
+            rpc_client,
+            raydium_v3_program,
+            mint_filter(TOKEN_MINT_0_OFFSET)
+        ),
+        get_program_anchor_accounts::<PoolState>(
+            rpc_client,
+            raydium_v3_program,
+            mint_filter(TOKEN_MINT_1_OFFSET)
+        )
+    )?;
+    pools.extend(pools_1);
+    get_pool_infos(rpc_client, &pools, &amm_configs).await
+}
+
+/// Every pool of the program, or only those of `amm_config` when given.
+pub async fn get_pools(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    amm_config: Option<&Pubkey>,
+) -> Result<Vec<(Pubkey, PoolState)>> {
+    let filters = amm_config
+        .map(|amm_config| {
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                AMM_CONFIG_OFFSET,
+                amm_config.as_ref(),
+            ))
+        })
+        .into_iter()
+        .collect();
+    get_program_anchor_accounts::<PoolState>(rpc_client, raydium_v3_program, filters).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::deserialize_anchor_account;
+    use anchor_client::anchor_lang::__private::bytemuck;
+    use anchor_client::anchor_lang::Discriminator;
+    use raydium_amm_v3::libraries::fixed_point_64;
+    use solana_sdk::account::Account;
+
+    #[test]
+    fn derives_a_pool_per_amm_config() {
+        let program = Pubkey::new_unique();
+        let amm_configs = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (token_mint_0, token_mint_1) = if mint_a < mint_b {
+            (mint_a, mint_b)
+        } else {
+            (mint_b, mint_a)
+        };
+
+        let pool_keys = pool_keys_by_mints(&program, &amm_configs, &mint_a, &mint_b);
+        assert_eq!(
+            pool_keys,
+            amm_configs
+                .iter()
+                .map(|amm_config| get_pool_key(&program, amm_config, &token_mint_0, &token_mint_1))
+                .collect::<Vec<_>>()
+        );
+        assert_ne!(pool_keys[0], pool_keys[1]);
+        // the mints are sorted, whichever order they are given in
+        assert_eq!(
+            pool_keys_by_mints(&program, &amm_configs, &mint_b, &mint_a),
+            pool_keys
+        );
+    }
+
+    #[test]
+    fn filter_offsets_match_the_pool_layout() {
+        let mut pool = PoolState::default();
+        pool.amm_config = Pubkey::new_unique();
+        pool.owner = Pubkey::new_unique();
+        pool.token_mint_0 = Pubkey::new_unique();
+        pool.token_mint_1 = Pubkey::new_unique();
+        let mut data = PoolState::discriminator().to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(&pool));
+        let field = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+
+        assert_eq!(field(AMM_CONFIG_OFFSET), pool.amm_config);
+        assert_eq!(field(TOKEN_MINT_0_OFFSET), pool.token_mint_0);
+        assert_eq!(field(TOKEN_MINT_1_OFFSET), pool.token_mint_1);
+        let account = Account {
+            data,
+            ..Account::default()
+        };
+        let loaded = deserialize_anchor_account::<PoolState>(&account).unwrap();
+        // copied out of the packed account
+        let (amm_config, token_mint_0) = (loaded.amm_config, loaded.token_mint_0);
+        assert_eq!(
+            (amm_config, token_mint_0),
+            (pool.amm_config, pool.token_mint_0)
+        );
+    }
+
+    #[test]
+    fn values_the_vaults_less_the_fees_in_token1() {
+        // price 4 in raw units, i.e. 0.4 with 6 and 7 decimals
+        let mut pool = PoolState::default();
+        pool.amm_config = Pubkey::new_unique();
+        pool.sqrt_price_x64 = 2 * fixed_point_64::Q64;
+        pool.mint_decimals_0 = 6;
+        pool.mint_decimals_1 = 7;
+        pool.liquidity = 1_000;
+        pool.protocol_fees_token_0 = 300_000;
+        pool.fund_fees_token_0 = 200_000;
+        pool.protocol_fees_token_1 = 1_000_000;
+        let amm_config = AmmConfig {
+            index: 3,
+            trade_fee_rate: 2_500,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        let pool_id = Pubkey::new_unique();
+
+        let info = pool_info(pool_id, &pool, &amm_config, 10_500_000, 31_000_000);
+        assert_eq!(info.price, 0.4);
+        assert_eq!(info.amount_0, 10.0);
+        assert_eq!(info.amount_1, 3.0);
+        assert_eq!(info.tvl, 7.0);
+        assert_eq!(
+            (
+                info.amm_config_index,
+                info.trade_fee_rate,
+                info.tick_spacing
+            ),
+            (3, 2_500, 60)
+        );
+        assert_eq!((info.pool_id, info.liquidity), (pool_id, 1_000));
+
+        // fees above the vault balance saturate
+        let info = pool_info(pool_id, &pool, &amm_config, 100_000, 0);
+        assert_eq!((info.amount_0, info.amount_1, info.tvl), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,45 @@
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("invalid config: {0}")]
+    Config(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("unsupported mint {mint}: {reason}")]
+    UnsupportedMint { mint: Pubkey, reason: String },
+    #[error("math overflow: {0}")]
+    MathOverflow(String),
+    #[error("slippage exceeded: received {received}, minimum {minimum}")]
+    SlippageExceeded { received: u64, minimum: u64 },
+    #[error("invalid rpc response: {0}")]
+    InvalidResponse(String),
+    #[error("invalid snapshot: {0}")]
+    Snapshot(String),
+    #[error("simulation failed: {0}")]
+    Simulation(String),
+    #[error("invalid transaction dump: {0}")]
+    Dump(String),
+    #[error("failed to write output: {0}")]
+    Output(String),
+    #[error(transparent)]
+    Rpc(#[from] solana_client::client_error::ClientError),
+    #[error(transparent)]
+    Pubsub(#[from] solana_client::nonblocking::pubsub_client::PubsubClientError),
+    #[error(transparent)]
+    Anchor(#[from] anchor_client::ClientError),
+    #[error(transparent)]
+    Program(#[from] anchor_client::anchor_lang::error::Error),
+    #[error(transparent)]
+    Token(#[from] ProgramError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
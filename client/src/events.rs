@@ -0,0 +1,500 @@
+use crate::error::{ClientError, Result};
+use anchor_client::anchor_lang::__private::base64;
+use anchor_client::anchor_lang::{AnchorDeserialize, Discriminator};
+use raydium_amm_v3::states::{
+    CollectPersonalFeeEvent, CollectProtocolFeeEvent, ConfigChangeEvent,
+    CreatePersonalPositionEvent, DecreaseLiquidityEvent, IncreaseLiquidityEvent,
+    LiquidityCalculateEvent, LiquidityChangeEvent, PoolCreatedEvent, SwapEvent,
+    UpdateRewardInfosEvent,
+};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::fmt;
+
+/// Prefix of the log lines anchor emits events with, as base64.
+const PROGRAM_DATA: &str = "Program data: ";
+
+/// An event emitted by the CLMM program.
+#[derive(Debug)]
+pub enum ClmmEvent {
+    ConfigChange(ConfigChangeEvent),
+    PoolCreated(PoolCreatedEvent),
+    CreatePersonalPosition(CreatePersonalPositionEvent),
+    IncreaseLiquidity(IncreaseLiquidityEvent),
+    DecreaseLiquidity(DecreaseLiquidityEvent),
+    LiquidityCalculate(LiquidityCalculateEvent),
+    LiquidityChange(LiquidityChangeEvent),
+    CollectPersonalFee(CollectPersonalFeeEvent),
+    CollectProtocolFee(CollectProtocolFeeEvent),
+    UpdateRewardInfos(UpdateRewardInfosEvent),
+    Swap(SwapEvent),
+}
+
+impl fmt::Display for ClmmEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClmmEvent::PoolCreated(event) => write!(
+                f,
+                "event:pool_created, pool_state:{}, sqrt_price_x64:{}, tick:{}",
+                event.pool_state, event.sqrt_price_x64, event.tick
+            ),
+            ClmmEvent::CreatePersonalPosition(event) => write!(
+                f,
+                "event:create_position, pool_state:{}, tick_lower_index:{}, tick_upper_index:{}, \
+                 liquidity:{}, amount_0:{}, amount_1:{}",
+                event.pool_state,
+                event.tick_lower_index,
+                event.tick_upper_index,
+                event.liquidity,
+                event.deposit_amount_0,
+                event.deposit_amount_1
+            ),
+            ClmmEvent::IncreaseLiquidity(event) => write!(
+                f,
+                "event:increase_liquidity, nft_mint:{}, liquidity:{}, amount_0:{}, amount_1:{}",
+                event.position_nft_mint, event.liquidity, event.amount_0, event.amount_1
+            ),
+            ClmmEvent::DecreaseLiquidity(event) => write!(
+                f,
+                "event:decrease_liquidity, nft_mint:{}, liquidity:{}, amount_0:{}, amount_1:{}, \
+                 fee_amount_0:{}, fee_amount_1:{}",
+                event.position_nft_mint,
+                event.liquidity,
+                event.decrease_amount_0,
+                event.decrease_amount_1,
+                event.fee_amount_0,
+                event.fee_amount_1
+            ),
+            ClmmEvent::CollectPersonalFee(event) => write!(
+                f,
+                "event:collect_fee, nft_mint:{}, amount_0:{}, amount_1:{}",
+                event.position_nft_mint, event.amount_0, event.amount_1
+            ),
+            ClmmEvent::CollectProtocolFee(event) => write!(
+                f,
+                "event:collect_protocol_fee, pool_state:{}, amount_0:{}, amount_1:{}",
+                event.pool_state, event.amount_0, event.amount_1
+            ),
+            ClmmEvent::Swap(event) => write!(
+                f,
+                "event:swap, pool_state:{}, zero_for_one:{}, amount_0:{}, amount_1:{}, tick:{}",
+                event.pool_state, event.zero_for_one, event.amount_0, event.amount_1, event.tick
+            ),
+            event => write!(f, "event:{:?}", event),
+        }
+    }
+}
+
+impl ClmmEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClmmEvent::ConfigChange(_) => "config_change",
+            ClmmEvent::PoolCreated(_) => "pool_created",
+            ClmmEvent::CreatePersonalPosition(_) => "create_position",
+            ClmmEvent::IncreaseLiquidity(_) => "increase_liquidity",
+            ClmmEvent::DecreaseLiquidity(_) => "decrease_liquidity",
+            ClmmEvent::LiquidityCalculate(_) => "liquidity_calculate",
+            ClmmEvent::LiquidityChange(_) => "liquidity_change",
+            ClmmEvent::CollectPersonalFee(_) => "collect_fee",
+            ClmmEvent::CollectProtocolFee(_) => "collect_protocol_fee",
+            ClmmEvent::UpdateRewardInfos(_) => "update_reward_infos",
+            ClmmEvent::Swap(_) => "swap",
+        }
+    }
+}
+
+/// The fields of the `Display` form keyed by name after an `event` entry, pubkeys as base58
+/// and `u128`s as decimal strings. Other events only carry their debug form.
+impl Serialize for ClmmEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("event", self.name())?;
+        match self {
+            ClmmEvent::PoolCreated(event) => {
+                map.serialize_entry("pool_state", &event.pool_state.to_string())?;
+                map.serialize_entry("sqrt_price_x64", &event.sqrt_price_x64.to_string())?;
+                map.serialize_entry("tick", &event.tick)?;
+            }
+            ClmmEvent::CreatePersonalPosition(event) => {
+                map.serialize_entry("pool_state", &event.pool_state.to_string())?;
+                map.serialize_entry("tick_lower_index", &event.tick_lower_index)?;
+                map.serialize_entry("tick_upper_index", &event.tick_upper_index)?;
+                map.serialize_entry("liquidity", &event.liquidity.to_string())?;
+                map.serialize_entry("amount_0", &event.deposit_amount_0)?;
+                map.serialize_entry("amount_1", &event.deposit_amount_1)?;
+            }
+            ClmmEvent::IncreaseLiquidity(event) => {
+                map.serialize_entry("nft_mint", &event.position_nft_mint.to_string())?;
+                map.serialize_entry("liquidity", &event.liquidity.to_string())?;
+                map.serialize_entry("amount_0", &event.amount_0)?;
+                map.serialize_entry("amount_1", &event.amount_1)?;
+            }
+            ClmmEvent::DecreaseLiquidity(event) => {
+                map.serialize_entry("nft_mint", &event.position_nft_mint.to_string())?;
+                map.serialize_entry("liquidity", &event.liquidity.to_string())?;
+                map.serialize_entry("amount_0", &event.decrease_amount_0)?;
+                map.serialize_entry("amount_1", &event.decrease_amount_1)?;
+                map.serialize_entry("fee_amount_0", &event.fee_amount_0)?;
+                map.serialize_entry("fee_amount_1", &event.fee_amount_1)?;
+            }
+            ClmmEvent::CollectPersonalFee(event) => {
+                map.serialize_entry("nft_mint", &event.position_nft_mint.to_string())?;
+                map.serialize_entry("amount_0", &event.amount_0)?;
+                map.serialize_entry("amount_1", &event.amount_1)?;
+            }
+            ClmmEvent::CollectProtocolFee(event) => {
+                map.serialize_entry("pool_state", &event.pool_state.to_string())?;
+                map.serialize_entry("amount_0", &event.amount_0)?;
+                map.serialize_entry("amount_1", &event.amount_1)?;
+            }
+            ClmmEvent::Swap(event) => {
+                map.serialize_entry("pool_state", &event.pool_state.to_string())?;
+                map.serialize_entry("zero_for_one", &event.zero_for_one)?;
+                map.serialize_entry("amount_0", &event.amount_0)?;
+                map.serialize_entry("amount_1", &event.amount_1)?;
+                map.serialize_entry("sqrt_price_x64", &event.sqrt_price_x64.to_string())?;
+                map.serialize_entry("liquidity", &event.liquidity.to_string())?;
+                map.serialize_entry("tick", &event.tick)?;
+            }
+            event => map.serialize_entry("debug", &format!("{:?}", event))?,
+        }
+        map.end()
+    }
+}
+
+/// Decode the base64 payload of a `Program data:` line, `None` when it is not a CLMM event.
+pub fn decode_event(data: &str) -> Result<Option<ClmmEvent>> {
+    let Ok(bytes) = base64::decode(data) else {
+        return Ok(None);
+    };
+    if bytes.len() < 8 {
+        return Ok(None);
+    }
+    let (discriminator, mut payload) = bytes.split_at(8);
+    let mut discriminator_bytes = [0u8; 8];
+    discriminator_bytes.copy_from_slice(discriminator);
+    let event = match discriminator_bytes {
+        ConfigChangeEvent::DISCRIMINATOR => {
+            ClmmEvent::ConfigChange(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        PoolCreatedEvent::DISCRIMINATOR => {
+            ClmmEvent::PoolCreated(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        CreatePersonalPositionEvent::DISCRIMINATOR => {
+            ClmmEvent::CreatePersonalPosition(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        IncreaseLiquidityEvent::DISCRIMINATOR => {
+            ClmmEvent::IncreaseLiquidity(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        DecreaseLiquidityEvent::DISCRIMINATOR => {
+            ClmmEvent::DecreaseLiquidity(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        LiquidityCalculateEvent::DISCRIMINATOR => {
+            ClmmEvent::LiquidityCalculate(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        LiquidityChangeEvent::DISCRIMINATOR => {
+            ClmmEvent::LiquidityChange(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        CollectPersonalFeeEvent::DISCRIMINATOR => {
+            ClmmEvent::CollectPersonalFee(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        CollectProtocolFeeEvent::DISCRIMINATOR => {
+            ClmmEvent::CollectProtocolFee(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        UpdateRewardInfosEvent::DISCRIMINATOR => {
+            ClmmEvent::UpdateRewardInfos(AnchorDeserialize::deserialize(&mut payload)?)
+        }
+        SwapEvent::DISCRIMINATOR => ClmmEvent::Swap(AnchorDeserialize::deserialize(&mut payload)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(event))
+}
+
+/// Events emitted by `raydium_v3_program` in the logs of a transaction, in order. Data logged
+/// by the programs it invokes, or by other programs of the transaction, is skipped.
+pub fn parse_log_events(raydium_v3_program: &Pubkey, logs: &[String]) -> Result<Vec<ClmmEvent>> {
+    Ok(parse_log_invocation_events(raydium_v3_program, logs)?
+        .into_iter()
+        .map(|(_, event)| event)
+        .collect())
+}
+
+/// [`parse_log_events`], each event with the index of the invocation of `raydium_v3_program`
+/// which emitted it, invocations being counted in the order they start, top level and inner
+/// ones alike.
+pub fn parse_log_invocation_events(
+    raydium_v3_program: &Pubkey,
+    logs: &[String],
+) -> Result<Vec<(usize, ClmmEvent)>> {
+    let program = raydium_v3_program.to_string();
+    // programs being executed, the innermost last, with the index of the program's invocations
+    let mut invocations = Vec::new();
+    let mut invocation_count = 0;
+    let mut events = Vec::new();
+    for log in logs {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA) {
+            if let Some((_, Some(invocation))) = invocations.last() {
+                events.extend(decode_event(data)?.map(|event| (*invocation, event)));
+            }
+        } else if let Some(log) = log.strip_prefix("Program ") {
+            // `<program> invoke [<depth>]`, `<program> success` or `<program> failed: <error>`
+            let mut words = log.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some(invoked), Some("invoke")) => {
+                    let invocation = (invoked == program).then(|| {
+                        invocation_count += 1;
+                        invocation_count - 1
+                    });
+                    invocations.push((invoked, invocation));
+                }
+                (Some(_), Some("success" | "failed:")) => {
+                    invocations.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Events of a confirmed transaction, from its meta.
+pub async fn get_transaction_events(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    signature: &Signature,
+) -> Result<Vec<ClmmEvent>> {
+    let transaction = rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: None,
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+    let logs: Option<Vec<String>> = transaction
+        .transaction
+        .meta
+        .and_then(|meta| meta.log_messages.into());
+    parse_log_events(raydium_v3_program, &logs.unwrap_or_default())
+}
+
+/// Events `txn` would emit, from a simulation. A failed simulation is an error.
+pub async fn simulate_transaction_events(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    txn: &Transaction,
+) -> Result<Vec<ClmmEvent>> {
+    let simulation = rpc_client.simulate_transaction(txn).await?.value;
+    if let Some(err) = simulation.err {
+        return Err(ClientError::Simulation(format!(
+            "{}, logs: {:?}",
+            err,
+            simulation.logs.unwrap_or_default()
+        )));
+    }
+    parse_log_events(raydium_v3_program, &simulation.logs.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::anchor_lang::Event;
+    use std::str::FromStr;
+
+    const CLMM: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const COMPUTE_BUDGET: &str = "ComputeBudget111111111111111111111111111111";
+    const AGGREGATOR: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+    fn program() -> Pubkey {
+        Pubkey::from_str(CLMM).unwrap()
+    }
+
+    fn swap_event(amount_0: u64, zero_for_one: bool) -> SwapEvent {
+        SwapEvent {
+            pool_state: Pubkey::new_unique(),
+            sender: Pubkey::new_unique(),
+            token_account_0: Pubkey::new_unique(),
+            token_account_1: Pubkey::new_unique(),
+            amount_0,
+            transfer_fee_0: 0,
+            amount_1: 2 * amount_0,
+            transfer_fee_1: 0,
+            zero_for_one,
+            sqrt_price_x64: 1 << 64,
+            liquidity: 1_000_000,
+            tick: -3,
+        }
+    }
+
+    fn program_data(event: &impl Event) -> String {
+        format!("{}{}", PROGRAM_DATA, base64::encode(event.data()))
+    }
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn swap_amounts(events: &[ClmmEvent]) -> Vec<u64> {
+        events
+            .iter()
+            .map(|event| match event {
+                ClmmEvent::Swap(event) => event.amount_0,
+                event => panic!("unexpected {}", event),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_a_top_level_swap() {
+        let swap = program_data(&swap_event(100, true));
+        let logs = logs(&[
+            &format!("Program {} invoke [1]", COMPUTE_BUDGET),
+            &format!("Program {} success", COMPUTE_BUDGET),
+            &format!("Program {} invoke [1]", CLMM),
+            "Program log: Instruction: SwapV2",
+            &format!("Program {} invoke [2]", TOKEN),
+            "Program log: Instruction: TransferChecked",
+            &format!("Program {} consumed 6173 of 180531 compute units", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &format!("Program {} invoke [2]", TOKEN),
+            "Program log: Instruction: TransferChecked",
+            &format!("Program {} consumed 6200 of 171200 compute units", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &swap,
+            &format!("Program {} consumed 48213 of 199850 compute units", CLMM),
+            &format!("Program {} success", CLMM),
+        ]);
+        let events = parse_log_events(&program(), &logs).unwrap();
+        assert_eq!(swap_amounts(&events), vec![100]);
+        let ClmmEvent::Swap(event) = &events[0] else {
+            unreachable!()
+        };
+        assert!(event.zero_for_one);
+        assert_eq!((event.amount_1, event.tick), (200, -3));
+    }
+
+    #[test]
+    fn keeps_events_of_the_program_invoked_by_another_one() {
+        // an aggregator swaps through the pool, and logs data of its own
+        let logs = logs(&[
+            &format!("Program {} invoke [1]", AGGREGATOR),
+            "Program log: Instruction: Route",
+            &format!("Program {} invoke [2]", CLMM),
+            "Program log: Instruction: SwapV2",
+            &format!("Program {} invoke [3]", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &program_data(&swap_event(100, true)),
+            &format!("Program {} consumed 48213 of 150000 compute units", CLMM),
+            &format!("Program {} success", CLMM),
+            &program_data(&swap_event(7, true)),
+            &format!(
+                "Program {} consumed 90000 of 199850 compute units",
+                AGGREGATOR
+            ),
+            &format!("Program return: {} AQAAAAAAAAA=", AGGREGATOR),
+            &format!("Program {} success", AGGREGATOR),
+        ]);
+        let events = parse_log_events(&program(), &logs).unwrap();
+        assert_eq!(swap_amounts(&events), vec![100]);
+    }
+
+    #[test]
+    fn skips_data_logged_by_programs_it_invokes() {
+        let logs = logs(&[
+            &format!("Program {} invoke [1]", CLMM),
+            &format!("Program {} invoke [2]", AGGREGATOR),
+            &program_data(&swap_event(7, false)),
+            &format!("Program {} success", AGGREGATOR),
+            &program_data(&swap_event(100, false)),
+            &format!("Program {} success", CLMM),
+        ]);
+        let events = parse_log_events(&program(), &logs).unwrap();
+        assert_eq!(swap_amounts(&events), vec![100]);
+    }
+
+    #[test]
+    fn keeps_events_logged_before_a_failed_inner_call() {
+        // a first swap succeeds, the transfer of the second one fails the transaction
+        let logs = logs(&[
+            &format!("Program {} invoke [1]", CLMM),
+            "Program log: Instruction: SwapV2",
+            &program_data(&swap_event(100, true)),
+            &format!("Program {} success", CLMM),
+            &format!("Program {} invoke [1]", CLMM),
+            "Program log: Instruction: SwapV2",
+            &format!("Program {} invoke [2]", TOKEN),
+            "Program log: Instruction: TransferChecked",
+            "Program log: Error: insufficient funds",
+            &format!("Program {} consumed 4300 of 150000 compute units", TOKEN),
+            &format!("Program {} failed: custom program error: 0x1", TOKEN),
+            &format!("Program {} consumed 30000 of 180000 compute units", CLMM),
+            &format!("Program {} failed: custom program error: 0x1", CLMM),
+        ]);
+        let events = parse_log_events(&program(), &logs).unwrap();
+        assert_eq!(swap_amounts(&events), vec![100]);
+    }
+
+    #[test]
+    fn decodes_every_hop_of_a_router_swap() {
+        let logs = logs(&[
+            &format!("Program {} invoke [1]", CLMM),
+            "Program log: Instruction: SwapRouterBaseIn",
+            &format!("Program {} invoke [2]", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &format!("Program {} invoke [2]", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &program_data(&swap_event(100, true)),
+            &format!("Program {} invoke [2]", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &format!("Program {} invoke [2]", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &program_data(&swap_event(200, false)),
+            &format!("Program {} consumed 98000 of 200000 compute units", CLMM),
+            &format!("Program {} success", CLMM),
+        ]);
+        let events = parse_log_events(&program(), &logs).unwrap();
+        assert_eq!(swap_amounts(&events), vec![100, 200]);
+    }
+
+    #[test]
+    fn numbers_events_by_the_invocation_emitting_them() {
+        // a top level swap, then two through an aggregator, the first logging nothing
+        let logs = logs(&[
+            &format!("Program {} invoke [1]", CLMM),
+            &program_data(&swap_event(100, true)),
+            &format!("Program {} success", CLMM),
+            &format!("Program {} invoke [1]", AGGREGATOR),
+            &format!("Program {} invoke [2]", CLMM),
+            &format!("Program {} success", CLMM),
+            &format!("Program {} invoke [2]", TOKEN),
+            &format!("Program {} success", TOKEN),
+            &format!("Program {} invoke [2]", CLMM),
+            &program_data(&swap_event(200, false)),
+            &format!("Program {} success", CLMM),
+            &format!("Program {} success", AGGREGATOR),
+        ]);
+        let events = parse_log_invocation_events(&program(), &logs).unwrap();
+        let invocations: Vec<usize> = events.iter().map(|(invocation, _)| *invocation).collect();
+        assert_eq!(invocations, vec![0, 2]);
+    }
+
+    #[test]
+    fn ignores_data_that_is_not_an_event() {
+        assert!(decode_event("not base64!").unwrap().is_none());
+        assert!(decode_event(&base64::encode([1u8; 4])).unwrap().is_none());
+        assert!(decode_event(&base64::encode([1u8; 40])).unwrap().is_none());
+        // a known discriminator with a truncated payload
+        let data = swap_event(100, true).data();
+        assert!(decode_event(&base64::encode(&data[..20])).is_err());
+        let event = decode_event(&base64::encode(&data)).unwrap().unwrap();
+        assert_eq!(event.name(), "swap");
+    }
+}
@@ -0,0 +1,382 @@
+use crate::client::get_anchor_account;
+use crate::config::ClientConfig;
+use crate::discovery::get_pools;
+use crate::error::{ClientError, Result};
+use crate::instructions::{
+    collect_fund_fee_instr, collect_protocol_fee_instr, create_ata_token_account_instr,
+};
+use crate::utils::{fits_in_transaction, serialize_pubkey};
+use raydium_amm_v3::states::PoolState;
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+/// Fees a pool holds for the protocol and the fund, in raw amounts.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolFees {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub amm_config: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_1: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_vault_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_vault_1: Pubkey,
+    pub protocol_fees_token_0: u64,
+    pub protocol_fees_token_1: u64,
+    pub fund_fees_token_0: u64,
+    pub fund_fees_token_1: u64,
+}
+
+/// Which fees to collect: `CollectProtocolFee` or `CollectFundFee`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeKind {
+    Protocol,
+    Fund,
+}
+
+impl FromStr for FeeKind {
+    type Err = ClientError;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "protocol" => Ok(FeeKind::Protocol),
+            "fund" => Ok(FeeKind::Fund),
+            _ => Err(ClientError::InvalidArgument(format!(
+                "unknown fee kind {}, expected protocol or fund",
+                kind
+            ))),
+        }
+    }
+}
+
+impl FeeKind {
+    /// Owner of the token accounts the fees go to, the configured one or `admin`.
+    pub fn recipient(&self, config: &ClientConfig, admin: &Pubkey) -> Pubkey {
+        match self {
+            FeeKind::Protocol => config.protocol_fee_recipient,
+            FeeKind::Fund => config.fund_fee_recipient,
+        }
+        .unwrap_or(*admin)
+    }
+}
+
+/// Pools to report or collect the fees of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeScope {
+    Pool(Pubkey),
+    AmmConfig(Pubkey),
+    All,
+}
+
+impl FeeScope {
+    /// Every pool when `all`, else those of `amm_config`, else `pool_id` or the default pool.
+    pub fn new(
+        pool_id: Option<Pubkey>,
+        amm_config: Option<Pubkey>,
+        all: bool,
+        default_pool_id: impl FnOnce() -> Result<Pubkey>,
+    ) -> Result<Self> {
+        Ok(match (pool_id, amm_config) {
+            _ if all => FeeScope::All,
+            (_, Some(amm_config)) => FeeScope::AmmConfig(amm_config),
+            (Some(pool_id), None) => FeeScope::Pool(pool_id),
+            (None, None) => FeeScope::Pool(default_pool_id()?),
+        })
+    }
+}
+
+impl PoolFees {
+    pub fn new(pool_id: Pubkey, pool_state: &PoolState) -> Self {
+        Self {
+            pool_id,
+            amm_config: pool_state.amm_config,
+            token_mint_0: pool_state.token_mint_0,
+            token_mint_1: pool_state.token_mint_1,
+            token_vault_0: pool_state.token_vault_0,
+            token_vault_1: pool_state.token_vault_1,
+            protocol_fees_token_0: pool_state.protocol_fees_token_0,
+            protocol_fees_token_1: pool_state.protocol_fees_token_1,
+            fund_fees_token_0: pool_state.fund_fees_token_0,
+            fund_fees_token_1: pool_state.fund_fees_token_1,
+        }
+    }
+
+    /// Token0 and token1 amounts of `kind`.
+    pub fn amounts(&self, kind: FeeKind) -> (u64, u64) {
+        match kind {
+            FeeKind::Protocol => (self.protocol_fees_token_0, self.protocol_fees_token_1),
+            FeeKind::Fund => (self.fund_fees_token_0, self.fund_fees_token_1),
+        }
+    }
+}
+
+/// Protocol and fund fees accrued on the pools of `scope`.
+pub async fn get_pool_fees(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    scope: FeeScope,
+) -> Result<Vec<PoolFees>> {
+    let pools = match scope {
+        FeeScope::Pool(pool_id) => {
+            vec![(
+                pool_id,
+                get_anchor_account::<PoolState>(rpc_client, &pool_id).await?,
+            )]
+        }
+        FeeScope::AmmConfig(amm_config) => {
+            get_pools(rpc_client, raydium_v3_program, Some(&amm_config)).await?
+        }
+        FeeScope::All => get_pools(rpc_client, raydium_v3_program, None).await?,
+    };
+    let mut pool_fees = pools
+        .iter()
+        .map(|(pool_id, pool_state)| PoolFees::new(*pool_id, pool_state))
+        .collect::<Vec<_>>();
+    pool_fees.sort_by_key(|pool_fees| pool_fees.pool_id);
+    Ok(pool_fees)
+}
+
+/// Instructions sweeping the `kind` fees of `pool_fees` to `recipient`'s token accounts,
+/// batched into as few transactions paid by `admin` as they fit in. Pools are added to a
+/// batch for as long as it fits, each batch creating the token accounts it uses once.
+/// `token_programs` are those of every mint.
+pub fn collect_fees_instructions(
+    config: &ClientConfig,
+    admin: &Pubkey,
+    recipient: &Pubkey,
+    kind: FeeKind,
+    pool_fees: &[PoolFees],
+    token_programs: &BTreeMap<Pubkey, Pubkey>,
+) -> Result<Vec<Vec<Instruction>>> {
+    let collect_fee_instr = match kind {
+        FeeKind::Protocol => collect_protocol_fee_instr,
+        FeeKind::Fund => collect_fund_fee_instr,
+    };
+    let pool_instructions =
+        |pool_fees: &PoolFees, created_mints: &mut HashSet<Pubkey>| -> Result<Vec<Instruction>> {
+            let mut instructions = Vec::new();
+            let mut recipient_token_accounts = Vec::with_capacity(2);
+            for mint in [pool_fees.token_mint_0, pool_fees.token_mint_1] {
+                let token_program = token_programs
+                    .get(&mint)
+                    .ok_or(ClientError::AccountNotFound(mint))?;
+                if created_mints.insert(mint) {
+                    instructions.extend(create_ata_token_account_instr(
+                        admin,
+                        recipient,
+                        &mint,
+                        token_program,
+                        0,
+                    )?);
+                }
+                recipient_token_accounts.push(get_associated_token_address_with_program_id(
+                    recipient,
+                    &mint,
+                    token_program,
+                ));
+            }
+            let (amount_0, amount_1) = pool_fees.amounts(kind);
+            instructions.extend(collect_fee_instr(
+                config,
+                *admin,
+                pool_fees.pool_id,
+                pool_fees.amm_config,
+                pool_fees.token_vault_0,
+                pool_fees.token_vault_1,
+                pool_fees.token_mint_0,
+                pool_fees.token_mint_1,
+                recipient_token_accounts[0],
+                recipient_token_accounts[1],
+                amount_0,
+                amount_1,
+            )?);
+            Ok(instructions)
+        };
+    let mut batches = Vec::new();
+    let mut instructions = Vec::new();
+    let mut created_mints = HashSet::new();
+    for pool_fees in pool_fees {
+        let mut batch_mints = created_mints.clone();
+        let mut batch = instructions.clone();
+        batch.extend(pool_instructions(pool_fees, &mut batch_mints)?);
+        if !instructions.is_empty() && !fits_in_transaction(&batch, admin) {
+            batches.push(std::mem::take(&mut instructions));
+            batch_mints = HashSet::new();
+            batch = pool_instructions(pool_fees, &mut batch_mints)?;
+        }
+        if !fits_in_transaction(&batch, admin) {
+            return Err(ClientError::InvalidArgument(format!(
+                "collecting the fees of {} does not fit in one transaction",
+                pool_fees.pool_id
+            )));
+        }
+        instructions = batch;
+        created_mints = batch_mints;
+    }
+    if !instructions.is_empty() {
+        batches.push(instructions);
+    }
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::anchor_lang::Discriminator;
+    use raydium_amm_v3::instruction as raydium_instruction;
+
+    fn config() -> ClientConfig {
+        ClientConfig {
+            http_url: "http://localhost:8899".to_string(),
+            ws_url: "ws://localhost:8900".to_string(),
+            payer_path: String::new(),
+            admin_path: String::new(),
+            raydium_v3_program: Pubkey::new_unique(),
+            slippage: 0.01,
+            amm_config_key: Pubkey::new_unique(),
+            mint0: None,
+            mint1: None,
+            pool_id_account: None,
+            tickarray_bitmap_extension: None,
+            amm_config_index: 0,
+            protocol_fee_recipient: None,
+            fund_fee_recipient: None,
+        }
+    }
+
+    fn pool_fees(token_mint_0: Pubkey, token_mint_1: Pubkey) -> PoolFees {
+        PoolFees {
+            pool_id: Pubkey::new_unique(),
+            amm_config: Pubkey::new_unique(),
+            token_mint_0,
+            token_mint_1,
+            token_vault_0: Pubkey::new_unique(),
+            token_vault_1: Pubkey::new_unique(),
+            protocol_fees_token_0: 1,
+            protocol_fees_token_1: 2,
+            fund_fees_token_0: 3,
+            fund_fees_token_1: 4,
+        }
+    }
+
+    #[test]
+    fn fee_kind_selects_amounts_and_recipient() {
+        assert_eq!("protocol".parse::<FeeKind>().unwrap(), FeeKind::Protocol);
+        assert_eq!("fund".parse::<FeeKind>().unwrap(), FeeKind::Fund);
+        assert!("trade".parse::<FeeKind>().is_err());
+
+        let fees = pool_fees(Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(fees.amounts(FeeKind::Protocol), (1, 2));
+        assert_eq!(fees.amounts(FeeKind::Fund), (3, 4));
+
+        let mut config = config();
+        let admin = Pubkey::new_unique();
+        assert_eq!(FeeKind::Protocol.recipient(&config, &admin), admin);
+        let fund_fee_recipient = Pubkey::new_unique();
+        config.fund_fee_recipient = Some(fund_fee_recipient);
+        assert_eq!(FeeKind::Protocol.recipient(&config, &admin), admin);
+        assert_eq!(FeeKind::Fund.recipient(&config, &admin), fund_fee_recipient);
+    }
+
+    #[test]
+    fn fee_scope_takes_the_widest_option() {
+        let (pool_id, amm_config, default_pool_id) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let unused = || Err(ClientError::Config("no default pool".to_string()));
+        assert_eq!(
+            FeeScope::new(Some(pool_id), Some(amm_config), true, unused).unwrap(),
+            FeeScope::All
+        );
+        assert_eq!(
+            FeeScope::new(Some(pool_id), Some(amm_config), false, unused).unwrap(),
+            FeeScope::AmmConfig(amm_config)
+        );
+        assert_eq!(
+            FeeScope::new(Some(pool_id), None, false, unused).unwrap(),
+            FeeScope::Pool(pool_id)
+        );
+        assert_eq!(
+            FeeScope::new(None, None, false, || Ok(default_pool_id)).unwrap(),
+            FeeScope::Pool(default_pool_id)
+        );
+        assert!(FeeScope::new(None, None, false, unused).is_err());
+    }
+
+    #[test]
+    fn batches_pools_creating_token_accounts_once_per_transaction() {
+        let (config, admin, recipient) = (config(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token_programs =
+            BTreeMap::from([(mint_a, spl_token::id()), (mint_b, spl_token_2022::id())]);
+        let pools = (0..12)
+            .map(|_| pool_fees(mint_a, mint_b))
+            .collect::<Vec<_>>();
+
+        for (kind, discriminator) in [
+            (
+                FeeKind::Protocol,
+                raydium_instruction::CollectProtocolFee::DISCRIMINATOR,
+            ),
+            (
+                FeeKind::Fund,
+                raydium_instruction::CollectFundFee::DISCRIMINATOR,
+            ),
+        ] {
+            let batches = collect_fees_instructions(
+                &config,
+                &admin,
+                &recipient,
+                kind,
+                &pools,
+                &token_programs,
+            )
+            .unwrap();
+            assert!(batches.len() > 1);
+            let mut collected = 0;
+            for batch in &batches {
+                assert!(fits_in_transaction(batch, &admin));
+                // the two token accounts are created first, once
+                assert!(batch[..2].iter().all(
+                    |instruction| instruction.program_id == spl_associated_token_account::id()
+                ));
+                assert!(batch[2..].iter().all(|instruction| instruction.program_id
+                    == config.raydium_v3_program
+                    && instruction.data.starts_with(&discriminator)));
+                collected += batch.len() - 2;
+            }
+            assert_eq!(collected, pools.len());
+        }
+
+        assert!(collect_fees_instructions(
+            &config,
+            &admin,
+            &recipient,
+            FeeKind::Protocol,
+            &[pool_fees(mint_a, Pubkey::new_unique())],
+            &token_programs,
+        )
+        .is_err());
+        assert!(collect_fees_instructions(
+            &config,
+            &admin,
+            &recipient,
+            FeeKind::Protocol,
+            &[],
+            &token_programs
+        )
+        .unwrap()
+        .is_empty());
+    }
+}
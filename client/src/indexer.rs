@@ -0,0 +1,681 @@
+use crate::error::{ClientError, Result};
+use crate::events::{parse_log_invocation_events, ClmmEvent};
+use anchor_client::anchor_lang::Discriminator;
+use raydium_amm_v3::instruction as raydium_instruction;
+use rusqlite::{params, Connection, OptionalExtension};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::bs58;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiInstruction,
+    UiLoadedAddresses, UiTransactionEncoding,
+};
+use std::str::FromStr;
+
+/// Most signatures `getSignaturesForAddress` returns per request.
+const SIGNATURES_PAGE_SIZE: usize = 1000;
+
+/// Instructions of the program by discriminator.
+const INSTRUCTIONS: &[([u8; 8], &str)] = &[
+    (
+        raydium_instruction::CreateAmmConfig::DISCRIMINATOR,
+        "create_amm_config",
+    ),
+    (
+        raydium_instruction::UpdateAmmConfig::DISCRIMINATOR,
+        "update_amm_config",
+    ),
+    (
+        raydium_instruction::CreatePool::DISCRIMINATOR,
+        "create_pool",
+    ),
+    (
+        raydium_instruction::UpdatePoolStatus::DISCRIMINATOR,
+        "update_pool_status",
+    ),
+    (
+        raydium_instruction::CreateOperationAccount::DISCRIMINATOR,
+        "create_operation_account",
+    ),
+    (
+        raydium_instruction::UpdateOperationAccount::DISCRIMINATOR,
+        "update_operation_account",
+    ),
+    (
+        raydium_instruction::TransferRewardOwner::DISCRIMINATOR,
+        "transfer_reward_owner",
+    ),
+    (
+        raydium_instruction::InitializeReward::DISCRIMINATOR,
+        "initialize_reward",
+    ),
+    (
+        raydium_instruction::CollectRemainingRewards::DISCRIMINATOR,
+        "collect_remaining_rewards",
+    ),
+    (
+        raydium_instruction::UpdateRewardInfos::DISCRIMINATOR,
+        "update_reward_infos",
+    ),
+    (
+        raydium_instruction::SetRewardParams::DISCRIMINATOR,
+        "set_reward_params",
+    ),
+    (
+        raydium_instruction::CollectProtocolFee::DISCRIMINATOR,
+        "collect_protocol_fee",
+    ),
+    (
+        raydium_instruction::CollectFundFee::DISCRIMINATOR,
+        "collect_fund_fee",
+    ),
+    (
+        raydium_instruction::OpenPosition::DISCRIMINATOR,
+        "open_position",
+    ),
+    (
+        raydium_instruction::OpenPositionV2::DISCRIMINATOR,
+        "open_position_v2",
+    ),
+    (
+        raydium_instruction::ClosePosition::DISCRIMINATOR,
+        "close_position",
+    ),
+    (
+        raydium_instruction::IncreaseLiquidity::DISCRIMINATOR,
+        "increase_liquidity",
+    ),
+    (
+        raydium_instruction::IncreaseLiquidityV2::DISCRIMINATOR,
+        "increase_liquidity_v2",
+    ),
+    (
+        raydium_instruction::DecreaseLiquidity::DISCRIMINATOR,
+        "decrease_liquidity",
+    ),
+    (
+        raydium_instruction::DecreaseLiquidityV2::DISCRIMINATOR,
+        "decrease_liquidity_v2",
+    ),
+    (raydium_instruction::Swap::DISCRIMINATOR, "swap"),
+    (raydium_instruction::SwapV2::DISCRIMINATOR, "swap_v2"),
+    (
+        raydium_instruction::SwapRouterBaseIn::DISCRIMINATOR,
+        "swap_router_base_in",
+    ),
+];
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    pool TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    failed INTEGER NOT NULL,
+    instructions TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS swaps (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    sender TEXT NOT NULL,
+    zero_for_one INTEGER NOT NULL,
+    amount_0 INTEGER NOT NULL,
+    amount_1 INTEGER NOT NULL,
+    transfer_fee_0 INTEGER NOT NULL,
+    transfer_fee_1 INTEGER NOT NULL,
+    sqrt_price_x64 TEXT NOT NULL,
+    liquidity TEXT NOT NULL,
+    tick INTEGER NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE TABLE IF NOT EXISTS liquidity_changes (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    kind TEXT NOT NULL,
+    nft_mint TEXT,
+    owner TEXT,
+    tick_lower INTEGER,
+    tick_upper INTEGER,
+    liquidity TEXT NOT NULL,
+    amount_0 INTEGER NOT NULL,
+    amount_1 INTEGER NOT NULL,
+    transfer_fee_0 INTEGER NOT NULL,
+    transfer_fee_1 INTEGER NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE TABLE IF NOT EXISTS fee_collections (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    kind TEXT NOT NULL,
+    nft_mint TEXT,
+    amount_0 INTEGER NOT NULL,
+    amount_1 INTEGER NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE TABLE IF NOT EXISTS checkpoints (
+    pool TEXT PRIMARY KEY,
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL
+);
+";
+
+/// Index of the pool among the accounts of the instructions emitting events without it.
+fn pool_account_index(instruction_name: &str) -> Option<usize> {
+    match instruction_name {
+        "open_position" | "open_position_v2" => Some(5),
+        "increase_liquidity" | "increase_liquidity_v2" => Some(2),
+        "decrease_liquidity" | "decrease_liquidity_v2" => Some(3),
+        _ => None,
+    }
+}
+
+/// Name of the CLMM instruction `data` encodes.
+pub fn instruction_name(data: &[u8]) -> Option<&'static str> {
+    let discriminator = data.get(..8)?;
+    INSTRUCTIONS
+        .iter()
+        .find(|(instruction, _)| instruction == discriminator)
+        .map(|(_, name)| *name)
+}
+
+/// A transaction of the program with its decoded instructions and events.
+#[derive(Debug)]
+pub struct IndexedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+    /// Top level and inner instructions of the program, in execution order. Only decoded for
+    /// binary encoded transactions.
+    pub instructions: Vec<&'static str>,
+    /// Empty for failed transactions, whose changes were reverted.
+    pub events: Vec<ClmmEvent>,
+    /// Pool account of the instruction each of `events` was emitted by, `None` when unknown,
+    /// e.g. for JSON encoded transactions.
+    pub event_pools: Vec<Option<Pubkey>>,
+}
+
+/// Decode the program instructions and events of a fetched transaction.
+pub fn decode_transaction(
+    raydium_v3_program: &Pubkey,
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<IndexedTransaction> {
+    let meta = transaction
+        .transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| ClientError::InvalidResponse("transaction without meta".to_string()))?;
+    let failed = meta.err.is_some();
+    let mut instructions = Vec::new();
+    // pool account of every invocation of the program, in execution order
+    let mut invocation_pools = Vec::new();
+    let signature = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui_transaction) => ui_transaction.signatures.first().cloned(),
+        encoded => encoded.decode().map(|versioned_transaction| {
+            // program ids are always static keys, never loaded from lookup tables
+            let static_account_keys = versioned_transaction.message.static_account_keys();
+            let is_program = |program_id_index: u8| {
+                static_account_keys.get(usize::from(program_id_index)) == Some(raydium_v3_program)
+            };
+            // instruction accounts index the loaded ones after the static ones
+            let mut account_keys = static_account_keys.to_vec();
+            let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.clone().into();
+            if let Some(loaded_addresses) = loaded_addresses {
+                account_keys.extend(
+                    loaded_addresses
+                        .writable
+                        .iter()
+                        .chain(&loaded_addresses.readonly)
+                        .filter_map(|key| Pubkey::from_str(key).ok()),
+                );
+            }
+            let mut add_invocation = |data: &[u8], accounts: &[u8]| {
+                let name = instruction_name(data);
+                instructions.extend(name);
+                invocation_pools.push(
+                    name.and_then(pool_account_index)
+                        .and_then(|index| accounts.get(index))
+                        .and_then(|index| account_keys.get(usize::from(*index)))
+                        .copied(),
+                );
+            };
+            let inner_instructions: Option<Vec<_>> = meta.inner_instructions.clone().into();
+            let inner_instructions = inner_instructions.unwrap_or_default();
+            for (index, instruction) in versioned_transaction
+                .message
+                .instructions()
+                .iter()
+                .enumerate()
+            {
+                if is_program(instruction.program_id_index) {
+                    add_invocation(&instruction.data, &instruction.accounts);
+                }
+                let inner = inner_instructions
+                    .iter()
+                    .filter(|inner| usize::from(inner.index) == index)
+                    .flat_map(|inner| &inner.instructions);
+                for inner_instruction in inner {
+                    if let UiInstruction::Compiled(inner_instruction) = inner_instruction {
+                        if is_program(inner_instruction.program_id_index) {
+                            let data = bs58::decode(&inner_instruction.data)
+                                .into_vec()
+                                .unwrap_or_default();
+                            add_invocation(&data, &inner_instruction.accounts);
+                        }
+                    }
+                }
+            }
+            versioned_transaction.signatures[0].to_string()
+        }),
+    }
+    .ok_or_else(|| ClientError::InvalidResponse("transaction without signature".to_string()))?;
+    let invocation_events = if failed {
+        Vec::new()
+    } else {
+        let logs: Option<Vec<String>> = meta.log_messages.clone().into();
+        parse_log_invocation_events(raydium_v3_program, &logs.unwrap_or_default())?
+    };
+    let (event_pools, events) = invocation_events
+        .into_iter()
+        .map(|(invocation, event)| (invocation_pools.get(invocation).copied().flatten(), event))
+        .unzip();
+    Ok(IndexedTransaction {
+        signature,
+        slot: transaction.slot,
+        block_time: transaction.block_time,
+        failed,
+        instructions,
+        events,
+        event_pools,
+    })
+}
+
+/// Signatures of the confirmed transactions mentioning `address`, newest first, down to
+/// `until` excluded, or at most `depth` of them when given.
+pub async fn get_signatures(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+    until: Option<Signature>,
+    depth: Option<usize>,
+) -> Result<Vec<Signature>> {
+    let mut signatures = Vec::new();
+    let mut before = None;
+    loop {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(SIGNATURES_PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        let page_len = page.len();
+        for status in page {
+            let signature = Signature::from_str(&status.signature).map_err(|err| {
+                ClientError::InvalidResponse(format!("signature {}: {}", status.signature, err))
+            })?;
+            signatures.push(signature);
+        }
+        if page_len < SIGNATURES_PAGE_SIZE || depth.is_some_and(|depth| signatures.len() >= depth) {
+            break;
+        }
+        before = signatures.last().copied();
+    }
+    if let Some(depth) = depth {
+        signatures.truncate(depth);
+    }
+    Ok(signatures)
+}
+
+/// A confirmed transaction, binary encoded so that its instructions can be decoded.
+pub async fn get_transaction(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    Ok(rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?)
+}
+
+/// SQLite store of the swaps, deposits, withdrawals and fee collections of a pool, with a
+/// checkpoint of the newest transaction indexed.
+pub struct Indexer {
+    connection: Connection,
+    raydium_v3_program: Pubkey,
+    pool_id: Pubkey,
+}
+
+impl Indexer {
+    /// Open or create the database at `path`.
+    pub fn open(path: &str, raydium_v3_program: Pubkey, pool_id: Pubkey) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection,
+            raydium_v3_program,
+            pool_id,
+        })
+    }
+
+    /// Newest transaction indexed for the pool.
+    pub fn checkpoint(&self) -> Result<Option<Signature>> {
+        let signature: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT signature FROM checkpoints WHERE pool = ?1",
+                params![self.pool_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        signature
+            .map(|signature| {
+                Signature::from_str(&signature).map_err(|err| {
+                    ClientError::InvalidResponse(format!("checkpoint {}: {}", signature, err))
+                })
+            })
+            .transpose()
+    }
+
+    /// Store a transaction and move the checkpoint to it, unless it is older. Storing a
+    /// transaction twice is a no-op.
+    pub fn store(&mut self, transaction: &IndexedTransaction) -> Result<()> {
+        let pool = self.pool_id.to_string();
+        // position events carry no pool, only the instruction emitting them does
+        let pool_id = self.pool_id;
+        let emitted_on_pool =
+            |event_index: usize| transaction.event_pools.get(event_index) == Some(&Some(pool_id));
+        let db_transaction = self.connection.transaction()?;
+        db_transaction.execute(
+            "INSERT OR IGNORE INTO transactions VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                transaction.signature,
+                pool,
+                transaction.slot,
+                transaction.block_time,
+                transaction.failed,
+                transaction.instructions.join(","),
+            ],
+        )?;
+        for (event_index, event) in transaction.events.iter().enumerate() {
+            match event {
+                // a transaction may also trade or deposit on other pools, e.g. through a router
+                ClmmEvent::Swap(event) if event.pool_state == self.pool_id => {
+                    db_transaction.execute(
+                        "INSERT OR IGNORE INTO swaps \
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                        params![
+                            transaction.signature,
+                            event_index,
+                            pool,
+                            transaction.slot,
+                            transaction.block_time,
+                            event.sender.to_string(),
+                            event.zero_for_one,
+                            event.amount_0,
+                            event.amount_1,
+                            event.transfer_fee_0,
+                            event.transfer_fee_1,
+                            event.sqrt_price_x64.to_string(),
+                            event.liquidity.to_string(),
+                            event.tick,
+                        ],
+                    )?;
+                }
+                ClmmEvent::CreatePersonalPosition(event) if event.pool_state == self.pool_id => {
+                    db_transaction.execute(
+                        "INSERT OR IGNORE INTO liquidity_changes \
+                         VALUES (?1, ?2, ?3, ?4, ?5, 'open', NULL, ?6, ?7, ?8, \
+                         ?9, ?10, ?11, ?12, ?13)",
+                        params![
+                            transaction.signature,
+                            event_index,
+                            pool,
+                            transaction.slot,
+                            transaction.block_time,
+                            event.nft_owner.to_string(),
+                            event.tick_lower_index,
+                            event.tick_upper_index,
+                            event.liquidity.to_string(),
+                            event.deposit_amount_0,
+                            event.deposit_amount_1,
+                            event.deposit_amount_0_transfer_fee,
+                            event.deposit_amount_1_transfer_fee,
+                        ],
+                    )?;
+                }
+                ClmmEvent::IncreaseLiquidity(event) if emitted_on_pool(event_index) => {
+                    db_transaction.execute(
+                        "INSERT OR IGNORE INTO liquidity_changes \
+                         VALUES (?1, ?2, ?3, ?4, ?5, 'increase', ?6, NULL, NULL, NULL, \
+                         ?7, ?8, ?9, ?10, ?11)",
+                        params![
+                            transaction.signature,
+                            event_index,
+                            pool,
+                            transaction.slot,
+                            transaction.block_time,
+                            event.position_nft_mint.to_string(),
+                            event.liquidity.to_string(),
+                            event.amount_0,
+                            event.amount_1,
+                            event.amount_0_transfer_fee,
+                            event.amount_1_transfer_fee,
+                        ],
+                    )?;
+                }
+                ClmmEvent::DecreaseLiquidity(event) if emitted_on_pool(event_index) => {
+                    db_transaction.execute(
+                        "INSERT OR IGNORE INTO liquidity_changes \
+                         VALUES (?1, ?2, ?3, ?4, ?5, 'decrease', ?6, NULL, NULL, NULL, \
+                         ?7, ?8, ?9, ?10, ?11)",
+                        params![
+                            transaction.signature,
+                            event_index,
+                            pool,
+                            transaction.slot,
+                            transaction.block_time,
+                            event.position_nft_mint.to_string(),
+                            event.liquidity.to_string(),
+                            event.decrease_amount_0,
+                            event.decrease_amount_1,
+                            event.transfer_fee_0,
+                            event.transfer_fee_1,
+                        ],
+                    )?;
+                }
+                ClmmEvent::CollectPersonalFee(event) if emitted_on_pool(event_index) => {
+                    db_transaction.execute(
+                        "INSERT OR IGNORE INTO fee_collections \
+                         VALUES (?1, ?2, ?3, ?4, ?5, 'position', ?6, ?7, ?8)",
+                        params![
+                            transaction.signature,
+                            event_index,
+                            pool,
+                            transaction.slot,
+                            transaction.block_time,
+                            event.position_nft_mint.to_string(),
+                            event.amount_0,
+                            event.amount_1,
+                        ],
+                    )?;
+                }
+                ClmmEvent::CollectProtocolFee(event) if event.pool_state == self.pool_id => {
+                    db_transaction.execute(
+                        "INSERT OR IGNORE INTO fee_collections \
+                         VALUES (?1, ?2, ?3, ?4, ?5, 'protocol', NULL, ?6, ?7)",
+                        params![
+                            transaction.signature,
+                            event_index,
+                            pool,
+                            transaction.slot,
+                            transaction.block_time,
+                            event.amount_0,
+                            event.amount_1,
+                        ],
+                    )?;
+                }
+                _ => {}
+            }
+        }
+        db_transaction.execute(
+            "INSERT INTO checkpoints VALUES (?1, ?2, ?3) \
+             ON CONFLICT (pool) DO UPDATE SET signature = excluded.signature, slot = excluded.slot \
+             WHERE excluded.slot >= checkpoints.slot",
+            params![pool, transaction.signature, transaction.slot],
+        )?;
+        db_transaction.commit()?;
+        Ok(())
+    }
+
+    /// Index the transactions of a JSON dump, e.g. written by [`Indexer::index`], oldest
+    /// first. Returns how many were read.
+    pub fn replay(&mut self, path: &str) -> Result<usize> {
+        let data = std::fs::read(path)?;
+        let mut transactions: Vec<EncodedConfirmedTransactionWithStatusMeta> =
+            serde_json::from_slice(&data).map_err(|err| ClientError::Dump(err.to_string()))?;
+        transactions.sort_by_key(|transaction| transaction.slot);
+        for transaction in &transactions {
+            let transaction = decode_transaction(&self.raydium_v3_program, transaction)?;
+            self.store(&transaction)?;
+        }
+        Ok(transactions.len())
+    }
+
+    /// Fetch and index the transactions of the pool since the checkpoint, oldest first so an
+    /// interrupted run resumes where it stopped. Without a checkpoint, only the newest `depth`
+    /// transactions are indexed when given. With `dump`, the fetched transactions are also
+    /// written there as JSON for [`Indexer::replay`]. Returns how many were indexed.
+    pub async fn index(
+        &mut self,
+        rpc_client: &RpcClient,
+        depth: Option<usize>,
+        dump: Option<&str>,
+    ) -> Result<usize> {
+        let until = self.checkpoint()?;
+        let depth = depth.filter(|_| until.is_none());
+        let signatures = get_signatures(rpc_client, &self.pool_id, until, depth).await?;
+        log::info!("signatures:{}", signatures.len());
+
+        let mut fetched = Vec::new();
+        for signature in signatures.iter().rev() {
+            let transaction = get_transaction(rpc_client, signature).await?;
+            let indexed = decode_transaction(&self.raydium_v3_program, &transaction)?;
+            self.store(&indexed)?;
+            if dump.is_some() {
+                fetched.push(transaction);
+            }
+        }
+        if let Some(dump) = dump {
+            let data = serde_json::to_vec_pretty(&fetched)
+                .map_err(|err| ClientError::Dump(err.to_string()))?;
+            std::fs::write(dump, data)?;
+        }
+        Ok(signatures.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/indexer-dump.json"
+    );
+
+    fn rows(indexer: &Indexer, query: &str) -> Vec<(u64, String)> {
+        let mut statement = indexer.connection.prepare(query).unwrap();
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn replays_a_dump() {
+        // swaps on the pool at slots 100 and 102, a deposit at 101, a withdrawal from another
+        // pool at 102 and from the pool at 103, and a failed swap at 104
+        let program = Pubkey::from_str("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK").unwrap();
+        let path = std::env::temp_dir().join(format!("{}-index.db", Pubkey::new_unique()));
+        let mut indexer = Indexer::open(
+            path.to_str().unwrap(),
+            program,
+            Pubkey::new_from_array([1; 32]),
+        )
+        .unwrap();
+        assert_eq!(indexer.replay(DUMP).unwrap(), 5);
+        // replaying again stores nothing new
+        assert_eq!(indexer.replay(DUMP).unwrap(), 5);
+
+        let transactions = rows(
+            &indexer,
+            "SELECT slot, failed || ':' || instructions FROM transactions ORDER BY slot",
+        );
+        assert_eq!(
+            transactions,
+            vec![
+                (100, "0:swap_v2".to_string()),
+                (101, "0:increase_liquidity_v2".to_string()),
+                (102, "0:decrease_liquidity_v2,swap_v2".to_string()),
+                (103, "0:decrease_liquidity_v2".to_string()),
+                (104, "1:swap_v2".to_string()),
+            ]
+        );
+        let swaps = rows(
+            &indexer,
+            "SELECT slot, amount_0 || ':' || amount_1 FROM swaps ORDER BY slot",
+        );
+        assert_eq!(
+            swaps,
+            vec![
+                (100, "1000:1990".to_string()),
+                (102, "500:1010".to_string())
+            ]
+        );
+        let liquidity_changes = rows(
+            &indexer,
+            "SELECT slot, kind || ':' || liquidity || ':' || amount_0 || ':' || amount_1 \
+             FROM liquidity_changes ORDER BY slot",
+        );
+        assert_eq!(
+            liquidity_changes,
+            vec![
+                (101, "increase:5000:300:600".to_string()),
+                (103, "decrease:2000:120:240".to_string()),
+            ]
+        );
+        let fee_collections = rows(
+            &indexer,
+            "SELECT slot, kind || ':' || amount_0 || ':' || amount_1 FROM fee_collections",
+        );
+        assert_eq!(fee_collections, vec![(103, "position:3:4".to_string())]);
+        assert_eq!(
+            indexer.checkpoint().unwrap(),
+            Some(Signature::from([104; 64]))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,27 +1,42 @@
-use super::ClientConfig;
+use crate::config::ClientConfig;
+use crate::error::Result;
+use crate::pda::{
+    get_observation_key, get_operation_key, get_personal_position_key, get_pool_key,
+    get_pool_reward_vault_key, get_pool_vault_key, get_protocol_position_key, get_tick_array_key,
+};
 use anchor_client::Client;
 use anchor_client::Cluster;
-use anyhow::anyhow;
-use anyhow::Result;
+use anchor_client::Program;
 use raydium_amm_v3::accounts as raydium_accounts;
 use raydium_amm_v3::instruction as raydium_instruction;
-use raydium_amm_v3::states::POSITION_SEED;
-use raydium_amm_v3::states::TICK_ARRAY_SEED;
-use raydium_amm_v3::states::{
-    oracle::OBSERVATION_SEED,
-    pool::{POOL_SEED, POOL_VAULT_SEED},
-};
+use raydium_amm_v3::instructions::InitializeRewardParam;
 use solana_sdk::instruction::AccountMeta;
 use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signer::keypair::read_keypair_file;
-use solana_sdk::signer::Signer;
+use solana_sdk::signature::Keypair;
+use solana_sdk::system_instruction;
 use solana_sdk::system_program;
 use solana_sdk::sysvar;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
 use std::rc::Rc;
 
+/// Metaplex token metadata program, owning the position NFT metadata.
+const METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Program handle the builders below make their instructions with. They take their signers as
+/// parameters, so its payer is a throwaway keypair that never signs.
+fn instruction_program(config: &ClientConfig) -> Result<Program<Rc<Keypair>>> {
+    let url = Cluster::Custom(config.http_url.clone(), config.ws_url.clone());
+    // Client.
+    let client = Client::new(url, Rc::new(Keypair::new()));
+    Ok(client.program(config.raydium_v3_program)?)
+}
+
 pub fn create_pool_instr(
     config: &ClientConfig,
+    payer: Pubkey,
     amm_config: Pubkey,
     token_mint_0: Pubkey,
     token_mint_1: Pubkey,
@@ -31,53 +46,15 @@ pub fn create_pool_instr(
     sqrt_price_x64: u128,
     open_time: u64,
 ) -> Result<Vec<Instruction>> {
-    let payer = read_keypair_file(&*shellexpand::tilde(&config.payer_path))
-        .map_err(|_| anyhow!("Expected payer keypair"))?;
-    dbg!(&payer.pubkey());
-    let url = Cluster::Custom(config.http_url.clone(), config.ws_url.clone());
-    // Client.
-    let client = Client::new(url, Rc::new(payer));
-    let program = client.program(config.raydium_v3_program)?;
-    let (pool_account_key, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_SEED.as_bytes(),
-            amm_config.to_bytes().as_ref(),
-            token_mint_0.to_bytes().as_ref(),
-            token_mint_1.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    dbg!(&pool_account_key);
-    let (token_vault_0, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_VAULT_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            token_mint_0.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    dbg!(&token_vault_0);
-    let (token_vault_1, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_VAULT_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            token_mint_1.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    dbg!(&token_vault_1);
-    let (observation_key, __bump) = Pubkey::find_program_address(
-        &[
-            OBSERVATION_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    dbg!(&observation_key);
+    let program = instruction_program(config)?;
+    let pool_account_key = get_pool_key(&program.id(), &amm_config, &token_mint_0, &token_mint_1);
+    let token_vault_0 = get_pool_vault_key(&program.id(), &pool_account_key, &token_mint_0);
+    let token_vault_1 = get_pool_vault_key(&program.id(), &pool_account_key, &token_mint_1);
+    let observation_key = get_observation_key(&program.id(), &pool_account_key);
     let instructions = program
         .request()
         .accounts(raydium_accounts::CreatePool {
-            pool_creator: program.payer(),
+            pool_creator: payer,
             amm_config,
             pool_state: pool_account_key,
             token_mint_0,
@@ -99,8 +76,11 @@ pub fn create_pool_instr(
     Ok(instructions)
 }
 
-pub fn increase_liquidity_instr(
+/// Open a position on `[tick_lower_index, tick_upper_index]` with `liquidity`, minting its NFT
+/// to `payer`. `nft_mint_key` must also sign, and no NFT metadata is created.
+pub fn open_position_instr(
     config: &ClientConfig,
+    payer: Pubkey,
     pool_account_key: Pubkey,
     token_vault_0: Pubkey,
     token_vault_1: Pubkey,
@@ -118,48 +98,118 @@ pub fn increase_liquidity_instr(
     tick_array_lower_start_index: i32,
     tick_array_upper_start_index: i32,
 ) -> Result<Vec<Instruction>> {
-    let payer = read_keypair_file(&*shellexpand::tilde(&config.payer_path))
-        .map_err(|_| anyhow!("Expected payer keypair"))?;
-    let url = Cluster::Custom(config.http_url.clone(), config.ws_url.clone());
-    // Client.
-    let client = Client::new(url, Rc::new(payer));
-    let program = client.program(config.raydium_v3_program)?;
+    let program = instruction_program(config)?;
     let nft_ata_token_account =
-        spl_associated_token_account::get_associated_token_address(&program.payer(), &nft_mint_key);
-    let (tick_array_lower, __bump) = Pubkey::find_program_address(
-        &[
-            TICK_ARRAY_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            &tick_array_lower_start_index.to_be_bytes(),
-        ],
-        &program.id(),
-    );
-    let (tick_array_upper, __bump) = Pubkey::find_program_address(
+        spl_associated_token_account::get_associated_token_address(&payer, &nft_mint_key);
+    let metadata_account_key = Pubkey::find_program_address(
         &[
-            TICK_ARRAY_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            &tick_array_upper_start_index.to_be_bytes(),
+            b"metadata",
+            METADATA_PROGRAM_ID.as_ref(),
+            nft_mint_key.as_ref(),
         ],
+        &METADATA_PROGRAM_ID,
+    )
+    .0;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::OpenPositionV2 {
+            payer,
+            position_nft_owner: payer,
+            position_nft_mint: nft_mint_key,
+            position_nft_account: nft_ata_token_account,
+            metadata_account: metadata_account_key,
+            pool_state: pool_account_key,
+            protocol_position: get_protocol_position_key(
+                &program.id(),
+                &pool_account_key,
+                tick_lower_index,
+                tick_upper_index,
+            ),
+            tick_array_lower: get_tick_array_key(
+                &program.id(),
+                &pool_account_key,
+                tick_array_lower_start_index,
+            ),
+            tick_array_upper: get_tick_array_key(
+                &program.id(),
+                &pool_account_key,
+                tick_array_upper_start_index,
+            ),
+            personal_position: get_personal_position_key(&program.id(), &nft_mint_key),
+            token_account_0: user_token_account_0,
+            token_account_1: user_token_account_1,
+            token_vault_0,
+            token_vault_1,
+            rent: sysvar::rent::id(),
+            system_program: system_program::id(),
+            token_program: spl_token::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            metadata_program: METADATA_PROGRAM_ID,
+            token_program_2022: spl_token_2022::id(),
+            vault_0_mint: token_mint_0,
+            vault_1_mint: token_mint_1,
+        })
+        .accounts(remaining_accounts)
+        .args(raydium_instruction::OpenPositionV2 {
+            tick_lower_index,
+            tick_upper_index,
+            tick_array_lower_start_index,
+            tick_array_upper_start_index,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+            with_metadata: false,
+            base_flag: None,
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+pub fn increase_liquidity_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_account_key: Pubkey,
+    token_vault_0: Pubkey,
+    token_vault_1: Pubkey,
+    token_mint_0: Pubkey,
+    token_mint_1: Pubkey,
+    nft_mint_key: Pubkey,
+    user_token_account_0: Pubkey,
+    user_token_account_1: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+    liquidity: u128,
+    amount_0_max: u64,
+    amount_1_max: u64,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    tick_array_lower_start_index: i32,
+    tick_array_upper_start_index: i32,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let nft_ata_token_account =
+        spl_associated_token_account::get_associated_token_address(&payer, &nft_mint_key);
+    let tick_array_lower = get_tick_array_key(
         &program.id(),
+        &pool_account_key,
+        tick_array_lower_start_index,
     );
-    let (protocol_position_key, __bump) = Pubkey::find_program_address(
-        &[
-            POSITION_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            &tick_lower_index.to_be_bytes(),
-            &tick_upper_index.to_be_bytes(),
-        ],
+    let tick_array_upper = get_tick_array_key(
         &program.id(),
+        &pool_account_key,
+        tick_array_upper_start_index,
     );
-    let (personal_position_key, __bump) = Pubkey::find_program_address(
-        &[POSITION_SEED.as_bytes(), nft_mint_key.to_bytes().as_ref()],
+    let protocol_position_key = get_protocol_position_key(
         &program.id(),
+        &pool_account_key,
+        tick_lower_index,
+        tick_upper_index,
     );
+    let personal_position_key = get_personal_position_key(&program.id(), &nft_mint_key);
 
     let instructions = program
         .request()
         .accounts(raydium_accounts::IncreaseLiquidityV2 {
-            nft_owner: program.payer(),
+            nft_owner: payer,
             nft_account: nft_ata_token_account,
             pool_state: pool_account_key,
             protocol_position: protocol_position_key,
@@ -188,6 +238,7 @@ pub fn increase_liquidity_instr(
 
 pub fn decrease_liquidity_instr(
     config: &ClientConfig,
+    payer: Pubkey,
     pool_account_key: Pubkey,
     token_vault_0: Pubkey,
     token_vault_1: Pubkey,
@@ -205,47 +256,30 @@ pub fn decrease_liquidity_instr(
     tick_array_lower_start_index: i32,
     tick_array_upper_start_index: i32,
 ) -> Result<Vec<Instruction>> {
-    let payer = read_keypair_file(&*shellexpand::tilde(&config.payer_path))
-        .map_err(|_| anyhow!("Expected payer keypair"))?;
-    let url = Cluster::Custom(config.http_url.clone(), config.ws_url.clone());
-    // Client.
-    let client = Client::new(url, Rc::new(payer));
-    let program = client.program(config.raydium_v3_program)?;
+    let program = instruction_program(config)?;
     let nft_ata_token_account =
-        spl_associated_token_account::get_associated_token_address(&program.payer(), &nft_mint_key);
-    let (personal_position_key, __bump) = Pubkey::find_program_address(
-        &[POSITION_SEED.as_bytes(), nft_mint_key.to_bytes().as_ref()],
-        &program.id(),
-    );
-    let (protocol_position_key, __bump) = Pubkey::find_program_address(
-        &[
-            POSITION_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            &tick_lower_index.to_be_bytes(),
-            &tick_upper_index.to_be_bytes(),
-        ],
+        spl_associated_token_account::get_associated_token_address(&payer, &nft_mint_key);
+    let personal_position_key = get_personal_position_key(&program.id(), &nft_mint_key);
+    let protocol_position_key = get_protocol_position_key(
         &program.id(),
+        &pool_account_key,
+        tick_lower_index,
+        tick_upper_index,
     );
-    let (tick_array_lower, __bump) = Pubkey::find_program_address(
-        &[
-            TICK_ARRAY_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            &tick_array_lower_start_index.to_be_bytes(),
-        ],
+    let tick_array_lower = get_tick_array_key(
         &program.id(),
+        &pool_account_key,
+        tick_array_lower_start_index,
     );
-    let (tick_array_upper, __bump) = Pubkey::find_program_address(
-        &[
-            TICK_ARRAY_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            &tick_array_upper_start_index.to_be_bytes(),
-        ],
+    let tick_array_upper = get_tick_array_key(
         &program.id(),
+        &pool_account_key,
+        tick_array_upper_start_index,
     );
     let instructions = program
         .request()
         .accounts(raydium_accounts::DecreaseLiquidityV2 {
-            nft_owner: program.payer(),
+            nft_owner: payer,
             nft_account: nft_ata_token_account,
             personal_position: personal_position_key,
             pool_state: pool_account_key,
@@ -271,3 +305,404 @@ pub fn decrease_liquidity_instr(
         .instructions()?;
     Ok(instructions)
 }
+
+pub fn swap_v2_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    amm_config: Pubkey,
+    pool_account_key: Pubkey,
+    input_vault: Pubkey,
+    output_vault: Pubkey,
+    observation_state: Pubkey,
+    user_input_token: Pubkey,
+    user_out_put_token: Pubkey,
+    input_vault_mint: Pubkey,
+    output_vault_mint: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+    amount: u64,
+    other_amount_threshold: u64,
+    sqrt_price_limit_x64: Option<u128>,
+    is_base_input: bool,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::SwapSingleV2 {
+            payer,
+            amm_config,
+            pool_state: pool_account_key,
+            input_token_account: user_input_token,
+            output_token_account: user_out_put_token,
+            input_vault,
+            output_vault,
+            observation_state,
+            token_program: spl_token::id(),
+            token_program_2022: spl_token_2022::id(),
+            memo_program: spl_memo::id(),
+            input_vault_mint,
+            output_vault_mint,
+        })
+        .accounts(remaining_accounts)
+        .args(raydium_instruction::SwapV2 {
+            amount,
+            other_amount_threshold,
+            sqrt_price_limit_x64: sqrt_price_limit_x64.unwrap_or(0u128),
+            is_base_input,
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Swap `amount_in` through several pools, each hop's accounts following one another in
+/// `remaining_accounts`.
+pub fn swap_router_base_in_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    input_token_account: Pubkey,
+    input_token_mint: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+    amount_in: u64,
+    amount_out_minimum: u64,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::SwapRouterBaseIn {
+            payer,
+            input_token_account,
+            input_token_mint,
+            token_program: spl_token::id(),
+            token_program_2022: spl_token_2022::id(),
+            memo_program: spl_memo::id(),
+        })
+        .accounts(remaining_accounts)
+        .args(raydium_instruction::SwapRouterBaseIn {
+            amount_in,
+            amount_out_minimum,
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Open reward stream of `reward_token_mint` on a pool, funded from `funder_token_account` for
+/// the whole `[open_time, end_time]` period.
+pub fn initialize_reward_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_account_key: Pubkey,
+    amm_config: Pubkey,
+    funder_token_account: Pubkey,
+    reward_token_mint: Pubkey,
+    reward_token_program: Pubkey,
+    open_time: u64,
+    end_time: u64,
+    emissions_per_second_x64: u128,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::InitializeReward {
+            reward_funder: payer,
+            funder_token_account,
+            amm_config,
+            pool_state: pool_account_key,
+            operation_state: get_operation_key(&program.id()),
+            reward_token_mint,
+            reward_token_vault: get_pool_reward_vault_key(
+                &program.id(),
+                &pool_account_key,
+                &reward_token_mint,
+            ),
+            reward_token_program,
+            system_program: system_program::id(),
+            rent: sysvar::rent::id(),
+        })
+        .args(raydium_instruction::InitializeReward {
+            param: InitializeRewardParam {
+                open_time,
+                end_time,
+                emissions_per_second_x64,
+            },
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Change the emission rate or period of reward `reward_index`. The pool owner funds the
+/// difference from `authority_token_account`, passed with the reward vault and mint as
+/// remaining accounts.
+pub fn set_reward_params_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_account_key: Pubkey,
+    amm_config: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+    reward_index: u8,
+    emissions_per_second_x64: u128,
+    open_time: u64,
+    end_time: u64,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::SetRewardParams {
+            authority: payer,
+            amm_config,
+            pool_state: pool_account_key,
+            operation_state: get_operation_key(&program.id()),
+            token_program: spl_token::id(),
+            token_program_2022: spl_token_2022::id(),
+        })
+        .accounts(remaining_accounts)
+        .args(raydium_instruction::SetRewardParams {
+            reward_index,
+            emissions_per_second_x64,
+            open_time,
+            end_time,
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Bring the reward growth of a pool up to the current time.
+pub fn update_reward_infos_instr(
+    config: &ClientConfig,
+    pool_account_key: Pubkey,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::UpdateRewardInfos {
+            pool_state: pool_account_key,
+        })
+        .args(raydium_instruction::UpdateRewardInfos)
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Collect the protocol fees of a pool, signed by `admin`.
+pub fn collect_protocol_fee_instr(
+    config: &ClientConfig,
+    admin: Pubkey,
+    pool_account_key: Pubkey,
+    amm_config: Pubkey,
+    token_vault_0: Pubkey,
+    token_vault_1: Pubkey,
+    token_mint_0: Pubkey,
+    token_mint_1: Pubkey,
+    recipient_token_account_0: Pubkey,
+    recipient_token_account_1: Pubkey,
+    amount_0_requested: u64,
+    amount_1_requested: u64,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::CollectProtocolFee {
+            owner: admin,
+            pool_state: pool_account_key,
+            amm_config,
+            token_vault_0,
+            token_vault_1,
+            vault_0_mint: token_mint_0,
+            vault_1_mint: token_mint_1,
+            recipient_token_account_0,
+            recipient_token_account_1,
+            token_program: spl_token::id(),
+            token_program_2022: spl_token_2022::id(),
+        })
+        .args(raydium_instruction::CollectProtocolFee {
+            amount_0_requested,
+            amount_1_requested,
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Collect the fund fees of a pool, signed by `admin` as fund owner.
+pub fn collect_fund_fee_instr(
+    config: &ClientConfig,
+    admin: Pubkey,
+    pool_account_key: Pubkey,
+    amm_config: Pubkey,
+    token_vault_0: Pubkey,
+    token_vault_1: Pubkey,
+    token_mint_0: Pubkey,
+    token_mint_1: Pubkey,
+    recipient_token_account_0: Pubkey,
+    recipient_token_account_1: Pubkey,
+    amount_0_requested: u64,
+    amount_1_requested: u64,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::CollectFundFee {
+            owner: admin,
+            pool_state: pool_account_key,
+            amm_config,
+            token_vault_0,
+            token_vault_1,
+            vault_0_mint: token_mint_0,
+            vault_1_mint: token_mint_1,
+            recipient_token_account_0,
+            recipient_token_account_1,
+            token_program: spl_token::id(),
+            token_program_2022: spl_token_2022::id(),
+        })
+        .args(raydium_instruction::CollectFundFee {
+            amount_0_requested,
+            amount_1_requested,
+        })
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Set the status bits of a pool, signed by `admin`.
+pub fn update_pool_status_instr(
+    config: &ClientConfig,
+    admin: Pubkey,
+    pool_account_key: Pubkey,
+    status: u8,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::UpdatePoolStatus {
+            authority: admin,
+            pool_state: pool_account_key,
+        })
+        .args(raydium_instruction::UpdatePoolStatus { status })
+        .instructions()?;
+    Ok(instructions)
+}
+
+pub fn create_operation_account_instr(
+    config: &ClientConfig,
+    admin: Pubkey,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::CreateOperationAccount {
+            owner: admin,
+            operation_state: get_operation_key(&program.id()),
+            system_program: system_program::id(),
+        })
+        .args(raydium_instruction::CreateOperationAccount)
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Add or remove operation owners or whitelisted mints, as selected by `param`.
+pub fn update_operation_account_instr(
+    config: &ClientConfig,
+    admin: Pubkey,
+    param: u8,
+    keys: Vec<Pubkey>,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::UpdateOperationAccount {
+            owner: admin,
+            operation_state: get_operation_key(&program.id()),
+            system_program: system_program::id(),
+        })
+        .args(raydium_instruction::UpdateOperationAccount { param, keys })
+        .instructions()?;
+    Ok(instructions)
+}
+
+pub fn close_position_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    nft_mint_key: Pubkey,
+) -> Result<Vec<Instruction>> {
+    let program = instruction_program(config)?;
+    let nft_ata_token_account =
+        spl_associated_token_account::get_associated_token_address(&payer, &nft_mint_key);
+    let personal_position_key = get_personal_position_key(&program.id(), &nft_mint_key);
+    let instructions = program
+        .request()
+        .accounts(raydium_accounts::ClosePosition {
+            nft_owner: payer,
+            position_nft_mint: nft_mint_key,
+            position_nft_account: nft_ata_token_account,
+            personal_position: personal_position_key,
+            system_program: system_program::id(),
+            token_program: spl_token::id(),
+        })
+        .args(raydium_instruction::ClosePosition)
+        .instructions()?;
+    Ok(instructions)
+}
+
+/// Idempotently create `owner`'s ATA of `mint` under `token_program`. For the native mint,
+/// `wrap_amount` lamports are also moved into it and synced as WSOL.
+pub fn create_ata_token_account_instr(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    wrap_amount: u64,
+) -> Result<Vec<Instruction>> {
+    let mut instructions = vec![create_associated_token_account_idempotent(
+        payer,
+        owner,
+        mint,
+        token_program,
+    )];
+    if *mint == spl_token::native_mint::id() && wrap_amount > 0 {
+        let wsol_account = get_associated_token_address_with_program_id(owner, mint, token_program);
+        instructions.push(system_instruction::transfer(
+            payer,
+            &wsol_account,
+            wrap_amount,
+        ));
+        instructions.push(spl_token::instruction::sync_native(
+            token_program,
+            &wsol_account,
+        )?);
+    }
+    Ok(instructions)
+}
+
+/// Close `owner`'s WSOL ATA, unwrapping its whole balance back to SOL.
+pub fn close_wsol_account_instr(owner: &Pubkey) -> Result<Vec<Instruction>> {
+    let wsol_account = get_associated_token_address_with_program_id(
+        owner,
+        &spl_token::native_mint::id(),
+        &spl_token::id(),
+    );
+    Ok(vec![spl_token::instruction::close_account(
+        &spl_token::id(),
+        &wsol_account,
+        owner,
+        owner,
+        &[],
+    )?])
+}
+
+/// Fail the transaction unless `owner`'s `token_account` holds at least `amount` of `mint`, by
+/// transferring that much from the account to itself: the token programs check the balance
+/// before skipping a self-transfer.
+pub fn assert_token_balance_instr(
+    owner: &Pubkey,
+    token_account: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    decimals: u8,
+    amount: u64,
+) -> Result<Vec<Instruction>> {
+    Ok(vec![spl_token_2022::instruction::transfer_checked(
+        token_program,
+        token_account,
+        mint,
+        token_account,
+        owner,
+        &[],
+        amount,
+        decimals,
+    )?])
+}
@@ -0,0 +1,35 @@
+//! Client library for the Raydium concentrated liquidity (CLMM) program: PDA helpers,
+//! instruction builders, token and math utilities, account fetchers and transaction flows.
+
+pub mod admin;
+pub mod backtest;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod depth;
+pub mod discovery;
+pub mod error;
+pub mod events;
+pub mod fees;
+pub mod indexer;
+pub mod instructions;
+pub mod oracle;
+pub mod output;
+pub mod pda;
+pub mod pnl;
+pub mod quote;
+pub mod range;
+pub mod rebalance;
+pub mod rewards;
+pub mod router;
+pub mod snapshot;
+pub mod stats;
+pub mod subscription;
+pub mod tick_array;
+pub mod token;
+pub mod transactions;
+pub mod transfer_fee;
+pub mod utils;
+
+pub use config::{load_cfg, ClientConfig};
+pub use error::{ClientError, Result};
@@ -1,217 +1,1036 @@
-mod client;
-mod instructions;
-mod transactions;
-mod utils;
-
-use anchor_client::Client;
-use anchor_client::Cluster;
-use anyhow::anyhow;
-use anyhow::Result;
-use ini::Ini;
-use raydium_amm_v3::states::POOL_TICK_ARRAY_BITMAP_SEED;
-use solana_client::rpc_client::RpcClient;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use client::admin::{get_operation_lists, get_pool_status, OperationUpdate, PoolOperation};
+use client::backtest::{
+    get_backtest_config, load_swaps, load_swaps_csv, run_backtest, write_equity_curve, FixedRange,
+    PoolParams, RebalanceOnExit, Strategy, StrategyKind, VolatilityScaled,
+};
+use client::cache::AccountCache;
+use client::depth::{get_liquidity_depth, render_depth, DepthFormat};
+use client::discovery::{find_pools_by_mint, find_pools_by_mints};
+use client::fees::{FeeKind, FeeScope};
+use client::indexer::Indexer;
+use client::oracle::get_pool_twap;
+use client::output::{Output, OutputFormat};
+use client::pnl::{
+    aggregate_pnl, get_owner_position_mints, get_position_pnl, PnlReport, PositionLedger,
+};
+use client::quote::quote_exact_input;
+use client::range::RangeStrategy;
+use client::rebalance::{run_rebalancer, RebalanceConfig};
+use client::rewards::get_pool_rewards;
+use client::snapshot::{fetch_pool_snapshot, PoolSnapshot};
+use client::stats::{get_pool_stats, StatsSource};
+use client::subscription::watch_pool;
+use client::tick_array::{get_pool_tick_arrays, load_pool_tick_arrays};
+use client::transactions::{
+    collect_fees_tx, create_pool_tx, deposit_both_tx, increase_liquidity_tx, initialize_reward_tx,
+    set_reward_params_tx, swap_tx, update_operation_account_tx, update_pool_status_tx,
+    update_reward_infos_tx, zap_out_tx,
+};
+use client::utils::{price_to_sqrt_price_x64, sqrt_price_x64_to_f64};
+use client::{load_cfg, ClientConfig};
+use raydium_amm_v3::libraries::tick_math;
+use raydium_amm_v3::states::AmmConfig;
+use serde_json::json;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
 use solana_sdk::signer::keypair::read_keypair_file;
-use std::str::FromStr;
-use std::sync::Arc;
-use std::time::SystemTime;
-use transactions::create_pool_tx;
-use transactions::increase_liquidity_tx;
+use solana_sdk::signer::Signer;
+use std::time::{Duration, SystemTime};
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct ClientConfig {
-    http_url: String,
-    ws_url: String,
-    payer_path: String,
-    admin_path: String,
-    raydium_v3_program: Pubkey,
-    slippage: f64,
-    amm_config_key: Pubkey,
-    mint0: Option<Pubkey>,
-    mint1: Option<Pubkey>,
-    pool_id_account: Option<Pubkey>,
-    tickarray_bitmap_extension: Option<Pubkey>,
-    amm_config_index: u16,
+#[derive(Debug, Parser)]
+#[clap(about = "Play with RaydiumV3 AMM")]
+struct Opts {
+    /// Path to the config file.
+    #[clap(long, default_value = "config.ini")]
+    config: String,
+    /// Results on stdout as text, json or ndjson.
+    #[clap(long, global = true, default_value = "text")]
+    output: OutputFormat,
+    /// Logs on stderr from this level: off, error, warn, info, debug or trace. `RUST_LOG`
+    /// takes precedence.
+    #[clap(long, global = true, default_value = "info")]
+    log_level: log::LevelFilter,
+    #[clap(subcommand)]
+    command: AnyCommand,
 }
 
-fn load_cfg(client_config: &String) -> Result<ClientConfig> {
-    let config =
-        Ini::load_from_file(client_config).expect("Expect 'config.ini' file at the project root");
-    // let _map = config.load(client_config).unwrap();
-    let global_section = config
-        .section(Some("Global"))
-        .expect("Didn't define Global section");
-    // WARN: Just replace unwrap with expect.
-    let http_url = global_section.get("http_url").unwrap().to_string();
-    if http_url.is_empty() {
-        panic!("http_url must not be empty");
-    }
-    let ws_url = global_section.get("ws_url").unwrap().to_string();
-    if ws_url.is_empty() {
-        panic!("ws_url must not be empty");
-    }
-    let payer_path = global_section.get("payer_path").unwrap().to_string();
-    if payer_path.is_empty() {
-        panic!("payer_path must not be empty");
-    }
-    let admin_path = global_section.get("admin_path").unwrap().to_string();
-    if admin_path.is_empty() {
-        panic!("admin_path must not be empty");
-    }
+#[derive(Debug, Parser)]
+enum Command {
+    /// Create the pool of the configured mints at `price` (token1 per token0, UI amounts).
+    CreatePool {
+        price: f64,
+        #[clap(long, default_value_t = 1)]
+        config_index: u16,
+        /// Unix timestamp from which the pool can be traded, now by default.
+        #[clap(long)]
+        open_time: Option<u64>,
+    },
+    /// Add liquidity to the position over a range from a single token amount, opening it when
+    /// there is none.
+    IncreaseLiquidity {
+        tick_lower_price: f64,
+        tick_upper_price: f64,
+        input_amount: u64,
+        /// `input_amount` is of token1 instead of token0.
+        #[clap(long)]
+        base_token_1: bool,
+    },
+    /// Deposit up to both amounts, optionally swapping the excess token first in the same
+    /// transaction.
+    Deposit {
+        tick_lower_price: f64,
+        tick_upper_price: f64,
+        amount_0_max: u64,
+        amount_1_max: u64,
+        #[clap(long)]
+        zap_in: bool,
+    },
+    /// Withdraw from a position into a single token.
+    ZapOut {
+        nft_mint: Pubkey,
+        /// Share of the liquidity to withdraw, within (0, 1].
+        #[clap(long, default_value_t = 1.0)]
+        percent: f64,
+        /// Receive token1 instead of token0.
+        #[clap(long)]
+        to_token_1: bool,
+        /// Pool to swap through instead of the position's own pool.
+        #[clap(long)]
+        swap_pool: Option<Pubkey>,
+        /// Close the position, only with all of its liquidity withdrawn.
+        #[clap(long)]
+        close_position: bool,
+    },
+    /// Save every account needed to quote against a pool, as JSON when `path` ends with
+    /// `.json` and as bincode otherwise.
+    Snapshot {
+        path: String,
+        /// Pool to snapshot instead of the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// List the initialized tick arrays of a pool.
+    TickArrays {
+        /// Pool to list instead of the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Liquidity of a pool by price range, within `±percent` of the current price.
+    Depth {
+        #[clap(long, default_value_t = 0.1)]
+        percent: f64,
+        /// text, csv or json, when `--output` is text.
+        #[clap(long, default_value = "text")]
+        format: DepthFormat,
+        /// Read the pool from a file saved by `snapshot` instead of the chain.
+        #[clap(long)]
+        snapshot: Option<String>,
+        /// Pool to show instead of the snapshot's or the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Quote swapping exactly `amount_in` (raw) through a pool, crossing its initialized ticks.
+    Quote {
+        amount_in: u64,
+        /// Swap token1 for token0 instead of token0 for token1.
+        #[clap(long)]
+        input_token_1: bool,
+        /// Read the pool from a file saved by `snapshot` instead of the chain.
+        #[clap(long)]
+        snapshot: Option<String>,
+        /// Pool to quote instead of the snapshot's or the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Time weighted average price of a pool over the last `window` seconds.
+    Twap {
+        #[clap(long, default_value_t = 600)]
+        window: u32,
+        /// Pool to price instead of the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// List the pools of a mint pair under every fee tier, or every pool trading a mint.
+    Pools {
+        mint_a: Pubkey,
+        mint_b: Option<Pubkey>,
+    },
+    /// Swap `amount_in` (raw) along the best route across fee tiers and intermediate mints.
+    Swap {
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount_in: u64,
+        /// Split the input across routes when it gives more output.
+        #[clap(long)]
+        split: bool,
+    },
+    /// Start a reward emitting `emissions_per_second` (UI amount) between two unix timestamps.
+    InitReward {
+        reward_mint: Pubkey,
+        emissions_per_second: f64,
+        open_time: u64,
+        end_time: u64,
+        /// Pool to reward instead of the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Extend or top up a reward, keeping what is not given.
+    SetReward {
+        reward_index: u8,
+        #[clap(long)]
+        emissions_per_second: Option<f64>,
+        #[clap(long)]
+        open_time: Option<u64>,
+        #[clap(long)]
+        end_time: Option<u64>,
+        /// Pool of the reward instead of the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Accrue the rewards of a pool up to now.
+    UpdateRewards {
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Show the rewards of a pool with their remaining funds and end time.
+    Rewards {
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Report the protocol or fund fees accrued on a pool, the pools of an AmmConfig or every
+    /// pool, and sweep them to the configured recipient with the admin keypair.
+    CollectFees {
+        /// protocol or fund.
+        kind: FeeKind,
+        /// Pool to collect from instead of the configured one.
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+        /// Collect from every pool of this AmmConfig.
+        #[clap(long, conflicts_with = "pool_id")]
+        amm_config: Option<Pubkey>,
+        /// Collect from every pool of the program.
+        #[clap(long, conflicts_with_all = &["pool_id", "amm_config"])]
+        all: bool,
+        /// Only report the fees.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Show which operations of a pool are enabled, and its open time. The program has no
+    /// instruction changing the open time, which is only set by `create-pool --open-time`.
+    PoolStatus {
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Enable or disable operations of a pool with the admin keypair: open-position,
+    /// decrease-liquidity, collect-fee, collect-reward or swap.
+    SetPoolStatus {
+        #[clap(long, multiple_occurrences = true)]
+        enable: Vec<PoolOperation>,
+        #[clap(long, multiple_occurrences = true)]
+        disable: Vec<PoolOperation>,
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Show the operation owners and whitelisted mints.
+    Operation,
+    /// Update the operation account with the admin keypair: add-owners, remove-owners,
+    /// add-whitelist-mints or remove-whitelist-mints.
+    UpdateOperation {
+        update: OperationUpdate,
+        #[clap(required = true)]
+        keys: Vec<Pubkey>,
+    },
+    /// Watch positions and move each one back around the current price when it leaves its
+    /// range, until killed.
+    Rebalance {
+        #[clap(required = true)]
+        nft_mints: Vec<Pubkey>,
+        /// New ranges span `±percent` around the current price.
+        #[clap(long, default_value_t = 0.05)]
+        percent: f64,
+        /// New ranges span `±ticks` around the current tick instead.
+        #[clap(long)]
+        ticks: Option<i32>,
+        /// Also move positions in range once they drift past this share of their half width.
+        #[clap(long)]
+        drift_threshold: Option<f64>,
+        /// Seconds between two moves of the same position.
+        #[clap(long, default_value_t = 3600)]
+        cooldown: u64,
+        /// Seconds between two checks.
+        #[clap(long, default_value_t = 60)]
+        interval: u64,
+        /// The configured slippage by default.
+        #[clap(long)]
+        max_slippage: Option<f64>,
+        /// Skip moves whose swap costs more than this share of the position value.
+        #[clap(long, default_value_t = 0.01)]
+        max_cost_ratio: f64,
+        /// Skip moves whose new position would not earn back their swap, transaction fees and
+        /// rent within this many seconds in range, at the pool's recent fee rate.
+        #[clap(long, default_value_t = 86_400)]
+        fee_horizon: u64,
+        /// Database written by `index` to take the fee rate of the last day from. Without it,
+        /// the pool is read twice `--sample-secs` apart when a move is planned.
+        #[clap(long)]
+        db: Option<String>,
+        #[clap(long, default_value_t = 60)]
+        sample_secs: u64,
+        /// Price of one SOL in token1, valuing transaction fees and rent in pools not trading
+        /// SOL.
+        #[clap(long)]
+        sol_price: Option<f64>,
+        /// Lamports the payer must hold to move a position.
+        #[clap(long, default_value_t = 10_000_000)]
+        min_sol_balance: u64,
+        /// Only report the moves.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Report the price, tick and liquidity of a pool live, its transactions, and when the
+    /// payer's positions on it go in or out of range.
+    Watch {
+        /// Also report the changes of its initialized tick arrays.
+        #[clap(long)]
+        tick_arrays: bool,
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Index the swaps, deposits, withdrawals and fee collections of a pool into a SQLite
+    /// database, resuming from the last indexed transaction.
+    Index {
+        db: String,
+        /// Without a checkpoint, only index this many of the newest transactions.
+        #[clap(long)]
+        depth: Option<usize>,
+        /// Also write the fetched transactions to this JSON file.
+        #[clap(long)]
+        dump: Option<String>,
+        /// Index the transactions of a JSON dump instead of fetching them.
+        #[clap(long, conflicts_with_all = &["depth", "dump"])]
+        replay: Option<String>,
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Value positions against holding their deposits: impermanent loss, fees, fee APR and
+    /// net PnL, per position and in total. All the payer's positions, or `--owner`'s, by
+    /// default.
+    Pnl {
+        nft_mints: Vec<Pubkey>,
+        /// Mint to value in, token1 of each pool by default.
+        #[clap(long)]
+        quote_mint: Option<Pubkey>,
+        /// Raw entry price of a single position, with its entry amounts, instead of replaying
+        /// its transactions.
+        #[clap(long, requires_all = &["entry_amount_0", "entry_amount_1"])]
+        entry_price: Option<f64>,
+        #[clap(long, requires = "entry_price")]
+        entry_amount_0: Option<u64>,
+        #[clap(long, requires = "entry_price")]
+        entry_amount_1: Option<u64>,
+        /// Unix timestamp of the entry, for the fee APR.
+        #[clap(long, requires = "entry_price")]
+        entry_time: Option<i64>,
+        /// Owner of the positions valued when none are given, instead of the payer.
+        #[clap(long, conflicts_with = "nft_mints")]
+        owner: Option<Pubkey>,
+    },
+    /// Replay the swaps of a pool through a range strategy and report its fee income,
+    /// impermanent loss and rebalances. Tick spacing and fee rates are taken from the pool
+    /// unless all given.
+    Backtest {
+        /// Database written by `index`, or a `.csv` file of swaps with `slot`, `block_time`,
+        /// `sqrt_price_x64`, `liquidity` and `tick` columns.
+        source: String,
+        /// Raw amounts deposited at the start.
+        amount_0: u64,
+        amount_1: u64,
+        /// fixed, exit or volatility.
+        #[clap(long, default_value = "exit")]
+        strategy: StrategyKind,
+        /// Ranges of the fixed and exit strategies span `±percent` around the price.
+        #[clap(long, default_value_t = 0.05)]
+        percent: f64,
+        /// Ranges of the fixed and exit strategies span `±ticks` around the tick instead.
+        #[clap(long)]
+        ticks: Option<i32>,
+        /// The exit strategy also moves positions drifting past this share of their half width.
+        #[clap(long)]
+        drift_threshold: Option<f64>,
+        /// Ranges of the volatility strategy span `±multiplier` standard deviations of the tick.
+        #[clap(long, default_value_t = 2.0)]
+        multiplier: f64,
+        /// Swaps the volatility strategy measures the deviation over.
+        #[clap(long, default_value_t = 500)]
+        window: usize,
+        /// Write the equity curve to this CSV file.
+        #[clap(long)]
+        equity_curve: Option<String>,
+        /// Tick spacing and fee rates of the pool, read from it unless all are given.
+        #[clap(long)]
+        tick_spacing: Option<u16>,
+        #[clap(long)]
+        trade_fee_rate: Option<u32>,
+        #[clap(long)]
+        protocol_fee_rate: Option<u32>,
+        #[clap(long)]
+        fund_fee_rate: Option<u32>,
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+    /// Volume, fees, value locked, fee APR and utilization of a pool over the last day and
+    /// week.
+    PoolStats {
+        /// Database written by `index` to take the swaps from. Without it, the pool is read
+        /// twice `--sample-secs` apart and the figures extrapolated.
+        #[clap(long)]
+        db: Option<String>,
+        #[clap(long, default_value_t = 60)]
+        sample_secs: u64,
+        /// Fee APR of a position spanning `±range_width` around the price.
+        #[clap(long, default_value_t = 0.05)]
+        range_width: f64,
+        #[clap(long)]
+        pool_id: Option<Pubkey>,
+    },
+}
 
-    let raydium_v3_program_str = global_section.get("raydium_v3_program").unwrap();
-    if raydium_v3_program_str.is_empty() {
-        panic!("raydium_v3_program must not be empty");
-    }
-    let raydium_v3_program = Pubkey::from_str(&raydium_v3_program_str).unwrap();
-    let slippage = global_section.get("slippage").unwrap().parse::<f64>()?;
+/// Commands computed locally, without the config or an RPC endpoint.
+#[derive(Debug, Parser)]
+enum OfflineCommand {
+    /// Tick of a raw price.
+    PriceToTick { price: f64 },
+    /// Raw price of a tick.
+    TickToPrice { tick: i32 },
+}
 
-    let pool_section = config
-        .section(Some("Pool"))
-        .expect("Didn't define Pool section");
-    let mut mint0 = None;
-    let mint0_str = pool_section.get("mint0").unwrap();
-    if !mint0_str.is_empty() {
-        mint0 = Some(Pubkey::from_str(&mint0_str).unwrap());
-    }
-    let mut mint1 = None;
-    let mint1_str = pool_section.get("mint1").unwrap();
-    if !mint1_str.is_empty() {
-        mint1 = Some(Pubkey::from_str(&mint1_str).unwrap());
-    }
-    let amm_config_index = pool_section
-        .get("amm_config_index")
-        .unwrap()
-        .parse::<u16>()?;
+#[derive(Debug, Parser)]
+enum AnyCommand {
+    #[clap(flatten)]
+    Offline(OfflineCommand),
+    #[clap(flatten)]
+    Online(Command),
+}
 
-    let (amm_config_key, __bump) = Pubkey::find_program_address(
-        &[
-            raydium_amm_v3::states::AMM_CONFIG_SEED.as_bytes(),
-            &amm_config_index.to_be_bytes(),
-        ],
-        &raydium_v3_program,
-    );
+fn read_payer(pool_config: &ClientConfig) -> Result<Keypair> {
+    read_keypair_file(&*shellexpand::tilde(&pool_config.payer_path))
+        .map_err(|_| anyhow!("failed in getting payer"))
+}
 
-    let pool_id_account = if mint0 != None && mint1 != None {
-        if mint0.unwrap() > mint1.unwrap() {
-            let temp_mint = mint0;
-            mint0 = mint1;
-            mint1 = temp_mint;
-        }
-        Some(
-            Pubkey::find_program_address(
-                &[
-                    raydium_amm_v3::states::POOL_SEED.as_bytes(),
-                    amm_config_key.to_bytes().as_ref(),
-                    mint0.unwrap().to_bytes().as_ref(),
-                    mint1.unwrap().to_bytes().as_ref(),
-                ],
-                &raydium_v3_program,
-            )
-            .0,
-        )
-    } else {
-        None
+fn read_admin(pool_config: &ClientConfig) -> Result<Keypair> {
+    read_keypair_file(&*shellexpand::tilde(&pool_config.admin_path))
+        .map_err(|_| anyhow!("failed in getting admin"))
+}
+
+/// Cache to read pool accounts through, holding the snapshot at `snapshot` when given, and the
+/// pool to read: `pool_id`, else the snapshot's, else the configured one.
+fn pool_cache(
+    pool_config: &ClientConfig,
+    snapshot: Option<&str>,
+    pool_id: Option<Pubkey>,
+) -> Result<(AccountCache, Pubkey)> {
+    // a snapshot is never refetched
+    let cache = AccountCache::new(None);
+    let snapshot_pool_id = match snapshot {
+        Some(path) => {
+            let snapshot = PoolSnapshot::load(path)?;
+            snapshot.load_into(&cache);
+            Some(snapshot.pool_id)
+        }
+        None => None,
     };
-    let tickarray_bitmap_extension = if pool_id_account != None {
-        Some(
-            Pubkey::find_program_address(
-                &[
-                    POOL_TICK_ARRAY_BITMAP_SEED.as_bytes(),
-                    pool_id_account.unwrap().to_bytes().as_ref(),
-                ],
-                &raydium_v3_program,
-            )
-            .0,
-        )
-    } else {
-        None
+    let pool_id = match pool_id.or(snapshot_pool_id) {
+        Some(pool_id) => pool_id,
+        None => pool_config.pool_id()?,
     };
-
-    Ok(ClientConfig {
-        http_url,
-        ws_url,
-        payer_path,
-        admin_path,
-        raydium_v3_program,
-        slippage,
-        amm_config_key,
-        mint0,
-        mint1,
-        pool_id_account,
-        tickarray_bitmap_extension,
-        amm_config_index,
-    })
+    Ok((cache, pool_id))
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    println!("\"Play with RaydiumV3 AMM\"");
-    let config_file = &"config.ini".to_string();
+async fn main() -> Result<()> {
+    let opts = Opts::parse();
+    env_logger::Builder::new()
+        .filter_level(opts.log_level)
+        .parse_default_env()
+        .init();
+    let output = Output::new(opts.output);
+    match opts.command {
+        AnyCommand::Offline(command) => run_offline(command, output),
+        AnyCommand::Online(command) => run(command, &opts.config, output).await,
+    }
+}
 
-    let pool_config = load_cfg(&config_file).unwrap();
-    // Admin and cluster params.
-    let payer = read_keypair_file(&*shellexpand::tilde(&pool_config.payer_path))
-        .map_err(|_| anyhow!("failed in getting payer"))?;
-    let payer = Arc::new(payer);
-    let admin = read_keypair_file(&*shellexpand::tilde(&pool_config.admin_path))
-        .map_err(|_| anyhow!("failed in getting admin"))?;
+fn run_offline(command: OfflineCommand, output: Output) -> Result<()> {
+    match command {
+        OfflineCommand::PriceToTick { price } => {
+            let tick = tick_math::get_tick_at_sqrt_price(price_to_sqrt_price_x64(price, 0, 0))?;
+            output.write(&json!({ "price": price, "tick": tick }))?;
+        }
+        OfflineCommand::TickToPrice { tick } => {
+            let sqrt_price = sqrt_price_x64_to_f64(tick_math::get_sqrt_price_at_tick(tick)?);
+            output.write(&json!({ "tick": tick, "price": sqrt_price * sqrt_price }))?;
+        }
+    }
+    Ok(())
+}
+
+async fn run(command: Command, config: &str, output: Output) -> Result<()> {
+    let pool_config = load_cfg(config)?;
     // solana rpc client
     let rpc_client = RpcClient::new(pool_config.http_url.to_string());
 
-    // anchor client.
-    let anchor_config = pool_config.clone();
-    let url = Cluster::Custom(anchor_config.http_url, anchor_config.ws_url);
-    let wallet = payer.clone();
-    let anchor_client = Client::new(url, wallet);
-    let ray_program = anchor_client.program(pool_config.raydium_v3_program)?;
-
-    /* Call create_pool externally */
-    let config_index = 1;
-    let price = 1.5;
-    let mint0 = pool_config
-        .mint0
-        .ok_or_else(|| anyhow!("Invalid mint0 pubkey"))?;
-    let mint1 = pool_config
-        .mint1
-        .ok_or_else(|| anyhow!("Invalid mint1 pubkey"))?;
-    if true {
-        create_pool_tx(
-            &rpc_client,
-            &pool_config,
-            config_index,
-            &payer,
+    match command {
+        Command::CreatePool {
             price,
-            mint0,
-            mint1,
-            {
-                let now = SystemTime::now();
-                now.duration_since(std::time::UNIX_EPOCH)?.as_secs()
-            },
-        )?;
+            config_index,
+            open_time,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let mint0 = pool_config
+                .mint0
+                .ok_or_else(|| anyhow!("Invalid mint0 pubkey"))?;
+            let mint1 = pool_config
+                .mint1
+                .ok_or_else(|| anyhow!("Invalid mint1 pubkey"))?;
+            let open_time = match open_time {
+                Some(open_time) => open_time,
+                None => SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_secs(),
+            };
+            let result = create_pool_tx(
+                &rpc_client,
+                &pool_config,
+                config_index,
+                &payer,
+                price,
+                mint0,
+                mint1,
+                open_time,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::IncreaseLiquidity {
+            tick_lower_price,
+            tick_upper_price,
+            input_amount,
+            base_token_1,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let result = increase_liquidity_tx(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                tick_lower_price,
+                tick_upper_price,
+                !base_token_1,
+                input_amount,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::Deposit {
+            tick_lower_price,
+            tick_upper_price,
+            amount_0_max,
+            amount_1_max,
+            zap_in,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let result = deposit_both_tx(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                tick_lower_price,
+                tick_upper_price,
+                amount_0_max,
+                amount_1_max,
+                zap_in,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::ZapOut {
+            nft_mint,
+            percent,
+            to_token_1,
+            swap_pool,
+            close_position,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let result = zap_out_tx(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                nft_mint,
+                percent,
+                !to_token_1,
+                swap_pool,
+                close_position,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::Snapshot { path, pool_id } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let snapshot =
+                fetch_pool_snapshot(&rpc_client, &pool_config.raydium_v3_program, &pool_id).await?;
+            snapshot.save(&path)?;
+            output.write(&json!({
+                "path": path,
+                "slot": snapshot.slot,
+                "accounts": snapshot.accounts.len(),
+                "tick_arrays": snapshot.tick_arrays().len(),
+            }))?;
+        }
+        Command::TickArrays { pool_id } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let (pool, tick_arrays) =
+                get_pool_tick_arrays(&rpc_client, &pool_config.raydium_v3_program, &pool_id)
+                    .await?;
+            let tick_arrays = tick_arrays
+                .iter()
+                .map(|(start_index, tick_array)| {
+                    json!({
+                        "start_index": start_index,
+                        "initialized_tick_count": tick_array.initialized_tick_count,
+                    })
+                })
+                .collect::<Vec<_>>();
+            output.write(&json!({
+                "tick_current": pool.tick_current,
+                "tick_arrays": tick_arrays,
+            }))?;
+        }
+        Command::Depth {
+            percent,
+            format,
+            snapshot,
+            pool_id,
+        } => {
+            let (cache, pool_id) = pool_cache(&pool_config, snapshot.as_deref(), pool_id)?;
+            let (pool, tick_arrays) = load_pool_tick_arrays(
+                &rpc_client,
+                &cache,
+                &pool_config.raydium_v3_program,
+                &pool_id,
+            )
+            .await?;
+            let bands = get_liquidity_depth(&pool, tick_arrays.values(), percent)?;
+            output.write_rendered_list(&bands, |bands| render_depth(bands, format))?;
+        }
+        Command::Quote {
+            amount_in,
+            input_token_1,
+            snapshot,
+            pool_id,
+        } => {
+            let (cache, pool_id) = pool_cache(&pool_config, snapshot.as_deref(), pool_id)?;
+            let (pool, tick_arrays) = load_pool_tick_arrays(
+                &rpc_client,
+                &cache,
+                &pool_config.raydium_v3_program,
+                &pool_id,
+            )
+            .await?;
+            let amm_config: AmmConfig = cache
+                .get_anchor_account(&rpc_client, &pool.amm_config)
+                .await?;
+            let quote = quote_exact_input(
+                &pool,
+                &tick_arrays,
+                amm_config.trade_fee_rate,
+                amount_in,
+                !input_token_1,
+            )?;
+            output.write(&quote)?;
+        }
+        Command::Twap { window, pool_id } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let twap = get_pool_twap(&rpc_client, &pool_id, window).await?;
+            output.write(&twap)?;
+        }
+        Command::Pools { mint_a, mint_b } => {
+            let pools = match mint_b {
+                Some(mint_b) => {
+                    find_pools_by_mints(
+                        &rpc_client,
+                        &pool_config.raydium_v3_program,
+                        &mint_a,
+                        &mint_b,
+                    )
+                    .await?
+                }
+                None => {
+                    find_pools_by_mint(&rpc_client, &pool_config.raydium_v3_program, &mint_a)
+                        .await?
+                }
+            };
+            output.write_list(&pools)?;
+        }
+        Command::Swap {
+            input_mint,
+            output_mint,
+            amount_in,
+            split,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let result = swap_tx(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                input_mint,
+                output_mint,
+                amount_in,
+                split,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::InitReward {
+            reward_mint,
+            emissions_per_second,
+            open_time,
+            end_time,
+            pool_id,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let result = initialize_reward_tx(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                pool_id,
+                reward_mint,
+                emissions_per_second,
+                open_time,
+                end_time,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::SetReward {
+            reward_index,
+            emissions_per_second,
+            open_time,
+            end_time,
+            pool_id,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let result = set_reward_params_tx(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                pool_id,
+                reward_index,
+                emissions_per_second,
+                open_time,
+                end_time,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::UpdateRewards { pool_id } => {
+            let payer = read_payer(&pool_config)?;
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let transaction =
+                update_reward_infos_tx(&rpc_client, &pool_config, &payer, pool_id).await?;
+            output.write(&transaction)?;
+        }
+        Command::Rewards { pool_id } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let (_, rewards) = get_pool_rewards(&rpc_client, &pool_id).await?;
+            output.write_list(&rewards)?;
+        }
+        Command::CollectFees {
+            kind,
+            pool_id,
+            amm_config,
+            all,
+            dry_run,
+        } => {
+            let scope = FeeScope::new(pool_id, amm_config, all, || pool_config.pool_id())?;
+            let admin = read_admin(&pool_config)?;
+            let result =
+                collect_fees_tx(&rpc_client, &pool_config, &admin, kind, scope, dry_run).await?;
+            output.write(&result)?;
+        }
+        Command::PoolStatus { pool_id } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let status = get_pool_status(&rpc_client, &pool_id).await?;
+            output.write(&status)?;
+        }
+        Command::SetPoolStatus {
+            enable,
+            disable,
+            pool_id,
+        } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let admin = read_admin(&pool_config)?;
+            let result = update_pool_status_tx(
+                &rpc_client,
+                &pool_config,
+                &admin,
+                pool_id,
+                &enable,
+                &disable,
+            )
+            .await?;
+            output.write(&result)?;
+        }
+        Command::Operation => {
+            let operation_lists =
+                get_operation_lists(&rpc_client, &pool_config.raydium_v3_program).await?;
+            if operation_lists.is_none() {
+                log::warn!("operation account not created");
+            }
+            output.write(&operation_lists)?;
+        }
+        Command::UpdateOperation { update, keys } => {
+            let admin = read_admin(&pool_config)?;
+            let transaction =
+                update_operation_account_tx(&rpc_client, &pool_config, &admin, update, keys)
+                    .await?;
+            output.write(&transaction)?;
+        }
+        Command::Rebalance {
+            nft_mints,
+            percent,
+            ticks,
+            drift_threshold,
+            cooldown,
+            interval,
+            max_slippage,
+            max_cost_ratio,
+            fee_horizon,
+            db,
+            sample_secs,
+            sol_price,
+            min_sol_balance,
+            dry_run,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let config = RebalanceConfig {
+                strategy: match ticks {
+                    Some(ticks) => RangeStrategy::Ticks(ticks),
+                    None => RangeStrategy::Percent(percent),
+                },
+                drift_threshold,
+                cooldown: Duration::from_secs(cooldown),
+                interval: Duration::from_secs(interval),
+                max_slippage: max_slippage.unwrap_or(pool_config.slippage),
+                max_cost_ratio,
+                fee_horizon: Duration::from_secs(fee_horizon),
+                fee_source: match db {
+                    Some(db) => StatsSource::Indexed(db),
+                    None => StatsSource::Sampled(Duration::from_secs(sample_secs)),
+                },
+                sol_price,
+                min_sol_balance,
+                dry_run,
+            };
+            run_rebalancer(
+                &rpc_client,
+                &pool_config,
+                &payer,
+                nft_mints,
+                &config,
+                |outcome| output.write(outcome),
+            )
+            .await?;
+        }
+        Command::Watch {
+            tick_arrays,
+            pool_id,
+        } => {
+            let payer = read_payer(&pool_config)?;
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            watch_pool(
+                &rpc_client,
+                &pool_config.ws_url,
+                &pool_config.raydium_v3_program,
+                pool_id,
+                Some(&payer.pubkey()),
+                tick_arrays,
+                |event| output.write(event),
+            )
+            .await?;
+        }
+        Command::Index {
+            db,
+            depth,
+            dump,
+            replay,
+            pool_id,
+        } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let mut indexer = Indexer::open(&db, pool_config.raydium_v3_program, pool_id)?;
+            let indexed = match replay {
+                Some(replay) => indexer.replay(&replay)?,
+                None => indexer.index(&rpc_client, depth, dump.as_deref()).await?,
+            };
+            let checkpoint = indexer
+                .checkpoint()?
+                .map(|checkpoint| checkpoint.to_string());
+            output.write(&json!({ "indexed": indexed, "checkpoint": checkpoint }))?;
+        }
+        Command::Pnl {
+            nft_mints,
+            quote_mint,
+            entry_price,
+            entry_amount_0,
+            entry_amount_1,
+            entry_time,
+            owner,
+        } => {
+            let nft_mints = if nft_mints.is_empty() {
+                let owner = match owner {
+                    Some(owner) => owner,
+                    None => read_payer(&pool_config)?.pubkey(),
+                };
+                get_owner_position_mints(&rpc_client, &owner, &pool_config.raydium_v3_program)
+                    .await?
+            } else {
+                nft_mints
+            };
+            let ledger = match (entry_price, entry_amount_0, entry_amount_1) {
+                (Some(price), Some(amount_0), Some(amount_1)) => {
+                    if nft_mints.len() != 1 {
+                        return Err(anyhow!("an entry price needs exactly one position"));
+                    }
+                    Some(PositionLedger::from_entry(
+                        amount_0, amount_1, price, entry_time,
+                    ))
+                }
+                _ => None,
+            };
+            let mut pnls = Vec::with_capacity(nft_mints.len());
+            for nft_mint in nft_mints {
+                let pnl = get_position_pnl(
+                    &rpc_client,
+                    &pool_config.raydium_v3_program,
+                    nft_mint,
+                    ledger.clone(),
+                    quote_mint,
+                )
+                .await?;
+                pnls.push(pnl);
+            }
+            output.write(&PnlReport {
+                totals: aggregate_pnl(&pnls),
+                positions: pnls,
+            })?;
+        }
+        Command::Backtest {
+            source,
+            amount_0,
+            amount_1,
+            strategy,
+            percent,
+            ticks,
+            drift_threshold,
+            multiplier,
+            window,
+            equity_curve,
+            tick_spacing,
+            trade_fee_rate,
+            protocol_fee_rate,
+            fund_fee_rate,
+            pool_id,
+        } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let swaps = if source.ends_with(".csv") {
+                load_swaps_csv(&source)?
+            } else {
+                load_swaps(&source, &pool_id)?
+            };
+            let params = PoolParams {
+                tick_spacing,
+                trade_fee_rate,
+                protocol_fee_rate,
+                fund_fee_rate,
+            };
+            let config =
+                get_backtest_config(&rpc_client, &pool_id, params, amount_0, amount_1).await?;
+            let range_strategy = match ticks {
+                Some(ticks) => RangeStrategy::Ticks(ticks),
+                None => RangeStrategy::Percent(percent),
+            };
+            let mut strategy: Box<dyn Strategy> = match strategy {
+                StrategyKind::Fixed => Box::new(FixedRange {
+                    strategy: range_strategy,
+                }),
+                StrategyKind::Exit => Box::new(RebalanceOnExit {
+                    strategy: range_strategy,
+                    drift_threshold,
+                }),
+                StrategyKind::Volatility => Box::new(VolatilityScaled::new(multiplier, window)?),
+            };
+            let report = run_backtest(&swaps, strategy.as_mut(), &config)?;
+            output.write(&report)?;
+            if let Some(equity_curve) = equity_curve {
+                write_equity_curve(&equity_curve, &report.equity_curve)?;
+            }
+        }
+        Command::PoolStats {
+            db,
+            sample_secs,
+            range_width,
+            pool_id,
+        } => {
+            let pool_id = match pool_id {
+                Some(pool_id) => pool_id,
+                None => pool_config.pool_id()?,
+            };
+            let source = match db {
+                Some(db) => StatsSource::Indexed(db),
+                None => StatsSource::Sampled(Duration::from_secs(sample_secs)),
+            };
+            let stats = get_pool_stats(&rpc_client, &pool_id, &source, range_width).await?;
+            output.write(&stats)?;
+        }
     }
 
-    let payer = read_keypair_file(&*shellexpand::tilde(&pool_config.payer_path))
-        .map_err(|_| anyhow!("failed in getting payer"))?;
-
-    // for price 1.5, kept these tick values.
-    // `$ target/debug/client price-to-tick 1.5`
-    // price:1.5, tick:4054
-    let tick_lower_price = 4001.0;
-    let tick_upper_price = 4101.0;
-    let is_base_0 = true;
-    let input_amount = 100;
-    increase_liquidity_tx(
-        ray_program,
-        rpc_client,
-        pool_config,
-        payer,
-        tick_lower_price,
-        tick_upper_price,
-        is_base_0,
-        input_amount,
-    )
-    .await?;
-
     Ok(())
 }
@@ -0,0 +1,191 @@
+use crate::client::{deserialize_anchor_account, get_accounts, get_anchor_account};
+use crate::error::{ClientError, Result};
+use crate::utils::multipler;
+use raydium_amm_v3::states::{ObservationState, PoolState};
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::from_account;
+use solana_sdk::clock::Clock;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar;
+
+/// Time weighted average over `[start_time, end_time]`, in unix seconds.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Twap {
+    pub start_time: u32,
+    pub end_time: u32,
+    pub tick: f64,
+    /// UI price of token0 in token1.
+    pub price: f64,
+    /// Seconds between the latest observation and `end_time`.
+    pub staleness: u32,
+}
+
+/// Recorded observations, oldest first, one per timestamp.
+pub(crate) fn get_observations(observation_state: &ObservationState) -> Vec<(u32, i64)> {
+    // copied out of the packed account
+    let observations = observation_state.observations;
+    let mut points = observations
+        .iter()
+        .filter(|observation| observation.block_timestamp != 0)
+        .map(|observation| (observation.block_timestamp, observation.tick_cumulative))
+        .collect::<Vec<(u32, i64)>>();
+    points.sort_by_key(|(block_timestamp, _)| *block_timestamp);
+    points.dedup_by_key(|(block_timestamp, _)| *block_timestamp);
+    points
+}
+
+/// Tick cumulative at `time`, interpolated between the surrounding observations, or
+/// extrapolated with `tick_current` after the latest one.
+fn get_tick_cumulative(points: &[(u32, i64)], tick_current: i32, time: u32) -> Result<f64> {
+    let (oldest_time, _) = points.first().ok_or_else(|| {
+        ClientError::InvalidArgument("the pool has no observation yet".to_string())
+    })?;
+    if time < *oldest_time {
+        return Err(ClientError::InvalidArgument(format!(
+            "no observation before {}, the oldest is at {}",
+            time, oldest_time
+        )));
+    }
+    let after = points.partition_point(|(block_timestamp, _)| *block_timestamp <= time);
+    let (before_time, before_tick_cumulative) = points[after - 1];
+    let tick_cumulative = match points.get(after) {
+        Some((after_time, after_tick_cumulative)) => {
+            // the tick is constant between two observations
+            before_tick_cumulative as f64
+                + (after_tick_cumulative - before_tick_cumulative) as f64
+                    * f64::from(time - before_time)
+                    / f64::from(after_time - before_time)
+        }
+        None => {
+            before_tick_cumulative as f64 + f64::from(tick_current) * f64::from(time - before_time)
+        }
+    };
+    Ok(tick_cumulative)
+}
+
+/// Time weighted average tick and price over the `window` seconds before `now`. The pool's
+/// `tick_current` covers the time since its latest observation.
+pub fn get_twap(
+    observation_state: &ObservationState,
+    pool_state: &PoolState,
+    window: u32,
+    now: u32,
+) -> Result<Twap> {
+    if window == 0 {
+        return Err(ClientError::InvalidArgument(
+            "window must be positive".to_string(),
+        ));
+    }
+    let points = get_observations(observation_state);
+    let start_time = now.checked_sub(window).ok_or_else(|| {
+        ClientError::InvalidArgument(format!("window {} is before the epoch", window))
+    })?;
+    let tick_current = pool_state.tick_current;
+    let tick = (get_tick_cumulative(&points, tick_current, now)?
+        - get_tick_cumulative(&points, tick_current, start_time)?)
+        / f64::from(window);
+    let latest_time = points.last().map(|(block_timestamp, _)| *block_timestamp);
+    Ok(Twap {
+        start_time,
+        end_time: now,
+        tick,
+        price: 1.0001_f64.powf(tick) * multipler(pool_state.mint_decimals_0)
+            / multipler(pool_state.mint_decimals_1),
+        staleness: now.saturating_sub(latest_time.unwrap_or_default()),
+    })
+}
+
+/// Load a pool with its observations and compute [`get_twap`] up to the cluster time.
+pub async fn get_pool_twap(rpc_client: &RpcClient, pool_id: &Pubkey, window: u32) -> Result<Twap> {
+    let pool_state = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    let accounts = get_accounts(
+        rpc_client,
+        &[pool_state.observation_key, sysvar::clock::id()],
+    )
+    .await?;
+    let observation_state = deserialize_anchor_account::<ObservationState>(
+        accounts[0]
+            .as_ref()
+            .ok_or(ClientError::AccountNotFound(pool_state.observation_key))?,
+    )?;
+    let clock = accounts[1]
+        .as_ref()
+        .and_then(from_account::<Clock, _>)
+        .ok_or_else(|| ClientError::InvalidResponse("undecodable clock sysvar".to_string()))?;
+    let now = u32::try_from(clock.unix_timestamp)
+        .map_err(|err| ClientError::InvalidResponse(format!("cluster time: {}", err)))?;
+    get_twap(&observation_state, &pool_state, window, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_amm_v3::states::Observation;
+
+    fn observation_state(points: &[(u32, i64)]) -> ObservationState {
+        let mut observation_state = ObservationState::default();
+        // copied out of the packed account
+        let mut observations = observation_state.observations;
+        for (observation, (block_timestamp, tick_cumulative)) in observations.iter_mut().zip(points)
+        {
+            *observation = Observation {
+                block_timestamp: *block_timestamp,
+                tick_cumulative: *tick_cumulative,
+                ..Default::default()
+            };
+        }
+        observation_state.observations = observations;
+        observation_state
+    }
+
+    fn pool_state(tick_current: i32) -> PoolState {
+        PoolState {
+            tick_current,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn observations_are_sorted_without_empty_slots() {
+        // the ring buffer wrapped: the newest observation is first
+        let observation_state =
+            observation_state(&[(300, 3_000), (100, 0), (200, 1_000), (200, 1_000)]);
+        assert_eq!(
+            get_observations(&observation_state),
+            vec![(100, 0), (200, 1_000), (300, 3_000)]
+        );
+    }
+
+    #[test]
+    fn twap_interpolates_between_observations() {
+        // tick 10 over [100, 200], then 20 over [200, 300]
+        let observation_state = observation_state(&[(100, 0), (200, 1_000), (300, 3_000)]);
+        let twap = get_twap(&observation_state, &pool_state(30), 40, 180).unwrap();
+        assert_eq!((twap.start_time, twap.end_time), (140, 180));
+        assert!((twap.tick - 10.0).abs() < 1e-9);
+        // 150..250 is half at tick 10 and half at tick 20
+        let twap = get_twap(&observation_state, &pool_state(30), 100, 250).unwrap();
+        assert!((twap.tick - 15.0).abs() < 1e-9);
+        assert_eq!(twap.staleness, 0);
+    }
+
+    #[test]
+    fn twap_extrapolates_with_the_current_tick() {
+        let observation_state = observation_state(&[(100, 0), (200, 1_000), (300, 3_000)]);
+        // 250..300 at tick 20, then 300..350 at the current tick 30
+        let twap = get_twap(&observation_state, &pool_state(30), 100, 350).unwrap();
+        assert!((twap.tick - 25.0).abs() < 1e-9);
+        assert!((twap.price - 1.0001_f64.powf(25.0)).abs() < 1e-12);
+        assert_eq!(twap.staleness, 50);
+    }
+
+    #[test]
+    fn twap_needs_observations_covering_the_window() {
+        let observation_state = observation_state(&[(100, 0), (200, 1_000)]);
+        assert!(get_twap(&observation_state, &pool_state(0), 0, 200).is_err());
+        assert!(get_twap(&observation_state, &pool_state(0), 100, 150).is_err());
+        assert!(get_twap(&observation_state, &pool_state(0), 300, 200).is_err());
+        assert!(get_twap(&ObservationState::default(), &pool_state(0), 10, 200).is_err());
+    }
+}
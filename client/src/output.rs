@@ -0,0 +1,148 @@
+use crate::error::{ClientError, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+use std::str::FromStr;
+
+/// How command results are written to stdout. Logs go to stderr whatever the format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `key:value` lines, nested objects and lists on lines of their own prefixed with their
+    /// key.
+    Text,
+    /// One pretty printed JSON document per result.
+    Json,
+    /// One compact JSON document per line, lists being written one item per line.
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ClientError;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => Err(ClientError::InvalidArgument(format!(
+                "unknown output format {}, expected text, json or ndjson",
+                format
+            ))),
+        }
+    }
+}
+
+/// Writes command results to stdout in one format.
+#[derive(Clone, Copy, Debug)]
+pub struct Output {
+    format: OutputFormat,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Output { format }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Write one result.
+    pub fn write<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        match self.format {
+            OutputFormat::Text => {
+                let value = serde_json::to_value(value)
+                    .map_err(|err| ClientError::Output(err.to_string()))?;
+                let mut lines = Vec::new();
+                text_lines(&mut lines, None, &value);
+                for line in lines {
+                    writeln!(stdout, "{}", line)?;
+                }
+            }
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut stdout, value)
+                    .map_err(|err| ClientError::Output(err.to_string()))?;
+                writeln!(stdout)?;
+            }
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut stdout, value)
+                    .map_err(|err| ClientError::Output(err.to_string()))?;
+                writeln!(stdout)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a list of results, as one JSON array or one item per line.
+    pub fn write_list<T: Serialize>(&self, values: &[T]) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => self.write(values),
+            OutputFormat::Text | OutputFormat::Ndjson => {
+                values.iter().try_for_each(|value| self.write(value))
+            }
+        }
+    }
+
+    /// Write a list of results, in text format as `render` draws them, e.g. as a table or a
+    /// chart, instead of their `key:value` lines.
+    pub fn write_rendered_list<T: Serialize>(
+        &self,
+        values: &[T],
+        render: impl FnOnce(&[T]) -> Result<String>,
+    ) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => {
+                write!(std::io::stdout().lock(), "{}", render(values)?)?;
+                Ok(())
+            }
+            OutputFormat::Json | OutputFormat::Ndjson => self.write_list(values),
+        }
+    }
+}
+
+/// `value` on one line when it is a scalar or a list of them, `None` for objects.
+fn inline_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some("none".to_string()),
+        Value::String(text) => Some(text.clone()),
+        Value::Bool(_) | Value::Number(_) => Some(value.to_string()),
+        Value::Array(items) => items
+            .iter()
+            .map(inline_text)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| format!("[{}]", items.join(", "))),
+        Value::Object(_) => None,
+    }
+}
+
+fn text_lines(lines: &mut Vec<String>, label: Option<&str>, value: &Value) {
+    let line = |text: String| match label {
+        Some(label) => format!("{} {}", label, text),
+        None => text,
+    };
+    match value {
+        Value::Object(fields) => {
+            let mut inline = Vec::new();
+            let mut nested = Vec::new();
+            for (key, field) in fields {
+                match inline_text(field) {
+                    Some(text) => inline.push(format!("{}:{}", key, text)),
+                    None => nested.push((key, field)),
+                }
+            }
+            if !inline.is_empty() {
+                lines.push(line(inline.join(", ")));
+            }
+            for (key, field) in nested {
+                text_lines(lines, Some(key.as_str()), field);
+            }
+        }
+        Value::Array(items) if inline_text(value).is_none() => {
+            for item in items {
+                text_lines(lines, label, item);
+            }
+        }
+        value => lines.extend(inline_text(value).map(line)),
+    }
+}
@@ -0,0 +1,129 @@
+use raydium_amm_v3::states::{
+    AMM_CONFIG_SEED, OBSERVATION_SEED, OPERATION_SEED, POOL_REWARD_VAULT_SEED, POOL_SEED,
+    POOL_TICK_ARRAY_BITMAP_SEED, POOL_VAULT_SEED, POSITION_SEED, TICK_ARRAY_SEED,
+};
+use solana_sdk::pubkey::Pubkey;
+
+pub fn get_amm_config_key(raydium_v3_program: &Pubkey, amm_config_index: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[AMM_CONFIG_SEED.as_bytes(), &amm_config_index.to_be_bytes()],
+        raydium_v3_program,
+    )
+    .0
+}
+
+/// `token_mint_0` must sort before `token_mint_1`.
+pub fn get_pool_key(
+    raydium_v3_program: &Pubkey,
+    amm_config: &Pubkey,
+    token_mint_0: &Pubkey,
+    token_mint_1: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_SEED.as_bytes(),
+            amm_config.to_bytes().as_ref(),
+            token_mint_0.to_bytes().as_ref(),
+            token_mint_1.to_bytes().as_ref(),
+        ],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_pool_vault_key(
+    raydium_v3_program: &Pubkey,
+    pool: &Pubkey,
+    token_mint: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_VAULT_SEED.as_bytes(),
+            pool.to_bytes().as_ref(),
+            token_mint.to_bytes().as_ref(),
+        ],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_pool_reward_vault_key(
+    raydium_v3_program: &Pubkey,
+    pool: &Pubkey,
+    reward_mint: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_REWARD_VAULT_SEED.as_bytes(),
+            pool.to_bytes().as_ref(),
+            reward_mint.to_bytes().as_ref(),
+        ],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_operation_key(raydium_v3_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[OPERATION_SEED.as_bytes()], raydium_v3_program).0
+}
+
+pub fn get_observation_key(raydium_v3_program: &Pubkey, pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[OBSERVATION_SEED.as_bytes(), pool.to_bytes().as_ref()],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_tickarray_bitmap_extension_key(raydium_v3_program: &Pubkey, pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_TICK_ARRAY_BITMAP_SEED.as_bytes(),
+            pool.to_bytes().as_ref(),
+        ],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_tick_array_key(
+    raydium_v3_program: &Pubkey,
+    pool: &Pubkey,
+    tick_array_start_index: i32,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED.as_bytes(),
+            pool.to_bytes().as_ref(),
+            &tick_array_start_index.to_be_bytes(),
+        ],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_protocol_position_key(
+    raydium_v3_program: &Pubkey,
+    pool: &Pubkey,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POSITION_SEED.as_bytes(),
+            pool.to_bytes().as_ref(),
+            &tick_lower_index.to_be_bytes(),
+            &tick_upper_index.to_be_bytes(),
+        ],
+        raydium_v3_program,
+    )
+    .0
+}
+
+pub fn get_personal_position_key(raydium_v3_program: &Pubkey, nft_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[POSITION_SEED.as_bytes(), nft_mint.to_bytes().as_ref()],
+        raydium_v3_program,
+    )
+    .0
+}
@@ -0,0 +1,782 @@
+use crate::client::{
+    get_anchor_account, get_anchor_accounts, get_nft_account_and_position_by_owner,
+};
+use crate::error::{ClientError, Result};
+use crate::events::ClmmEvent;
+use crate::indexer::{decode_transaction, get_signatures, get_transaction};
+use crate::pda::{get_personal_position_key, get_tick_array_key};
+use crate::utils::{multipler, serialize_pubkey, sqrt_price_x64_to_f64};
+use raydium_amm_v3::libraries::{fixed_point_64, liquidity_math, tick_math};
+use raydium_amm_v3::states::{
+    PersonalPositionState, PoolState, TickArrayState, TickState, REWARD_NUM,
+};
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+/// Tokens moved in or out of a position, in raw amounts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flow {
+    pub amount_0: u64,
+    pub amount_1: u64,
+    /// Raw price of token0 in token1 at the time, unknown when a single token moved: the
+    /// price was then anywhere out of the range.
+    pub price: Option<f64>,
+    /// Unix timestamp, when known.
+    pub time: Option<i64>,
+}
+
+/// Deposits, withdrawals and collected fees and rewards of a position.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PositionLedger {
+    pub deposits: Vec<Flow>,
+    pub withdrawals: Vec<Flow>,
+    pub collected_fees_0: u64,
+    pub collected_fees_1: u64,
+    /// By reward index of the pool.
+    pub collected_rewards: [u64; REWARD_NUM],
+}
+
+impl PositionLedger {
+    /// A single deposit of `amount_0` and `amount_1` at the raw `price`.
+    pub fn from_entry(amount_0: u64, amount_1: u64, price: f64, time: Option<i64>) -> Self {
+        Self {
+            deposits: vec![Flow {
+                amount_0,
+                amount_1,
+                price: Some(price),
+                time,
+            }],
+            ..Self::default()
+        }
+    }
+
+    /// Replay the events of the transactions of `position`, oldest first with their block
+    /// time. The price of each deposit and withdrawal of both tokens is recovered from its
+    /// amounts and liquidity.
+    pub fn from_events(
+        position: &PersonalPositionState,
+        events: &[(Option<i64>, ClmmEvent)],
+    ) -> Result<Self> {
+        let mut ledger = Self::default();
+        for (time, event) in events {
+            match event {
+                ClmmEvent::CreatePersonalPosition(event)
+                    if event.pool_state == position.pool_id
+                        && event.tick_lower_index == position.tick_lower_index
+                        && event.tick_upper_index == position.tick_upper_index =>
+                {
+                    ledger.deposits.push(Flow {
+                        amount_0: event.deposit_amount_0,
+                        amount_1: event.deposit_amount_1,
+                        price: flow_price(
+                            position,
+                            event.liquidity,
+                            event.deposit_amount_0,
+                            event.deposit_amount_1,
+                        )?,
+                        time: *time,
+                    });
+                }
+                ClmmEvent::IncreaseLiquidity(event)
+                    if event.position_nft_mint == position.nft_mint =>
+                {
+                    ledger.deposits.push(Flow {
+                        amount_0: event.amount_0,
+                        amount_1: event.amount_1,
+                        price: flow_price(
+                            position,
+                            event.liquidity,
+                            event.amount_0,
+                            event.amount_1,
+                        )?,
+                        time: *time,
+                    });
+                }
+                // fees and rewards are collected on every decrease, even of no liquidity
+                ClmmEvent::DecreaseLiquidity(event)
+                    if event.position_nft_mint == position.nft_mint =>
+                {
+                    if event.liquidity > 0 {
+                        ledger.withdrawals.push(Flow {
+                            amount_0: event.decrease_amount_0,
+                            amount_1: event.decrease_amount_1,
+                            price: flow_price(
+                                position,
+                                event.liquidity,
+                                event.decrease_amount_0,
+                                event.decrease_amount_1,
+                            )?,
+                            time: *time,
+                        });
+                    }
+                    ledger.collected_fees_0 += event.fee_amount_0;
+                    ledger.collected_fees_1 += event.fee_amount_1;
+                    for (collected, amount) in ledger
+                        .collected_rewards
+                        .iter_mut()
+                        .zip(event.reward_amounts)
+                    {
+                        *collected += amount;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(ledger)
+    }
+
+    fn first_deposit_time(&self) -> Option<i64> {
+        self.deposits.iter().filter_map(|flow| flow.time).min()
+    }
+}
+
+/// Raw price at which `liquidity` moved in or out of the range of `position` with `amount_0`
+/// and `amount_1`, from `amount_1 = liquidity * (sqrt_price - sqrt_price_lower)`. Unknown
+/// unless both tokens moved, i.e. the price was in the range.
+fn flow_price(
+    position: &PersonalPositionState,
+    liquidity: u128,
+    amount_0: u64,
+    amount_1: u64,
+) -> Result<Option<f64>> {
+    if liquidity == 0 || amount_0 == 0 || amount_1 == 0 {
+        return Ok(None);
+    }
+    let sqrt_price_lower = sqrt_price_x64_to_f64(tick_math::get_sqrt_price_at_tick(
+        position.tick_lower_index,
+    )?);
+    let sqrt_price_upper = sqrt_price_x64_to_f64(tick_math::get_sqrt_price_at_tick(
+        position.tick_upper_index,
+    )?);
+    let sqrt_price = (sqrt_price_lower + amount_1 as f64 / liquidity as f64).min(sqrt_price_upper);
+    Ok(Some(sqrt_price * sqrt_price))
+}
+
+/// Growth of a global accumulator within `[tick_lower, tick_upper)`, from its growth outside
+/// of each tick.
+fn growth_inside(
+    tick_current: i32,
+    tick_lower: i32,
+    growth_outside_lower: u128,
+    tick_upper: i32,
+    growth_outside_upper: u128,
+    growth_global: u128,
+) -> u128 {
+    let growth_below = if tick_current >= tick_lower {
+        growth_outside_lower
+    } else {
+        growth_global.wrapping_sub(growth_outside_lower)
+    };
+    let growth_above = if tick_current < tick_upper {
+        growth_outside_upper
+    } else {
+        growth_global.wrapping_sub(growth_outside_upper)
+    };
+    growth_global
+        .wrapping_sub(growth_below)
+        .wrapping_sub(growth_above)
+}
+
+/// Tokens `liquidity` earned since the growth inside its range was `growth_inside_last_x64`.
+fn earned(growth_inside_x64: u128, growth_inside_last_x64: u128, liquidity: u128) -> u64 {
+    (growth_inside_x64.wrapping_sub(growth_inside_last_x64) as f64 * liquidity as f64
+        / fixed_point_64::Q64 as f64) as u64
+}
+
+/// Value of a position against holding what went into it, in UI units of `quote_mint`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PositionPnl {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub quote_mint: Pubkey,
+    pub in_range: bool,
+    /// Raw amounts the position holds.
+    pub amount_0: u64,
+    pub amount_1: u64,
+    /// Raw fees and rewards (by reward index) owed to the position and not collected yet.
+    pub uncollected_fees_0: u64,
+    pub uncollected_fees_1: u64,
+    pub uncollected_rewards: [u64; REWARD_NUM],
+    /// Deposits and withdrawals at their own price.
+    pub deposited_value: f64,
+    pub withdrawn_value: f64,
+    /// Deposits and withdrawals of the other mint than `quote_mint` at an unknown price,
+    /// included in `deposited_value` and `withdrawn_value` at the current price.
+    pub unpriced_flows: usize,
+    pub position_value: f64,
+    /// Deposits less withdrawals, held instead, at the current price.
+    pub hodl_value: f64,
+    /// `position_value / hodl_value - 1`, fees and rewards excluded.
+    pub impermanent_loss: f64,
+    /// Collected and uncollected fees at the current price.
+    pub fees_value: f64,
+    /// Collected and uncollected rewards paid in a mint of the pool, at the current price.
+    pub rewards_value: f64,
+    /// Position, withdrawals, fees and rewards less deposits.
+    pub net_pnl: f64,
+    /// Fees over deposits, annualized since the first deposit.
+    pub fee_apr: Option<f64>,
+}
+
+/// Value `position` at the current price of its pool, in `quote_mint` which must be one of
+/// the pool mints. `tick_lower` and `tick_upper` are the states of the position's ticks, for
+/// the fees and rewards earned since the position was last updated.
+pub fn compute_position_pnl(
+    pool_id: Pubkey,
+    pool_state: &PoolState,
+    position: &PersonalPositionState,
+    ledger: &PositionLedger,
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+    quote_mint: Pubkey,
+    now: i64,
+) -> Result<PositionPnl> {
+    let quote_is_token_0 = if quote_mint == pool_state.token_mint_0 {
+        true
+    } else if quote_mint == pool_state.token_mint_1 {
+        false
+    } else {
+        return Err(ClientError::InvalidArgument(format!(
+            "quote mint {} is not a mint of pool {}",
+            quote_mint, pool_id
+        )));
+    };
+    let quote_multiplier = multipler(if quote_is_token_0 {
+        pool_state.mint_decimals_0
+    } else {
+        pool_state.mint_decimals_1
+    });
+    let value = |amount_0: f64, amount_1: f64, price: f64| {
+        if quote_is_token_0 {
+            (amount_0 + amount_1 / price) / quote_multiplier
+        } else {
+            (amount_0 * price + amount_1) / quote_multiplier
+        }
+    };
+    let sqrt_price = sqrt_price_x64_to_f64(pool_state.sqrt_price_x64);
+    let price = sqrt_price * sqrt_price;
+
+    let (amount_0, amount_1) = liquidity_math::get_delta_amounts_signed(
+        pool_state.tick_current,
+        pool_state.sqrt_price_x64,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        -(position.liquidity as i128),
+    )?;
+    let growth_inside = |growth_outside_lower: u128, growth_outside_upper: u128, global: u128| {
+        growth_inside(
+            pool_state.tick_current,
+            tick_lower.tick,
+            growth_outside_lower,
+            tick_upper.tick,
+            growth_outside_upper,
+            global,
+        )
+    };
+    let uncollected_fees_0 = position.token_fees_owed_0
+        + earned(
+            growth_inside(
+                tick_lower.fee_growth_outside_0_x64,
+                tick_upper.fee_growth_outside_0_x64,
+                pool_state.fee_growth_global_0_x64,
+            ),
+            position.fee_growth_inside_0_last_x64,
+            position.liquidity,
+        );
+    let uncollected_fees_1 = position.token_fees_owed_1
+        + earned(
+            growth_inside(
+                tick_lower.fee_growth_outside_1_x64,
+                tick_upper.fee_growth_outside_1_x64,
+                pool_state.fee_growth_global_1_x64,
+            ),
+            position.fee_growth_inside_1_last_x64,
+            position.liquidity,
+        );
+    // copied out of the packed accounts
+    let reward_infos = pool_state.reward_infos;
+    let position_reward_infos = position.reward_infos;
+    let (reward_growths_lower, reward_growths_upper) = (
+        tick_lower.reward_growths_outside_x64,
+        tick_upper.reward_growths_outside_x64,
+    );
+    let mut uncollected_rewards = [0; REWARD_NUM];
+    let mut rewards_value = 0.0;
+    for (reward_index, reward_info) in reward_infos.iter().enumerate() {
+        if !reward_info.initialized() {
+            continue;
+        }
+        let position_reward_info = &position_reward_infos[reward_index];
+        uncollected_rewards[reward_index] = position_reward_info.reward_amount_owed
+            + earned(
+                growth_inside(
+                    reward_growths_lower[reward_index],
+                    reward_growths_upper[reward_index],
+                    reward_info.reward_growth_global_x64,
+                ),
+                position_reward_info.growth_inside_last_x64,
+                position.liquidity,
+            );
+        let amount =
+            (ledger.collected_rewards[reward_index] + uncollected_rewards[reward_index]) as f64;
+        if reward_info.token_mint == pool_state.token_mint_0 {
+            rewards_value += value(amount, 0.0, price);
+        } else if reward_info.token_mint == pool_state.token_mint_1 {
+            rewards_value += value(0.0, amount, price);
+        }
+    }
+
+    // a flow of the quote mint alone is worth the same at any price
+    let is_unpriced = |flow: &Flow| {
+        flow.price.is_none()
+            && if quote_is_token_0 {
+                flow.amount_1 > 0
+            } else {
+                flow.amount_0 > 0
+            }
+    };
+    let flow_value = |flow: &Flow| {
+        value(
+            flow.amount_0 as f64,
+            flow.amount_1 as f64,
+            flow.price.unwrap_or(price),
+        )
+    };
+    let deposited_value = ledger.deposits.iter().map(flow_value).sum::<f64>();
+    let withdrawn_value = ledger.withdrawals.iter().map(flow_value).sum::<f64>();
+    let unpriced_flows = ledger
+        .deposits
+        .iter()
+        .chain(&ledger.withdrawals)
+        .filter(|flow| is_unpriced(flow))
+        .count();
+    let (held_0, held_1) = ledger
+        .deposits
+        .iter()
+        .fold((0.0, 0.0), |(held_0, held_1), flow| {
+            (held_0 + flow.amount_0 as f64, held_1 + flow.amount_1 as f64)
+        });
+    let (held_0, held_1) = ledger
+        .withdrawals
+        .iter()
+        .fold((held_0, held_1), |(held_0, held_1), flow| {
+            (held_0 - flow.amount_0 as f64, held_1 - flow.amount_1 as f64)
+        });
+    let position_value = value(amount_0 as f64, amount_1 as f64, price);
+    let hodl_value = value(held_0, held_1, price);
+    let impermanent_loss = if hodl_value > 0.0 {
+        position_value / hodl_value - 1.0
+    } else {
+        0.0
+    };
+    let fees_value = value(
+        (ledger.collected_fees_0 + uncollected_fees_0) as f64,
+        (ledger.collected_fees_1 + uncollected_fees_1) as f64,
+        price,
+    );
+    let fee_apr = ledger
+        .first_deposit_time()
+        .map(|time| now - time)
+        .filter(|elapsed| *elapsed > 0 && deposited_value > 0.0)
+        .map(|elapsed| fees_value / deposited_value * SECONDS_PER_YEAR / elapsed as f64);
+
+    Ok(PositionPnl {
+        nft_mint: position.nft_mint,
+        pool_id,
+        quote_mint,
+        in_range: position.tick_lower_index <= pool_state.tick_current
+            && pool_state.tick_current < position.tick_upper_index,
+        amount_0,
+        amount_1,
+        uncollected_fees_0,
+        uncollected_fees_1,
+        uncollected_rewards,
+        deposited_value,
+        withdrawn_value,
+        unpriced_flows,
+        position_value,
+        hodl_value,
+        impermanent_loss,
+        fees_value,
+        rewards_value,
+        net_pnl: position_value + withdrawn_value + fees_value + rewards_value - deposited_value,
+        fee_apr,
+    })
+}
+
+/// Totals of the positions valued in the same quote mint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PnlSummary {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub quote_mint: Pubkey,
+    pub positions: usize,
+    pub deposited_value: f64,
+    pub withdrawn_value: f64,
+    pub unpriced_flows: usize,
+    pub position_value: f64,
+    pub hodl_value: f64,
+    pub impermanent_loss: f64,
+    pub fees_value: f64,
+    pub rewards_value: f64,
+    pub net_pnl: f64,
+}
+
+/// Positions valued together, with their totals by quote mint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PnlReport {
+    pub positions: Vec<PositionPnl>,
+    pub totals: Vec<PnlSummary>,
+}
+
+/// Sum `pnls` by quote mint.
+pub fn aggregate_pnl(pnls: &[PositionPnl]) -> Vec<PnlSummary> {
+    let mut summaries = BTreeMap::<Pubkey, PnlSummary>::new();
+    for pnl in pnls {
+        let summary = summaries
+            .entry(pnl.quote_mint)
+            .or_insert_with(|| PnlSummary {
+                quote_mint: pnl.quote_mint,
+                positions: 0,
+                deposited_value: 0.0,
+                withdrawn_value: 0.0,
+                unpriced_flows: 0,
+                position_value: 0.0,
+                hodl_value: 0.0,
+                impermanent_loss: 0.0,
+                fees_value: 0.0,
+                rewards_value: 0.0,
+                net_pnl: 0.0,
+            });
+        summary.positions += 1;
+        summary.deposited_value += pnl.deposited_value;
+        summary.withdrawn_value += pnl.withdrawn_value;
+        summary.unpriced_flows += pnl.unpriced_flows;
+        summary.position_value += pnl.position_value;
+        summary.hodl_value += pnl.hodl_value;
+        summary.fees_value += pnl.fees_value;
+        summary.rewards_value += pnl.rewards_value;
+        summary.net_pnl += pnl.net_pnl;
+    }
+    summaries
+        .into_values()
+        .map(|mut summary| {
+            if summary.hodl_value > 0.0 {
+                summary.impermanent_loss = summary.position_value / summary.hodl_value - 1.0;
+            }
+            summary
+        })
+        .collect()
+}
+
+/// State of `tick` in its tick array, the default state when the array does not exist.
+fn get_tick_state(
+    tick_array: Option<&TickArrayState>,
+    tick: i32,
+    tick_spacing: u16,
+) -> Result<TickState> {
+    let Some(tick_array) = tick_array else {
+        return Ok(TickState::default());
+    };
+    let offset = (tick - tick_array.start_tick_index) / i32::from(tick_spacing);
+    // copied out of the packed account
+    let ticks = tick_array.ticks;
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| ticks.get(offset).copied())
+        .ok_or_else(|| {
+            ClientError::InvalidResponse(format!(
+                "tick {} is outside of tick array {}",
+                tick, tick_array.start_tick_index
+            ))
+        })
+}
+
+/// Rebuild the ledger of `position` from the events of the transactions touching it.
+pub async fn get_position_ledger(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    position: &PersonalPositionState,
+) -> Result<PositionLedger> {
+    let position_key = get_personal_position_key(raydium_v3_program, &position.nft_mint);
+    let signatures = get_signatures(rpc_client, &position_key, None, None).await?;
+    let mut events = Vec::new();
+    for signature in signatures.iter().rev() {
+        let transaction = get_transaction(rpc_client, signature).await?;
+        let transaction = decode_transaction(raydium_v3_program, &transaction)?;
+        events.extend(
+            transaction
+                .events
+                .into_iter()
+                .map(|event| (transaction.block_time, event)),
+        );
+    }
+    PositionLedger::from_events(position, &events)
+}
+
+/// Load the position of `nft_mint` and compute its PnL, in `quote_mint` or else token1.
+/// Without `ledger`, it is rebuilt from the position's transactions.
+pub async fn get_position_pnl(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    nft_mint: Pubkey,
+    ledger: Option<PositionLedger>,
+    quote_mint: Option<Pubkey>,
+) -> Result<PositionPnl> {
+    let position = get_anchor_account::<PersonalPositionState>(
+        rpc_client,
+        &get_personal_position_key(raydium_v3_program, &nft_mint),
+    )
+    .await?;
+    let pool_state = get_anchor_account::<PoolState>(rpc_client, &position.pool_id).await?;
+    let tick_array_keys = [position.tick_lower_index, position.tick_upper_index].map(|tick| {
+        get_tick_array_key(
+            raydium_v3_program,
+            &position.pool_id,
+            TickArrayState::get_array_start_index(tick, pool_state.tick_spacing),
+        )
+    });
+    let tick_arrays = get_anchor_accounts::<TickArrayState>(rpc_client, &tick_array_keys).await?;
+    let tick_lower = get_tick_state(
+        tick_arrays[0].as_ref(),
+        position.tick_lower_index,
+        pool_state.tick_spacing,
+    )?;
+    let tick_upper = get_tick_state(
+        tick_arrays[1].as_ref(),
+        position.tick_upper_index,
+        pool_state.tick_spacing,
+    )?;
+    let ledger = match ledger {
+        Some(ledger) => ledger,
+        None => get_position_ledger(rpc_client, raydium_v3_program, &position).await?,
+    };
+    let now = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|err| ClientError::InvalidArgument(format!("system clock: {}", err)))?
+        .as_secs() as i64;
+    compute_position_pnl(
+        position.pool_id,
+        &pool_state,
+        &position,
+        &ledger,
+        &tick_lower,
+        &tick_upper,
+        quote_mint.unwrap_or(pool_state.token_mint_1),
+        now,
+    )
+}
+
+/// NFT mints of the positions `owner` holds.
+pub async fn get_owner_position_mints(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+    raydium_v3_program: &Pubkey,
+) -> Result<Vec<Pubkey>> {
+    let (nft_tokens, position_keys) =
+        get_nft_account_and_position_by_owner(rpc_client, owner, raydium_v3_program).await?;
+    let positions =
+        get_anchor_accounts::<PersonalPositionState>(rpc_client, &position_keys).await?;
+    Ok(nft_tokens
+        .into_iter()
+        .zip(positions)
+        .filter(|(_, position)| position.is_some())
+        .map(|(nft_token, _)| nft_token.mint)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_amm_v3::states::IncreaseLiquidityEvent;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+    /// What `LIQUIDITY` takes of each token in `[-1000, 1000)` at price 1, `L * (1 - 1/sqrt(pb))`
+    /// and `L * (1 - sqrt(pa))` with `sqrt(pb) = 1 / sqrt(pa) = 1.0001^500`.
+    const DEPOSIT: u64 = 48_768_197_581;
+
+    fn pool_state(tick: i32) -> PoolState {
+        let mut pool_state = PoolState::default();
+        pool_state.token_mint_0 = Pubkey::new_unique();
+        pool_state.token_mint_1 = Pubkey::new_unique();
+        pool_state.mint_decimals_0 = 6;
+        pool_state.mint_decimals_1 = 6;
+        pool_state.tick_spacing = 10;
+        pool_state.tick_current = tick;
+        pool_state.sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(tick).unwrap();
+        pool_state
+    }
+
+    fn position() -> PersonalPositionState {
+        PersonalPositionState {
+            nft_mint: Pubkey::new_unique(),
+            tick_lower_index: -1000,
+            tick_upper_index: 1000,
+            liquidity: LIQUIDITY,
+            ..Default::default()
+        }
+    }
+
+    fn tick_state(tick: i32) -> TickState {
+        TickState {
+            tick,
+            ..Default::default()
+        }
+    }
+
+    fn pnl(pool_state: &PoolState, ledger: &PositionLedger, quote_mint: Pubkey) -> PositionPnl {
+        compute_position_pnl(
+            Pubkey::new_unique(),
+            pool_state,
+            &position(),
+            ledger,
+            &tick_state(-1000),
+            &tick_state(1000),
+            quote_mint,
+            0,
+        )
+        .unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6 * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn impermanent_loss_above_the_range() {
+        // all in token1 at price 1.0001^2000: L * (sqrt(pb) - sqrt(pa)) of it, against the
+        // deposit held at that price
+        let pool_state = pool_state(2000);
+        let ledger = PositionLedger::from_entry(DEPOSIT, DEPOSIT, 1.0, None);
+        let pnl = pnl(&pool_state, &ledger, pool_state.token_mint_1);
+        assert!(!pnl.in_range);
+        assert_eq!(pnl.amount_0, 0);
+        assert_close(pnl.deposited_value, 97_536.395162);
+        assert_close(pnl.position_value, 100_036.665958);
+        assert_close(pnl.hodl_value, 108_333.213004);
+        assert_close(pnl.impermanent_loss, -0.076583596);
+        assert_close(pnl.net_pnl, 2_500.270796);
+        assert_eq!(pnl.unpriced_flows, 0);
+    }
+
+    #[test]
+    fn no_impermanent_loss_at_the_entry_price() {
+        let pool_state = pool_state(0);
+        let ledger = PositionLedger::from_entry(DEPOSIT, DEPOSIT, 1.0, None);
+        // valued in token0 this time
+        let pnl = pnl(&pool_state, &ledger, pool_state.token_mint_0);
+        assert!(pnl.in_range);
+        assert_close(pnl.hodl_value, 97_536.395162);
+        assert_close(pnl.position_value, pnl.hodl_value);
+        assert!(pnl.impermanent_loss.abs() < 1e-9);
+    }
+
+    #[test]
+    fn single_token_flows_have_no_price() {
+        let position = position();
+        let increase = |amount_0, amount_1| {
+            (
+                Some(100),
+                ClmmEvent::IncreaseLiquidity(IncreaseLiquidityEvent {
+                    position_nft_mint: position.nft_mint,
+                    liquidity: LIQUIDITY,
+                    amount_0,
+                    amount_1,
+                    amount_0_transfer_fee: 0,
+                    amount_1_transfer_fee: 0,
+                }),
+            )
+        };
+        let events = [increase(DEPOSIT, DEPOSIT), increase(DEPOSIT, 0)];
+        let ledger = PositionLedger::from_events(&position, &events).unwrap();
+        assert_close(ledger.deposits[0].price.unwrap(), 1.0);
+        assert_eq!(ledger.deposits[1].price, None);
+
+        // the token0 deposit is only worth a known amount of token0
+        let pool_state = pool_state(0);
+        assert_eq!(
+            pnl(&pool_state, &ledger, pool_state.token_mint_1).unpriced_flows,
+            1
+        );
+        assert_eq!(
+            pnl(&pool_state, &ledger, pool_state.token_mint_0).unpriced_flows,
+            0
+        );
+    }
+
+    #[test]
+    fn growth_inside_wraps_around() {
+        // in the range, with the growth outside of the lower tick ahead of the global one
+        assert_eq!(growth_inside(0, -10, u128::MAX - 9, 10, 5, 20), 25);
+        // below the range, with the growth outside of the upper tick ahead of the lower one's
+        assert_eq!(growth_inside(-20, -10, 3, 10, u128::MAX, 20), 4);
+        // above the range, with the global growth wrapped around past both ticks' growth
+        assert_eq!(growth_inside(20, -10, 7, 10, 10, 1), 3);
+        // the growth since the last update wraps around as well
+        assert_eq!(earned(5, u128::MAX - 4, fixed_point_64::Q64), 10);
+    }
+
+    fn position_pnl(quote_mint: Pubkey, position_value: f64, hodl_value: f64) -> PositionPnl {
+        PositionPnl {
+            nft_mint: Pubkey::new_unique(),
+            pool_id: Pubkey::new_unique(),
+            quote_mint,
+            in_range: true,
+            amount_0: 0,
+            amount_1: 0,
+            uncollected_fees_0: 0,
+            uncollected_fees_1: 0,
+            uncollected_rewards: [0; REWARD_NUM],
+            deposited_value: hodl_value,
+            withdrawn_value: 1.0,
+            unpriced_flows: 1,
+            position_value,
+            hodl_value,
+            impermanent_loss: position_value / hodl_value - 1.0,
+            fees_value: 2.0,
+            rewards_value: 0.5,
+            net_pnl: position_value + 3.5 - hodl_value,
+            fee_apr: None,
+        }
+    }
+
+    #[test]
+    fn aggregates_by_quote_mint() {
+        let (usdc, sol) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let totals = aggregate_pnl(&[
+            position_pnl(usdc, 90.0, 100.0),
+            position_pnl(sol, 10.0, 10.0),
+            position_pnl(usdc, 300.0, 300.0),
+        ]);
+        assert_eq!(totals.len(), 2);
+        let usdc_total = totals
+            .iter()
+            .find(|total| total.quote_mint == usdc)
+            .unwrap();
+        assert_eq!(usdc_total.positions, 2);
+        assert_eq!(usdc_total.unpriced_flows, 2);
+        assert_close(usdc_total.position_value, 390.0);
+        assert_close(usdc_total.hodl_value, 400.0);
+        assert_close(usdc_total.withdrawn_value, 2.0);
+        assert_close(usdc_total.fees_value, 4.0);
+        assert_close(usdc_total.rewards_value, 1.0);
+        assert_close(usdc_total.net_pnl, -3.0);
+        // weighted by value, not the mean of the positions' -10% and 0%
+        assert_close(usdc_total.impermanent_loss, -0.025);
+        let sol_total = totals.iter().find(|total| total.quote_mint == sol).unwrap();
+        assert_eq!(sol_total.positions, 1);
+        assert_close(sol_total.impermanent_loss, 0.0);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::depth::get_initialized_ticks;
+use crate::error::{ClientError, Result};
+use crate::utils::serialize_display;
+use raydium_amm_v3::libraries::{swap_math, tick_math};
+use raydium_amm_v3::states::{PoolState, TickArrayState, TICK_ARRAY_SIZE};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Extra tick arrays passed beyond the last one a quote goes through, so that the swap still
+/// executes when the price moved a little since.
+const TICK_ARRAY_BUFFER: usize = 1;
+
+/// Outcome of swapping an exact input against a pool, in raw amounts as they reach and leave
+/// the vaults.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SwapQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Trade fee, included in `amount_in`.
+    pub fee_amount: u64,
+    #[serde(serialize_with = "serialize_display")]
+    pub sqrt_price_x64: u128,
+    pub tick: i32,
+    /// Start indexes of the tick arrays the swap goes through, in the order the program
+    /// expects them as remaining accounts.
+    pub tick_array_start_indexes: Vec<i32>,
+}
+
+/// Quote swapping exactly `amount_in` against `pool_state`, crossing the initialized ticks of
+/// `tick_arrays` with the program's own swap math. Fails when the liquidity runs out before
+/// the whole input is taken.
+pub fn quote_exact_input(
+    pool_state: &PoolState,
+    tick_arrays: &BTreeMap<i32, TickArrayState>,
+    trade_fee_rate: u32,
+    amount_in: u64,
+    zero_for_one: bool,
+) -> Result<SwapQuote> {
+    let ticks = get_initialized_ticks(tick_arrays.values());
+    let tick_current = pool_state.tick_current;
+    let split = ticks.partition_point(|(tick, _)| *tick <= tick_current);
+    // initialized ticks in the order the swap meets them
+    let next_ticks = if zero_for_one {
+        ticks[..split].iter().rev().copied().collect::<Vec<_>>()
+    } else {
+        ticks[split..].to_vec()
+    };
+
+    let mut sqrt_price_x64 = pool_state.sqrt_price_x64;
+    let mut tick = tick_current;
+    let mut liquidity = pool_state.liquidity;
+    let mut amount_remaining = amount_in;
+    let mut amount_out: u64 = 0;
+    let mut fee_amount: u64 = 0;
+    let mut next_ticks = next_ticks.into_iter();
+    while amount_remaining > 0 {
+        let next_tick = next_ticks.next();
+        let sqrt_price_target_x64 = match next_tick {
+            Some((tick_next, _)) => tick_math::get_sqrt_price_at_tick(tick_next)?,
+            None if zero_for_one => tick_math::MIN_SQRT_PRICE_X64 + 1,
+            None => tick_math::MAX_SQRT_PRICE_X64 - 1,
+        };
+        // the program only uses the timestamp to pick a rounding, as its own client does
+        let step = swap_math::compute_swap_step(
+            sqrt_price_x64,
+            sqrt_price_target_x64,
+            liquidity,
+            amount_remaining,
+            trade_fee_rate,
+            true,
+            zero_for_one,
+            1,
+        )?;
+        amount_remaining = step
+            .amount_in
+            .checked_add(step.fee_amount)
+            .and_then(|amount| amount_remaining.checked_sub(amount))
+            .ok_or_else(|| ClientError::MathOverflow("swap step input".to_string()))?;
+        amount_out = amount_out
+            .checked_add(step.amount_out)
+            .ok_or_else(|| ClientError::MathOverflow("swap output".to_string()))?;
+        fee_amount += step.fee_amount;
+        sqrt_price_x64 = step.sqrt_price_next_x64;
+
+        match next_tick {
+            Some((tick_next, liquidity_net)) if sqrt_price_x64 == sqrt_price_target_x64 => {
+                let liquidity_net = if zero_for_one {
+                    -liquidity_net
+                } else {
+                    liquidity_net
+                };
+                liquidity = i128::try_from(liquidity)
+                    .ok()
+                    .and_then(|liquidity| liquidity.checked_add(liquidity_net))
+                    .and_then(|liquidity| u128::try_from(liquidity).ok())
+                    .ok_or_else(|| {
+                        ClientError::MathOverflow(format!("liquidity at tick {}", tick_next))
+                    })?;
+                tick = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            }
+            _ => {
+                tick = tick_math::get_tick_at_sqrt_price(sqrt_price_x64)?;
+                break;
+            }
+        }
+    }
+    if amount_remaining > 0 {
+        return Err(ClientError::InvalidArgument(format!(
+            "pool liquidity only takes {} of {}",
+            amount_in - amount_remaining,
+            amount_in
+        )));
+    }
+
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        fee_amount,
+        sqrt_price_x64,
+        tick,
+        tick_array_start_indexes: get_swap_tick_array_start_indexes(
+            pool_state,
+            tick_arrays,
+            tick,
+            zero_for_one,
+        ),
+    })
+}
+
+/// Initialized tick arrays from the current tick to `tick_end`, plus [`TICK_ARRAY_BUFFER`].
+fn get_swap_tick_array_start_indexes(
+    pool_state: &PoolState,
+    tick_arrays: &BTreeMap<i32, TickArrayState>,
+    tick_end: i32,
+    zero_for_one: bool,
+) -> Vec<i32> {
+    let ticks_in_array = TICK_ARRAY_SIZE * i32::from(pool_state.tick_spacing);
+    let current_start_index =
+        TickArrayState::get_array_start_index(pool_state.tick_current, pool_state.tick_spacing);
+    let start_indexes = if zero_for_one {
+        tick_arrays
+            .range(..=current_start_index)
+            .rev()
+            .map(|(start_index, _)| *start_index)
+            .collect::<Vec<_>>()
+    } else {
+        tick_arrays
+            .range(current_start_index..)
+            .map(|(start_index, _)| *start_index)
+            .collect::<Vec<_>>()
+    };
+    let crossed = start_indexes
+        .iter()
+        .take_while(|start_index| {
+            if zero_for_one {
+                **start_index + ticks_in_array > tick_end
+            } else {
+                **start_index <= tick_end
+            }
+        })
+        .count();
+    start_indexes
+        .into_iter()
+        .take(crossed.max(1) + TICK_ARRAY_BUFFER)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    fn pool_state(liquidity: u128) -> PoolState {
+        let mut pool_state = PoolState::default();
+        pool_state.tick_current = 0;
+        pool_state.sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(0).unwrap();
+        pool_state.tick_spacing = 10;
+        pool_state.liquidity = liquidity;
+        pool_state
+    }
+
+    fn tick_array(ticks: &[(i32, i128)]) -> TickArrayState {
+        let mut tick_array = TickArrayState::default();
+        // copied out of the packed account
+        let mut tick_states = tick_array.ticks;
+        for (tick_state, (tick, liquidity_net)) in tick_states.iter_mut().zip(ticks) {
+            tick_state.tick = *tick;
+            tick_state.liquidity_net = *liquidity_net;
+            tick_state.liquidity_gross = liquidity_net.unsigned_abs();
+        }
+        tick_array.ticks = tick_states;
+        tick_array
+    }
+
+    /// `LIQUIDITY` over [-500, 500], and as much again over [-500, 100].
+    fn tick_arrays() -> BTreeMap<i32, TickArrayState> {
+        let liquidity = LIQUIDITY as i128;
+        BTreeMap::from([
+            (-600, tick_array(&[(-500, 2 * liquidity)])),
+            (0, tick_array(&[(100, -liquidity), (500, -liquidity)])),
+        ])
+    }
+
+    fn sqrt_price(tick: i32) -> f64 {
+        1.0001_f64.powf(tick as f64 / 2.0)
+    }
+
+    #[test]
+    fn quote_within_a_range_takes_the_fee_from_the_input() {
+        let quote = quote_exact_input(
+            &pool_state(2 * LIQUIDITY),
+            &tick_arrays(),
+            2_500,
+            1_000_000,
+            true,
+        )
+        .unwrap();
+        assert_eq!((quote.amount_in, quote.fee_amount), (1_000_000, 2_500));
+        // 997_500 token0 in at a price of 1 and 2e12 liquidity, rounded down
+        let expected = 997_500.0 * 2e12 / (2e12 + 997_500.0);
+        assert!((expected - 2.0..=expected).contains(&(quote.amount_out as f64)));
+        assert_eq!(quote.tick, -1);
+        assert_eq!(quote.tick_array_start_indexes, vec![0, -600]);
+    }
+
+    #[test]
+    fn quote_crosses_initialized_ticks() {
+        let liquidity = LIQUIDITY as f64;
+        // token1 to reach tick 100 with twice the liquidity, then 1e9 more with half of it
+        let to_tick_100 = (2.0 * liquidity * (sqrt_price(100) - 1.0)).ceil();
+        let amount_in = to_tick_100 as u64 + 1_000_000_000;
+        let sqrt_price_end = sqrt_price(100) + 1e9 / liquidity;
+        let expected = 2.0 * liquidity * (1.0 - 1.0 / sqrt_price(100))
+            + liquidity * (1.0 / sqrt_price(100) - 1.0 / sqrt_price_end);
+
+        let quote = quote_exact_input(
+            &pool_state(2 * LIQUIDITY),
+            &tick_arrays(),
+            0,
+            amount_in,
+            false,
+        )
+        .unwrap();
+        assert!((quote.amount_out as f64 - expected).abs() <= 1e-6 * expected);
+        assert_eq!(
+            quote.tick,
+            (sqrt_price_end.powi(2).ln() / 1.0001_f64.ln()).floor() as i32
+        );
+        assert_eq!(quote.tick_array_start_indexes, vec![0]);
+    }
+
+    #[test]
+    fn quote_fails_when_the_liquidity_runs_out() {
+        let err = quote_exact_input(
+            &pool_state(2 * LIQUIDITY),
+            &tick_arrays(),
+            0,
+            u64::MAX,
+            false,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ClientError::InvalidArgument(_)));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::error::{ClientError, Result};
+use crate::oracle::get_observations;
+use crate::utils::tick_with_spacing;
+use raydium_amm_v3::libraries::tick_math;
+use raydium_amm_v3::states::ObservationState;
+
+/// Preset ways of placing a position around the pool's current tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeStrategy {
+    /// The whole tradable range.
+    FullRange,
+    /// `±percent` around the current price, e.g. `0.05` for ±5%.
+    Percent(f64),
+    /// `±ticks` around the current tick.
+    Ticks(i32),
+    /// `±multiplier` standard deviations, `sigma` being measured in ticks (see [`tick_std_dev`]).
+    StdDev { multiplier: f64, sigma: f64 },
+    /// Range order of `width` ticks, entirely above (token0 only) or below (token1 only)
+    /// the current tick.
+    OneSided { above: bool, width: i32 },
+}
+
+/// Lowest and highest ticks usable with `tick_spacing`.
+pub fn tick_bounds(tick_spacing: i32) -> (i32, i32) {
+    let max_tick = tick_with_spacing(tick_math::MAX_TICK, tick_spacing);
+    (-max_tick, max_tick)
+}
+
+/// Align `tick` to `tick_spacing` and keep it within [`tick_bounds`].
+pub fn clamp_tick(tick: i32, tick_spacing: i32) -> i32 {
+    let (min_tick, max_tick) = tick_bounds(tick_spacing);
+    tick_with_spacing(tick, tick_spacing).clamp(min_tick, max_tick)
+}
+
+/// Number of ticks for a relative price move, `ratio` being e.g. `1.05` for +5%.
+pub fn ticks_for_price_ratio(ratio: f64) -> i32 {
+    (ratio.ln() / 1.0001_f64.ln()).round() as i32
+}
+
+/// Compute `(tick_lower, tick_upper)` for `strategy`, aligned to `tick_spacing`.
+pub fn get_range_ticks(
+    strategy: RangeStrategy,
+    tick_current: i32,
+    tick_spacing: u16,
+) -> Result<(i32, i32)> {
+    let tick_spacing = i32::from(tick_spacing);
+    let (lower, upper) = match strategy {
+        RangeStrategy::FullRange => tick_bounds(tick_spacing),
+        RangeStrategy::Percent(percent) => {
+            if percent <= 0.0 || percent >= 1.0 {
+                return Err(ClientError::InvalidArgument(format!(
+                    "percent must be within (0, 1), got {}",
+                    percent
+                )));
+            }
+            (
+                tick_current + ticks_for_price_ratio(1.0 - percent),
+                tick_current + ticks_for_price_ratio(1.0 + percent),
+            )
+        }
+        RangeStrategy::Ticks(ticks) => {
+            if ticks <= 0 {
+                return Err(ClientError::InvalidArgument(format!(
+                    "ticks must be positive, got {}",
+                    ticks
+                )));
+            }
+            // clamped to the tick bounds below
+            (
+                tick_current.saturating_sub(ticks),
+                tick_current.saturating_add(ticks),
+            )
+        }
+        RangeStrategy::StdDev { multiplier, sigma } => {
+            let ticks = (multiplier * sigma).ceil();
+            if ticks.is_nan() || ticks <= 0.0 {
+                return Err(ClientError::InvalidArgument(format!(
+                    "multiplier * sigma must be positive, got {} * {}",
+                    multiplier, sigma
+                )));
+            }
+            let ticks = ticks.min(tick_math::MAX_TICK as f64) as i32;
+            (
+                tick_current.saturating_sub(ticks),
+                tick_current.saturating_add(ticks),
+            )
+        }
+        RangeStrategy::OneSided { above, width } => {
+            if width <= 0 {
+                return Err(ClientError::InvalidArgument(format!(
+                    "width must be positive, got {}",
+                    width
+                )));
+            }
+            let width = width.max(tick_spacing);
+            let current = tick_with_spacing(tick_current, tick_spacing);
+            if above {
+                // first aligned tick strictly above the current one
+                (
+                    current + tick_spacing,
+                    (current + tick_spacing).saturating_add(width),
+                )
+            } else {
+                (current.saturating_sub(width), current)
+            }
+        }
+    };
+
+    let mut tick_lower = clamp_tick(lower, tick_spacing);
+    let mut tick_upper = clamp_tick(upper, tick_spacing);
+    if let RangeStrategy::OneSided { .. } = strategy {
+        // rounding the width down must not make the range cross the current tick
+        if tick_upper - tick_lower < tick_spacing {
+            return Err(ClientError::InvalidArgument(format!(
+                "no room for a one-sided range at tick {}",
+                tick_current
+            )));
+        }
+        return Ok((tick_lower, tick_upper));
+    }
+    if tick_lower == tick_upper {
+        let (min_tick, max_tick) = tick_bounds(tick_spacing);
+        if tick_upper + tick_spacing <= max_tick {
+            tick_upper += tick_spacing;
+        } else if tick_lower - tick_spacing >= min_tick {
+            tick_lower -= tick_spacing;
+        }
+    }
+    Ok((tick_lower, tick_upper))
+}
+
+/// Time weighted standard deviation of the tick, in ticks, over the window covered
+/// by the pool's observations.
+pub fn tick_std_dev(observation_state: &ObservationState) -> Result<f64> {
+    let points = get_observations(observation_state);
+
+    // average tick and duration of every interval between two observations
+    let intervals = points
+        .windows(2)
+        .map(|pair| {
+            let duration = f64::from(pair[1].0 - pair[0].0);
+            ((pair[1].1 - pair[0].1) as f64 / duration, duration)
+        })
+        .collect::<Vec<(f64, f64)>>();
+    if intervals.len() < 2 {
+        return Err(ClientError::InvalidArgument(
+            "not enough observations to measure volatility".to_string(),
+        ));
+    }
+
+    let total_time: f64 = intervals.iter().map(|(_, duration)| duration).sum();
+    let mean = intervals
+        .iter()
+        .map(|(tick, duration)| tick * duration)
+        .sum::<f64>()
+        / total_time;
+    let variance = intervals
+        .iter()
+        .map(|(tick, duration)| (tick - mean).powi(2) * duration)
+        .sum::<f64>()
+        / total_time;
+    Ok(variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_are_aligned_to_spacing() {
+        assert_eq!(tick_bounds(1), (-tick_math::MAX_TICK, tick_math::MAX_TICK));
+        assert_eq!(tick_bounds(60), (-443_580, 443_580));
+        assert_eq!(clamp_tick(-443_636, 60), -443_580);
+        assert_eq!(clamp_tick(443_636, 60), 443_580);
+    }
+
+    #[test]
+    fn percent_range_rounds_down_to_spacing() {
+        // -513 and +488 ticks, rounded towards negative infinity
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Percent(0.05), 0, 10).unwrap(),
+            (-520, 480)
+        );
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Percent(0.05), -1_005, 10).unwrap(),
+            (-1_520, -520)
+        );
+        assert!(get_range_ticks(RangeStrategy::Percent(0.0), 0, 10).is_err());
+        assert!(get_range_ticks(RangeStrategy::Percent(1.0), 0, 10).is_err());
+    }
+
+    #[test]
+    fn ranges_are_clamped_at_the_tick_bounds() {
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Ticks(1_000), 443_000, 60).unwrap(),
+            (441_960, 443_580)
+        );
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Ticks(1_000), -443_000, 60).unwrap(),
+            (-443_580, -442_020)
+        );
+        assert_eq!(
+            get_range_ticks(RangeStrategy::FullRange, 12_345, 60).unwrap(),
+            (-443_580, 443_580)
+        );
+        // widths past the i32 range do not overflow
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Ticks(i32::MAX), 12_345, 60).unwrap(),
+            (-443_580, 443_580)
+        );
+        let above = RangeStrategy::OneSided {
+            above: true,
+            width: i32::MAX,
+        };
+        assert_eq!(
+            get_range_ticks(above, 12_345, 60).unwrap(),
+            (12_360, 443_580)
+        );
+    }
+
+    #[test]
+    fn collapsed_range_is_widened_by_one_spacing() {
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Ticks(1), 5, 60).unwrap(),
+            (0, 60)
+        );
+        // no room above the highest usable tick
+        assert_eq!(
+            get_range_ticks(RangeStrategy::Ticks(1), 443_600, 60).unwrap(),
+            (443_520, 443_580)
+        );
+    }
+
+    #[test]
+    fn std_dev_range_spans_multiplier_sigmas() {
+        // ceil(2 * 10.2) = 21 ticks each side
+        assert_eq!(
+            get_range_ticks(
+                RangeStrategy::StdDev {
+                    multiplier: 2.0,
+                    sigma: 10.2
+                },
+                100,
+                10
+            )
+            .unwrap(),
+            (70, 120)
+        );
+        // a huge deviation is the full range
+        assert_eq!(
+            get_range_ticks(
+                RangeStrategy::StdDev {
+                    multiplier: 3.0,
+                    sigma: 1e9
+                },
+                0,
+                60
+            )
+            .unwrap(),
+            (-443_580, 443_580)
+        );
+        for sigma in [0.0, -1.0, f64::NAN] {
+            assert!(get_range_ticks(
+                RangeStrategy::StdDev {
+                    multiplier: 2.0,
+                    sigma
+                },
+                0,
+                10
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn one_sided_range_excludes_the_current_tick() {
+        let above = RangeStrategy::OneSided {
+            above: true,
+            width: 100,
+        };
+        let below = RangeStrategy::OneSided {
+            above: false,
+            width: 100,
+        };
+        assert_eq!(get_range_ticks(above, 5, 10).unwrap(), (10, 110));
+        assert_eq!(get_range_ticks(below, 5, 10).unwrap(), (-100, 0));
+        // the width is at least one spacing
+        assert_eq!(
+            get_range_ticks(
+                RangeStrategy::OneSided {
+                    above: true,
+                    width: 1
+                },
+                5,
+                10
+            )
+            .unwrap(),
+            (10, 20)
+        );
+    }
+}
@@ -0,0 +1,725 @@
+use crate::client::{get_accounts, get_anchor_account, get_anchor_accounts};
+use crate::config::ClientConfig;
+use crate::error::{ClientError, Result};
+use crate::pda::{get_personal_position_key, get_protocol_position_key, get_tick_array_key};
+use crate::range::{get_range_ticks, RangeStrategy};
+use crate::stats::{
+    get_indexed_activity, sample_activity, PoolActivity, StatsSource, SECONDS_PER_DAY,
+};
+use crate::transactions::{rebalance_position_tx, unix_now, RebalanceResult};
+use crate::utils::{
+    estimate_swap_amount_out, get_zap_in_swap_amount, is_native_mint, multipler, serialize_display,
+    serialize_pubkey, sqrt_price_x64_to_f64,
+};
+use raydium_amm_v3::libraries::{liquidity_math, tick_math};
+use raydium_amm_v3::states::{
+    AmmConfig, PersonalPositionState, PoolState, ProtocolPositionState, TickArrayState,
+    FEE_RATE_DENOMINATOR_VALUE,
+};
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Base fee of a signature, moves setting no priority fee.
+const SIGNATURE_FEE_LAMPORTS: u64 = 5_000;
+
+/// Settings of [`run_rebalancer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RebalanceConfig {
+    /// New ranges, placed around the current tick.
+    pub strategy: RangeStrategy,
+    /// Also move a position in range once the current tick is further from the middle of its
+    /// range than this share of the half width, within `(0, 1)`.
+    pub drift_threshold: Option<f64>,
+    /// Minimum time between two moves of the same position.
+    pub cooldown: Duration,
+    /// Time between two checks of the positions.
+    pub interval: Duration,
+    /// Slippage allowed on the withdrawal, the swap and the deposit.
+    pub max_slippage: f64,
+    /// Skip a move whose swap fee and slippage would cost more than this share of the position
+    /// value.
+    pub max_cost_ratio: f64,
+    /// Time the new position is expected to stay in range, over which its fee income must pay
+    /// for the swap, the transaction fees and the rent of a move.
+    pub fee_horizon: Duration,
+    /// Swaps the fee income is estimated from: those of the last day in an indexer database,
+    /// or a sample of the pool taken when a move is planned.
+    pub fee_source: StatsSource,
+    /// Price of one SOL in token1 (UI amounts), valuing the lamports a move costs in pools not
+    /// trading SOL. They are left out of the profit check without it.
+    pub sol_price: Option<f64>,
+    /// Lamports the payer must hold to send the transaction of a move.
+    pub min_sol_balance: u64,
+    /// Only report what would be done.
+    pub dry_run: bool,
+}
+
+/// Why a position is moved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RebalanceReason {
+    OutOfRange,
+    /// Distance from the middle of the range, as a share of the half width.
+    Drift(f64),
+}
+
+impl fmt::Display for RebalanceReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebalanceReason::OutOfRange => f.write_str("out-of-range"),
+            RebalanceReason::Drift(drift) => write!(f, "drift {:.3}", drift),
+        }
+    }
+}
+
+/// A move of a position to a new range. Values are raw token1 amounts at the current price.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RebalancePlan {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_display")]
+    pub reason: RebalanceReason,
+    pub tick_current: i32,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub new_tick_lower_index: i32,
+    pub new_tick_upper_index: i32,
+    pub position_value: f64,
+    /// Trade fee and worst case slippage of the swap to the new ratio.
+    pub swap_cost: f64,
+    /// Liquidity of the new position, from the amounts the swap is expected to leave.
+    #[serde(serialize_with = "serialize_display")]
+    pub new_liquidity: u128,
+    /// Lamports of the transaction fees and of the rent the new position does not get back
+    /// from closing the old one.
+    pub transaction_lamports: u64,
+    /// `transaction_lamports` in token1, unknown in pools not trading SOL without a SOL price.
+    pub transaction_cost: Option<f64>,
+    /// Fees the new position earns over the fee horizon at the pool's recent fee growth.
+    pub expected_fee_income: f64,
+}
+
+impl RebalancePlan {
+    pub fn cost_ratio(&self) -> f64 {
+        if self.position_value > 0.0 {
+            self.swap_cost / self.position_value
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Expected fee income less the swap and transaction costs.
+    pub fn profit(&self) -> f64 {
+        self.expected_fee_income - self.swap_cost - self.transaction_cost.unwrap_or_default()
+    }
+
+    /// Why the move is not worth making under `config`, `None` when it is.
+    pub fn skip_action(&self, config: &RebalanceConfig) -> Option<RebalanceAction> {
+        if self.cost_ratio() > config.max_cost_ratio {
+            Some(RebalanceAction::SkippedCost)
+        } else if self.profit() < 0.0 {
+            Some(RebalanceAction::SkippedUnprofitable)
+        } else {
+            None
+        }
+    }
+}
+
+/// What was done about a planned move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RebalanceAction {
+    Moved,
+    /// The swap would cost more than `max_cost_ratio` of the position value.
+    SkippedCost,
+    /// The expected fee income does not cover the swap and transaction costs.
+    SkippedUnprofitable,
+    /// The payer holds less than `min_sol_balance`.
+    SkippedBalance,
+    DryRun,
+}
+
+/// A planned move with what was done about it, and the transactions when it was made.
+#[derive(Debug, Serialize)]
+pub struct RebalanceOutcome {
+    #[serde(flatten)]
+    pub plan: RebalancePlan,
+    pub cost_ratio: f64,
+    pub action: RebalanceAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rebalance: Option<RebalanceResult>,
+}
+
+/// Whether a position on `[tick_lower_index, tick_upper_index)` must be moved at `tick_current`.
+pub fn should_rebalance(
+    tick_current: i32,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    drift_threshold: Option<f64>,
+) -> Option<RebalanceReason> {
+    if tick_current < tick_lower_index || tick_current >= tick_upper_index {
+        return Some(RebalanceReason::OutOfRange);
+    }
+    let half_width = f64::from(tick_upper_index - tick_lower_index) / 2.0;
+    let middle = f64::from(tick_lower_index) + half_width;
+    let drift = (f64::from(tick_current) - middle).abs() / half_width;
+    drift_threshold
+        .filter(|threshold| drift > *threshold)
+        .map(|_| RebalanceReason::Drift(drift))
+}
+
+/// Plan the move of the position of `nft_mint`, `None` when it can stay where it is.
+pub async fn plan_rebalance(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    nft_mint: Pubkey,
+    config: &RebalanceConfig,
+) -> Result<Option<RebalancePlan>> {
+    let personal_position_key = get_personal_position_key(raydium_v3_program, &nft_mint);
+    let position =
+        get_anchor_accounts::<PersonalPositionState>(rpc_client, &[personal_position_key])
+            .await?
+            .remove(0)
+            .ok_or(ClientError::AccountNotFound(personal_position_key))?;
+    if position.liquidity == 0 {
+        return Err(ClientError::InvalidArgument(format!(
+            "position {} has no liquidity",
+            nft_mint
+        )));
+    }
+    let pool = get_anchor_account::<PoolState>(rpc_client, &position.pool_id).await?;
+    let Some(reason) = should_rebalance(
+        pool.tick_current,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        config.drift_threshold,
+    ) else {
+        return Ok(None);
+    };
+    let (new_tick_lower_index, new_tick_upper_index) =
+        get_range_ticks(config.strategy, pool.tick_current, pool.tick_spacing)?;
+    if (new_tick_lower_index, new_tick_upper_index)
+        == (position.tick_lower_index, position.tick_upper_index)
+    {
+        return Ok(None);
+    }
+    let amm_config = get_anchor_account::<AmmConfig>(rpc_client, &pool.amm_config).await?;
+
+    // what the withdrawal brings, ignoring transfer fees
+    let (amount_0, amount_1) = liquidity_math::get_delta_amounts_signed(
+        pool.tick_current,
+        pool.sqrt_price_x64,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        -(position.liquidity as i128),
+    )?;
+    let amount_0 = amount_0.saturating_add(position.token_fees_owed_0);
+    let amount_1 = amount_1.saturating_add(position.token_fees_owed_1);
+    let sqrt_price = sqrt_price_x64_to_f64(pool.sqrt_price_x64);
+    let price = sqrt_price * sqrt_price;
+    let position_value = amount_0 as f64 * price + amount_1 as f64;
+    let new_tick_lower_price_x64 = tick_math::get_sqrt_price_at_tick(new_tick_lower_index)?;
+    let new_tick_upper_price_x64 = tick_math::get_sqrt_price_at_tick(new_tick_upper_index)?;
+    let zap_in_swap = get_zap_in_swap_amount(
+        pool.sqrt_price_x64,
+        new_tick_lower_price_x64,
+        new_tick_upper_price_x64,
+        amm_config.trade_fee_rate,
+        amount_0,
+        amount_1,
+    );
+    let swap_value = match zap_in_swap {
+        Some((true, amount_in)) => amount_in as f64 * price,
+        Some((false, amount_in)) => amount_in as f64,
+        None => 0.0,
+    };
+    let swap_cost = swap_value
+        * (amm_config.trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64
+            + config.max_slippage);
+
+    let (new_amount_0, new_amount_1) = match zap_in_swap {
+        Some((zero_for_one, amount_in)) => {
+            let amount_out = estimate_swap_amount_out(
+                pool.sqrt_price_x64,
+                amm_config.trade_fee_rate,
+                amount_in,
+                zero_for_one,
+            );
+            if zero_for_one {
+                (amount_0.saturating_sub(amount_in), amount_1 + amount_out)
+            } else {
+                (amount_0 + amount_out, amount_1.saturating_sub(amount_in))
+            }
+        }
+        None => (amount_0, amount_1),
+    };
+    let new_liquidity = liquidity_math::get_liquidity_from_amounts(
+        pool.sqrt_price_x64,
+        new_tick_lower_price_x64,
+        new_tick_upper_price_x64,
+        new_amount_0,
+        new_amount_1,
+    );
+    let activity = match &config.fee_source {
+        StatsSource::Indexed(path) => get_indexed_activity(
+            path,
+            &position.pool_id,
+            &amm_config,
+            unix_now()?,
+            &[SECONDS_PER_DAY],
+        )?
+        .1
+        .remove(0),
+        StatsSource::Sampled(interval) => {
+            sample_activity(rpc_client, &position.pool_id, *interval).await?
+        }
+    };
+    let expected_fee_income = get_expected_fee_income(
+        &activity,
+        pool.sqrt_price_x64,
+        new_liquidity,
+        config.fee_horizon,
+    );
+    let transaction_lamports = get_move_lamports(
+        rpc_client,
+        raydium_v3_program,
+        &position.pool_id,
+        &pool,
+        new_tick_lower_index,
+        new_tick_upper_index,
+    )
+    .await?;
+    // raw token1 per lamport
+    let lamport_value = if is_native_mint(&pool.token_mint_1) {
+        Some(1.0)
+    } else if is_native_mint(&pool.token_mint_0) {
+        Some(price)
+    } else {
+        config.sol_price.map(|sol_price| {
+            sol_price * multipler(pool.mint_decimals_1)
+                / multipler(spl_token::native_mint::DECIMALS)
+        })
+    };
+
+    Ok(Some(RebalancePlan {
+        nft_mint,
+        pool_id: position.pool_id,
+        reason,
+        tick_current: pool.tick_current,
+        tick_lower_index: position.tick_lower_index,
+        tick_upper_index: position.tick_upper_index,
+        new_tick_lower_index,
+        new_tick_upper_index,
+        position_value,
+        swap_cost,
+        new_liquidity,
+        transaction_lamports,
+        transaction_cost: lamport_value.map(|value| transaction_lamports as f64 * value),
+        expected_fee_income,
+    }))
+}
+
+/// Fees `liquidity` kept in range earns over `horizon`, in raw token1 at `sqrt_price_x64`, at
+/// the fee growth rate of `activity`.
+fn get_expected_fee_income(
+    activity: &PoolActivity,
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    horizon: Duration,
+) -> f64 {
+    if activity.seconds <= 0.0 {
+        return 0.0;
+    }
+    let sqrt_price = sqrt_price_x64_to_f64(sqrt_price_x64);
+    let fee_value_per_second = (activity.fee_growth_0 * sqrt_price * sqrt_price
+        + activity.fee_growth_1)
+        / activity.seconds;
+    liquidity as f64 * fee_value_per_second * horizon.as_secs_f64()
+}
+
+/// Lamports a move to `[tick_lower_index, tick_upper_index]` costs: the fees of its two
+/// signatures and the rent of the accounts it leaves open, the new position's NFT mint and any
+/// tick array or protocol position it initializes. Closing the old position refunds the rest.
+async fn get_move_lamports(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    pool_id: &Pubkey,
+    pool: &PoolState,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+) -> Result<u64> {
+    let tick_array_lower_start_index =
+        TickArrayState::get_array_start_index(tick_lower_index, pool.tick_spacing);
+    let tick_array_upper_start_index =
+        TickArrayState::get_array_start_index(tick_upper_index, pool.tick_spacing);
+    let accounts = get_accounts(
+        rpc_client,
+        &[
+            get_protocol_position_key(
+                raydium_v3_program,
+                pool_id,
+                tick_lower_index,
+                tick_upper_index,
+            ),
+            get_tick_array_key(raydium_v3_program, pool_id, tick_array_lower_start_index),
+            get_tick_array_key(raydium_v3_program, pool_id, tick_array_upper_start_index),
+        ],
+    )
+    .await?;
+    let mut sizes = vec![spl_token::state::Mint::LEN];
+    if accounts[0].is_none() {
+        sizes.push(ProtocolPositionState::LEN);
+    }
+    if accounts[1].is_none() {
+        sizes.push(TickArrayState::LEN);
+    }
+    if accounts[2].is_none() && tick_array_upper_start_index != tick_array_lower_start_index {
+        sizes.push(TickArrayState::LEN);
+    }
+    let mut lamports = 2 * SIGNATURE_FEE_LAMPORTS;
+    for size in sizes {
+        lamports += rpc_client
+            .get_minimum_balance_for_rent_exemption(size)
+            .await?;
+    }
+    Ok(lamports)
+}
+
+/// Check the position of `nft_mint` and move it when needed, `None` when it can stay where it
+/// is.
+pub async fn rebalance_once(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    nft_mint: Pubkey,
+    config: &RebalanceConfig,
+) -> Result<Option<RebalanceOutcome>> {
+    let Some(plan) = plan_rebalance(
+        rpc_client,
+        &pool_config.raydium_v3_program,
+        nft_mint,
+        config,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    let mut outcome = RebalanceOutcome {
+        cost_ratio: plan.cost_ratio(),
+        plan,
+        action: RebalanceAction::DryRun,
+        rebalance: None,
+    };
+    if let Some(action) = outcome.plan.skip_action(config) {
+        outcome.action = action;
+        return Ok(Some(outcome));
+    }
+    let sol_balance = rpc_client.get_balance(&payer.pubkey()).await?;
+    if sol_balance < config.min_sol_balance {
+        log::warn!(
+            "payer balance {} below {} lamports",
+            sol_balance,
+            config.min_sol_balance
+        );
+        outcome.action = RebalanceAction::SkippedBalance;
+        return Ok(Some(outcome));
+    }
+    if config.dry_run {
+        return Ok(Some(outcome));
+    }
+    outcome.rebalance = Some(
+        rebalance_position_tx(
+            rpc_client,
+            pool_config,
+            payer,
+            nft_mint,
+            outcome.plan.new_tick_lower_index,
+            outcome.plan.new_tick_upper_index,
+            config.max_slippage,
+        )
+        .await?,
+    );
+    outcome.action = RebalanceAction::Moved;
+    Ok(Some(outcome))
+}
+
+/// Whether the position of `nft_mint` was moved, or its move skipped, less than `cooldown`
+/// before `now`.
+fn is_cooling_down(
+    cooldowns: &HashMap<Pubkey, Instant>,
+    nft_mint: &Pubkey,
+    cooldown: Duration,
+    now: Instant,
+) -> bool {
+    cooldowns
+        .get(nft_mint)
+        .is_some_and(|start| now.saturating_duration_since(*start) < cooldown)
+}
+
+/// Start the cooldown of the position of `nft_mint` after `action`, unless it was a dry run,
+/// returning the NFT mint of the position to watch from then on: `new_nft_mint` after a move.
+fn start_cooldown(
+    cooldowns: &mut HashMap<Pubkey, Instant>,
+    nft_mint: Pubkey,
+    action: RebalanceAction,
+    new_nft_mint: Option<Pubkey>,
+    now: Instant,
+) -> Pubkey {
+    match (action, new_nft_mint) {
+        (RebalanceAction::Moved, Some(new_nft_mint)) => {
+            cooldowns.remove(&nft_mint);
+            cooldowns.insert(new_nft_mint, now);
+            new_nft_mint
+        }
+        (RebalanceAction::DryRun, _) => nft_mint,
+        _ => {
+            cooldowns.insert(nft_mint, now);
+            nft_mint
+        }
+    }
+}
+
+/// Watch the positions of `nft_mints` and move each one to a range around the current price
+/// when it leaves its range or drifts past the threshold, passing each planned move to
+/// `on_outcome`. A position is not checked again for the cooldown after a move or a skipped
+/// one. Runs until killed, `on_outcome` fails or every position is closed; the other
+/// errors of a position are logged and it is retried at the next check.
+pub async fn run_rebalancer(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    nft_mints: Vec<Pubkey>,
+    config: &RebalanceConfig,
+    mut on_outcome: impl FnMut(&RebalanceOutcome) -> Result<()>,
+) -> Result<()> {
+    if config
+        .drift_threshold
+        .is_some_and(|threshold| threshold <= 0.0 || threshold >= 1.0)
+    {
+        return Err(ClientError::InvalidArgument(format!(
+            "drift threshold must be within (0, 1), got {:?}",
+            config.drift_threshold
+        )));
+    }
+    let mut nft_mints = nft_mints;
+    let mut cooldowns = HashMap::<Pubkey, Instant>::new();
+    loop {
+        let mut closed = Vec::new();
+        for nft_mint in nft_mints.iter_mut() {
+            if is_cooling_down(&cooldowns, nft_mint, config.cooldown, Instant::now()) {
+                continue;
+            }
+            match rebalance_once(rpc_client, pool_config, payer, *nft_mint, config).await {
+                Ok(Some(outcome)) => {
+                    on_outcome(&outcome)?;
+                    *nft_mint = start_cooldown(
+                        &mut cooldowns,
+                        *nft_mint,
+                        outcome.action,
+                        outcome.rebalance.map(|rebalance| rebalance.new_nft_mint),
+                        Instant::now(),
+                    );
+                }
+                Ok(None) => {}
+                // a move either lands whole or leaves the old position open, so a missing
+                // position was closed by someone else
+                Err(ClientError::AccountNotFound(key))
+                    if key
+                        == get_personal_position_key(&pool_config.raydium_v3_program, nft_mint) =>
+                {
+                    log::warn!("nft_mint:{}, position closed, no longer watched", nft_mint);
+                    closed.push(*nft_mint);
+                }
+                Err(err) => log::warn!("nft_mint:{}, error:{}", nft_mint, err),
+            }
+        }
+        nft_mints.retain(|nft_mint| !closed.contains(nft_mint));
+        if nft_mints.is_empty() {
+            return Ok(());
+        }
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_amm_v3::libraries::fixed_point_64;
+
+    fn config() -> RebalanceConfig {
+        RebalanceConfig {
+            strategy: RangeStrategy::Percent(0.05),
+            drift_threshold: None,
+            cooldown: Duration::from_secs(3600),
+            interval: Duration::from_secs(60),
+            max_slippage: 0.01,
+            max_cost_ratio: 0.01,
+            fee_horizon: Duration::from_secs(86_400),
+            fee_source: StatsSource::Sampled(Duration::from_secs(60)),
+            sol_price: None,
+            min_sol_balance: 0,
+            dry_run: false,
+        }
+    }
+
+    fn plan(
+        swap_cost: f64,
+        transaction_cost: Option<f64>,
+        expected_fee_income: f64,
+    ) -> RebalancePlan {
+        RebalancePlan {
+            nft_mint: Pubkey::new_unique(),
+            pool_id: Pubkey::new_unique(),
+            reason: RebalanceReason::OutOfRange,
+            tick_current: 0,
+            tick_lower_index: 60,
+            tick_upper_index: 120,
+            new_tick_lower_index: -60,
+            new_tick_upper_index: 60,
+            position_value: 1_000_000.0,
+            swap_cost,
+            new_liquidity: 1_000,
+            transaction_lamports: 10_000,
+            transaction_cost,
+            expected_fee_income,
+        }
+    }
+
+    #[test]
+    fn rebalances_out_of_range_or_past_the_drift_threshold() {
+        assert_eq!(
+            should_rebalance(-1, 0, 100, None),
+            Some(RebalanceReason::OutOfRange)
+        );
+        // the upper tick is out of the range
+        assert_eq!(
+            should_rebalance(100, 0, 100, None),
+            Some(RebalanceReason::OutOfRange)
+        );
+        assert_eq!(should_rebalance(0, 0, 100, None), None);
+        assert_eq!(should_rebalance(90, 0, 100, None), None);
+        assert_eq!(
+            should_rebalance(90, 0, 100, Some(0.5)),
+            Some(RebalanceReason::Drift(0.8))
+        );
+        assert_eq!(
+            should_rebalance(10, 0, 100, Some(0.5)),
+            Some(RebalanceReason::Drift(0.8))
+        );
+        assert_eq!(should_rebalance(90, 0, 100, Some(0.8)), None);
+        assert_eq!(should_rebalance(50, 0, 100, Some(0.5)), None);
+    }
+
+    #[test]
+    fn skips_moves_costing_too_much_or_earning_too_little() {
+        let config = config();
+        // the swap costs 1% of the position, as much as allowed
+        assert_eq!(
+            plan(10_000.0, Some(5_000.0), 15_000.0).skip_action(&config),
+            None
+        );
+        assert_eq!(
+            plan(10_001.0, Some(0.0), 1e9).skip_action(&config),
+            Some(RebalanceAction::SkippedCost)
+        );
+        assert_eq!(
+            plan(10_000.0, Some(5_000.0), 14_999.0).skip_action(&config),
+            Some(RebalanceAction::SkippedUnprofitable)
+        );
+        // transaction costs are left out when they are unknown
+        assert_eq!(plan(10_000.0, None, 10_000.0).skip_action(&config), None);
+
+        let mut empty = plan(0.0, None, 0.0);
+        empty.position_value = 0.0;
+        assert_eq!(
+            empty.skip_action(&config),
+            Some(RebalanceAction::SkippedCost)
+        );
+    }
+
+    #[test]
+    fn expected_fee_income_at_the_sampled_rate() {
+        let activity = PoolActivity {
+            seconds: 100.0,
+            fee_growth_0: 1e-3,
+            fee_growth_1: 2e-3,
+            ..Default::default()
+        };
+        // price 4: 6e-3 of token1 per unit of liquidity over 100s
+        let income = get_expected_fee_income(
+            &activity,
+            2 * fixed_point_64::Q64,
+            1_000_000,
+            Duration::from_secs(1_000),
+        );
+        assert!((income - 60_000.0).abs() < 1e-6, "{}", income);
+        assert_eq!(
+            get_expected_fee_income(
+                &PoolActivity::default(),
+                fixed_point_64::Q64,
+                1_000_000,
+                Duration::from_secs(1_000)
+            ),
+            0.0
+        );
+    }
+
+    #[test]
+    fn cooldown_follows_moves_and_skips() {
+        let (cooldown, now) = (Duration::from_secs(3600), Instant::now());
+        let later = now + cooldown;
+        let mut cooldowns = HashMap::new();
+        let nft_mint = Pubkey::new_unique();
+
+        assert_eq!(
+            start_cooldown(&mut cooldowns, nft_mint, RebalanceAction::DryRun, None, now),
+            nft_mint
+        );
+        assert!(!is_cooling_down(&cooldowns, &nft_mint, cooldown, now));
+
+        for action in [
+            RebalanceAction::SkippedCost,
+            RebalanceAction::SkippedUnprofitable,
+            RebalanceAction::SkippedBalance,
+        ] {
+            let nft_mint = Pubkey::new_unique();
+            assert_eq!(
+                start_cooldown(&mut cooldowns, nft_mint, action, None, now),
+                nft_mint
+            );
+            assert!(is_cooling_down(&cooldowns, &nft_mint, cooldown, now));
+            assert!(!is_cooling_down(&cooldowns, &nft_mint, cooldown, later));
+        }
+
+        // the new position takes over the cooldown
+        let new_nft_mint = Pubkey::new_unique();
+        start_cooldown(
+            &mut cooldowns,
+            nft_mint,
+            RebalanceAction::SkippedCost,
+            None,
+            now,
+        );
+        assert_eq!(
+            start_cooldown(
+                &mut cooldowns,
+                nft_mint,
+                RebalanceAction::Moved,
+                Some(new_nft_mint),
+                now
+            ),
+            new_nft_mint
+        );
+        assert!(!cooldowns.contains_key(&nft_mint));
+        assert!(is_cooling_down(&cooldowns, &new_nft_mint, cooldown, now));
+        assert!(!is_cooling_down(&cooldowns, &new_nft_mint, cooldown, later));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::client::{get_accounts, get_anchor_account};
+use crate::error::{ClientError, Result};
+use crate::utils::serialize_pubkey;
+use raydium_amm_v3::libraries::fixed_point_64;
+use raydium_amm_v3::states::PoolState;
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::from_account;
+use solana_sdk::clock::Clock;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar;
+use spl_token_2022::extension::StateWithExtensions;
+
+/// A reward stream of a pool, decoded from `PoolState.reward_infos`. Amounts are raw units of
+/// `token_mint`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RewardStatus {
+    pub reward_index: u8,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_vault: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub authority: Pubkey,
+    pub open_time: u64,
+    pub end_time: u64,
+    pub last_update_time: u64,
+    pub emissions_per_second: f64,
+    pub total_emissioned: u64,
+    pub claimed: u64,
+    pub vault_amount: u64,
+    /// Vault balance not owed to positions yet, as of `last_update_time`.
+    pub remaining_funds: u64,
+    /// Still to be emitted from now until `end_time`.
+    pub remaining_emission: u64,
+}
+
+/// Initialized rewards of `pool_state`, given their vault balances by reward index.
+pub fn get_reward_statuses(
+    pool_state: &PoolState,
+    vault_amounts: &[u64],
+    now: u64,
+) -> Vec<RewardStatus> {
+    // copied out of the packed account
+    let reward_infos = pool_state.reward_infos;
+    reward_infos
+        .iter()
+        .zip(vault_amounts)
+        .enumerate()
+        .filter(|(_, (reward_info, _))| reward_info.token_mint != Pubkey::default())
+        .map(|(reward_index, (reward_info, vault_amount))| {
+            let emissions_per_second =
+                reward_info.emissions_per_second_x64 as f64 / fixed_point_64::Q64 as f64;
+            let remaining_seconds = reward_info
+                .end_time
+                .saturating_sub(now.max(reward_info.open_time));
+            let unclaimed = reward_info
+                .reward_total_emissioned
+                .saturating_sub(reward_info.reward_claimed);
+            RewardStatus {
+                reward_index: reward_index as u8,
+                token_mint: reward_info.token_mint,
+                token_vault: reward_info.token_vault,
+                authority: reward_info.authority,
+                open_time: reward_info.open_time,
+                end_time: reward_info.end_time,
+                last_update_time: reward_info.last_update_time,
+                emissions_per_second,
+                total_emissioned: reward_info.reward_total_emissioned,
+                claimed: reward_info.reward_claimed,
+                vault_amount: *vault_amount,
+                remaining_funds: vault_amount.saturating_sub(unclaimed),
+                remaining_emission: (emissions_per_second * remaining_seconds as f64).ceil() as u64,
+            }
+        })
+        .collect()
+}
+
+/// Load a pool with its reward vaults and decode its rewards at the cluster time.
+pub async fn get_pool_rewards(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+) -> Result<(PoolState, Vec<RewardStatus>)> {
+    let pool_state = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    // copied out of the packed account
+    let reward_infos = pool_state.reward_infos;
+    let mut keys = reward_infos
+        .iter()
+        .map(|reward_info| reward_info.token_vault)
+        .collect::<Vec<_>>();
+    keys.push(sysvar::clock::id());
+    let accounts = get_accounts(rpc_client, &keys).await?;
+    let (clock_account, vault_accounts) = accounts
+        .split_last()
+        .ok_or_else(|| ClientError::InvalidResponse("no account returned".to_string()))?;
+    let clock = clock_account
+        .as_ref()
+        .and_then(from_account::<Clock, _>)
+        .ok_or_else(|| ClientError::InvalidResponse("undecodable clock sysvar".to_string()))?;
+    let now = u64::try_from(clock.unix_timestamp)
+        .map_err(|err| ClientError::InvalidResponse(format!("cluster time: {}", err)))?;
+    let mut vault_amounts = Vec::with_capacity(vault_accounts.len());
+    for (key, vault_account) in keys.iter().zip(vault_accounts) {
+        // uninitialized rewards have a default vault key
+        if *key == Pubkey::default() {
+            vault_amounts.push(0);
+            continue;
+        }
+        let vault_account = vault_account
+            .as_ref()
+            .ok_or(ClientError::AccountNotFound(*key))?;
+        vault_amounts.push(
+            StateWithExtensions::<spl_token_2022::state::Account>::unpack(&vault_account.data)?
+                .base
+                .amount,
+        );
+    }
+    let reward_statuses = get_reward_statuses(&pool_state, &vault_amounts, now);
+    Ok((pool_state, reward_statuses))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_amm_v3::states::RewardInfo;
+
+    fn reward_info(open_time: u64, end_time: u64, emissions_per_second_x64: u128) -> RewardInfo {
+        RewardInfo {
+            open_time,
+            end_time,
+            last_update_time: open_time,
+            emissions_per_second_x64,
+            token_mint: Pubkey::new_unique(),
+            token_vault: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reward_statuses_from_now_to_the_end_time() {
+        let q64 = fixed_point_64::Q64;
+        let mut active = reward_info(1_000, 2_000, 2 * q64);
+        active.reward_total_emissioned = 1_000;
+        active.reward_claimed = 400;
+        let mut ended = reward_info(0, 1_200, q64);
+        ended.reward_total_emissioned = 1_200;
+        ended.reward_claimed = 1_200;
+        let pending = reward_info(3_000, 3_999, 3 * q64 / 4);
+        let mut pool_state = PoolState::default();
+        pool_state.reward_infos = [active, ended, pending];
+
+        let statuses = get_reward_statuses(&pool_state, &[5_000, 100, 400], 1_500);
+        let remaining = statuses
+            .iter()
+            .map(|status| {
+                (
+                    status.emissions_per_second,
+                    status.remaining_funds,
+                    status.remaining_emission,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            remaining,
+            vec![
+                // 500s left, 600 owed to positions
+                (2.0, 4_400, 1_000),
+                // nothing left to emit nor owed
+                (1.0, 100, 0),
+                // the whole 999s, rounded up
+                (0.75, 400, 750),
+            ]
+        );
+        // copied out of the packed account
+        let token_mint = active.token_mint;
+        assert_eq!(statuses[0].token_mint, token_mint);
+        assert_eq!(
+            (statuses[0].total_emissioned, statuses[0].claimed),
+            (1_000, 400)
+        );
+    }
+
+    #[test]
+    fn skips_uninitialized_rewards() {
+        let mut pool_state = PoolState::default();
+        let mut reward_infos = pool_state.reward_infos;
+        reward_infos[1] = reward_info(0, 100, fixed_point_64::Q64);
+        reward_infos[1].reward_total_emissioned = 50;
+        pool_state.reward_infos = reward_infos;
+
+        let statuses = get_reward_statuses(&pool_state, &[0, 10, 0], 50);
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].reward_index, 1);
+        // more owed than the vault holds
+        assert_eq!(statuses[0].remaining_funds, 0);
+        assert_eq!(statuses[0].remaining_emission, 50);
+    }
+}
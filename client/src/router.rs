@@ -0,0 +1,757 @@
+use crate::client::get_epoch;
+use crate::config::ClientConfig;
+use crate::discovery::{find_pools_by_mint, PoolInfo};
+use crate::error::{ClientError, Result};
+use crate::instructions::{
+    close_wsol_account_instr, create_ata_token_account_instr, swap_router_base_in_instr,
+    swap_v2_instr,
+};
+use crate::pda::{get_tick_array_key, get_tickarray_bitmap_extension_key};
+use crate::quote::{quote_exact_input, SwapQuote};
+use crate::tick_array::get_pool_tick_arrays;
+use crate::token::{get_mint_infos, MintInfo};
+use crate::transfer_fee::post_fee_amount;
+use crate::utils::{amount_with_slippage, fits_in_transaction, is_native_mint, wsol_shortfall};
+use futures::future::join_all;
+use raydium_amm_v3::states::{PoolState, TickArrayState};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Parts an input is cut into when splitting it across routes.
+const SPLIT_PARTS: u64 = 10;
+/// Routes an input is split across at most. Two routes of two hops do not always fit in one
+/// transaction, whose size is checked once built.
+const MAX_SPLIT_ROUTES: usize = 2;
+
+/// A pool with everything needed to quote it locally.
+#[derive(Clone, Debug)]
+pub struct RoutePool {
+    pub pool_id: Pubkey,
+    pub pool_state: PoolState,
+    pub trade_fee_rate: u32,
+    pub tick_arrays: BTreeMap<i32, TickArrayState>,
+}
+
+/// One swap of a route. `amount_in` leaves and `amount_out` reaches the user's token
+/// accounts, transfer fees included, while `quote` is on the vaults' side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hop {
+    pub pool_id: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub zero_for_one: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub quote: SwapQuote,
+}
+
+/// Swaps through one pool, or through two with an intermediate mint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub hops: Vec<Hop>,
+}
+
+/// Pools on a direct or two hop path from `input_mint` to `output_mint`, found through pool
+/// discovery, with their tick arrays and the mints they trade.
+#[derive(Clone, Debug)]
+pub struct Router {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub pools: HashMap<Pubkey, RoutePool>,
+    pub mint_infos: HashMap<Pubkey, MintInfo>,
+    pub epoch: u64,
+}
+
+fn other_mint(pool_info: &PoolInfo, mint: &Pubkey) -> Pubkey {
+    if pool_info.token_mint_0 == *mint {
+        pool_info.token_mint_1
+    } else {
+        pool_info.token_mint_0
+    }
+}
+
+impl Router {
+    pub async fn load(
+        rpc_client: &RpcClient,
+        raydium_v3_program: &Pubkey,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+    ) -> Result<Self> {
+        if input_mint == output_mint {
+            return Err(ClientError::InvalidArgument(format!(
+                "cannot swap {} into itself",
+                input_mint
+            )));
+        }
+        let (input_pools, output_pools) = tokio::try_join!(
+            find_pools_by_mint(rpc_client, raydium_v3_program, &input_mint),
+            find_pools_by_mint(rpc_client, raydium_v3_program, &output_mint)
+        )?;
+        let input_sides = input_pools
+            .iter()
+            .map(|pool_info| other_mint(pool_info, &input_mint))
+            .collect::<HashSet<_>>();
+        let output_sides = output_pools
+            .iter()
+            .map(|pool_info| other_mint(pool_info, &output_mint))
+            .collect::<HashSet<_>>();
+        // direct pools are found from both sides
+        let mut pool_infos = BTreeMap::new();
+        for pool_info in input_pools {
+            let mint = other_mint(&pool_info, &input_mint);
+            if mint == output_mint || output_sides.contains(&mint) {
+                pool_infos.insert(pool_info.pool_id, pool_info);
+            }
+        }
+        for pool_info in output_pools {
+            let mint = other_mint(&pool_info, &output_mint);
+            if mint == input_mint || input_sides.contains(&mint) {
+                pool_infos.insert(pool_info.pool_id, pool_info);
+            }
+        }
+
+        let mints = pool_infos
+            .values()
+            .flat_map(|pool_info| [pool_info.token_mint_0, pool_info.token_mint_1])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let (pool_tick_arrays, mint_infos, epoch) = tokio::join!(
+            join_all(pool_infos.keys().map(|pool_id| get_pool_tick_arrays(
+                rpc_client,
+                raydium_v3_program,
+                pool_id
+            ))),
+            get_mint_infos(rpc_client, &mints),
+            get_epoch(rpc_client)
+        );
+        let (mint_infos, epoch) = (mint_infos?, epoch?);
+        // a pool failing to load is left out of the routes rather than failing them all, and
+        // one without any tick array has no liquidity anywhere to swap against
+        let pools = pool_infos
+            .values()
+            .zip(pool_tick_arrays)
+            .filter_map(|(pool_info, tick_arrays)| match tick_arrays {
+                Ok((pool_state, tick_arrays)) if !tick_arrays.is_empty() => Some((
+                    pool_info.pool_id,
+                    RoutePool {
+                        pool_id: pool_info.pool_id,
+                        pool_state,
+                        trade_fee_rate: pool_info.trade_fee_rate,
+                        tick_arrays,
+                    },
+                )),
+                Ok(_) => None,
+                Err(err) => {
+                    log::warn!("skipping pool {}: {}", pool_info.pool_id, err);
+                    None
+                }
+            })
+            .collect();
+        Ok(Self {
+            input_mint,
+            output_mint,
+            pools,
+            mint_infos: mints.into_iter().zip(mint_infos).collect(),
+            epoch,
+        })
+    }
+
+    fn pool(&self, pool_id: &Pubkey) -> Result<&RoutePool> {
+        self.pools
+            .get(pool_id)
+            .ok_or(ClientError::AccountNotFound(*pool_id))
+    }
+
+    fn mint_info(&self, mint: &Pubkey) -> Result<&MintInfo> {
+        self.mint_infos
+            .get(mint)
+            .ok_or(ClientError::AccountNotFound(*mint))
+    }
+
+    /// Every direct pool and two hop path, as pool ids.
+    pub fn paths(&self) -> Vec<Vec<Pubkey>> {
+        let trades = |pool: &RoutePool, mint: &Pubkey| {
+            pool.pool_state.token_mint_0 == *mint || pool.pool_state.token_mint_1 == *mint
+        };
+        let mut paths = Vec::new();
+        for first in self.pools.values() {
+            if !trades(first, &self.input_mint) {
+                continue;
+            }
+            if trades(first, &self.output_mint) {
+                paths.push(vec![first.pool_id]);
+                continue;
+            }
+            let intermediate_mint = if first.pool_state.token_mint_0 == self.input_mint {
+                first.pool_state.token_mint_1
+            } else {
+                first.pool_state.token_mint_0
+            };
+            for second in self.pools.values() {
+                if trades(second, &intermediate_mint) && trades(second, &self.output_mint) {
+                    paths.push(vec![first.pool_id, second.pool_id]);
+                }
+            }
+        }
+        paths.sort();
+        paths
+    }
+
+    /// Quote swapping `amount_in` through the pools of `path` in turn.
+    pub fn quote_route(&self, path: &[Pubkey], amount_in: u64) -> Result<Route> {
+        let mut input_mint = self.input_mint;
+        let mut amount = amount_in;
+        let mut hops = Vec::with_capacity(path.len());
+        for pool_id in path {
+            let pool = self.pool(pool_id)?;
+            let zero_for_one = pool.pool_state.token_mint_0 == input_mint;
+            let output_mint = if zero_for_one {
+                pool.pool_state.token_mint_1
+            } else if pool.pool_state.token_mint_1 == input_mint {
+                pool.pool_state.token_mint_0
+            } else {
+                return Err(ClientError::InvalidArgument(format!(
+                    "pool {} does not trade {}",
+                    pool_id, input_mint
+                )));
+            };
+            let quote = quote_exact_input(
+                &pool.pool_state,
+                &pool.tick_arrays,
+                pool.trade_fee_rate,
+                post_fee_amount(self.mint_info(&input_mint)?, self.epoch, amount)?,
+                zero_for_one,
+            )?;
+            let amount_out =
+                post_fee_amount(self.mint_info(&output_mint)?, self.epoch, quote.amount_out)?;
+            hops.push(Hop {
+                pool_id: *pool_id,
+                input_mint,
+                output_mint,
+                zero_for_one,
+                amount_in: amount,
+                amount_out,
+                quote,
+            });
+            input_mint = output_mint;
+            amount = amount_out;
+        }
+        if input_mint != self.output_mint {
+            return Err(ClientError::InvalidArgument(format!(
+                "path ends in {} instead of {}",
+                input_mint, self.output_mint
+            )));
+        }
+        Ok(Route {
+            amount_in,
+            amount_out: amount,
+            hops,
+        })
+    }
+
+    /// The path giving the most output for `amount_in`. Paths without enough liquidity are
+    /// left out.
+    pub fn best_route(&self, amount_in: u64) -> Result<Route> {
+        self.paths()
+            .iter()
+            .filter_map(|path| self.quote_route(path, amount_in).ok())
+            .max_by_key(|route| route.amount_out)
+            .ok_or_else(|| {
+                ClientError::InvalidArgument(format!(
+                    "no route from {} to {} takes {}",
+                    self.input_mint, self.output_mint, amount_in
+                ))
+            })
+    }
+
+    /// Split `amount_in` across up to [`MAX_SPLIT_ROUTES`] paths without a pool in common,
+    /// handing out [`SPLIT_PARTS`] parts one at a time to the path they add the most output
+    /// to. Falls back to [`Router::best_route`] when splitting does not give more.
+    pub fn best_split(&self, amount_in: u64) -> Result<Vec<Route>> {
+        let best_route = self.best_route(amount_in)?;
+        let part = amount_in / SPLIT_PARTS;
+        if part == 0 {
+            return Ok(vec![best_route]);
+        }
+        let paths = self.paths();
+        let mut allocations = vec![0u64; paths.len()];
+        let mut amounts_out = vec![0u64; paths.len()];
+        for index in 0..SPLIT_PARTS {
+            // the last part takes the rounding remainder
+            let part = if index == SPLIT_PARTS - 1 {
+                amount_in - part * (SPLIT_PARTS - 1)
+            } else {
+                part
+            };
+            let used = (0..paths.len())
+                .filter(|i| allocations[*i] != 0)
+                .collect::<Vec<_>>();
+            let mut best: Option<(usize, u64, u64)> = None;
+            for (i, path) in paths.iter().enumerate() {
+                if allocations[i] == 0
+                    && (used.len() >= MAX_SPLIT_ROUTES
+                        || used
+                            .iter()
+                            .any(|j| paths[*j].iter().any(|pool_id| path.contains(pool_id))))
+                {
+                    continue;
+                }
+                let Ok(route) = self.quote_route(path, allocations[i] + part) else {
+                    continue;
+                };
+                let gain = route.amount_out.saturating_sub(amounts_out[i]);
+                if best.is_none_or(|(_, best_gain, _)| gain > best_gain) {
+                    best = Some((i, gain, route.amount_out));
+                }
+            }
+            let Some((i, _, amount_out)) = best else {
+                return Ok(vec![best_route]);
+            };
+            allocations[i] += part;
+            amounts_out[i] = amount_out;
+        }
+
+        let routes = paths
+            .iter()
+            .zip(allocations)
+            .filter(|(_, allocation)| *allocation != 0)
+            .map(|(path, allocation)| self.quote_route(path, allocation))
+            .collect::<Result<Vec<_>>>()?;
+        if routes.iter().map(|route| route.amount_out).sum::<u64>() > best_route.amount_out {
+            Ok(routes)
+        } else {
+            Ok(vec![best_route])
+        }
+    }
+
+    /// Bitmap extension and tick arrays a hop goes through, as remaining accounts.
+    fn tick_array_accounts(&self, raydium_v3_program: &Pubkey, hop: &Hop) -> Vec<AccountMeta> {
+        let mut accounts = vec![AccountMeta::new_readonly(
+            get_tickarray_bitmap_extension_key(raydium_v3_program, &hop.pool_id),
+            false,
+        )];
+        accounts.extend(
+            hop.quote
+                .tick_array_start_indexes
+                .iter()
+                .map(|start_index| {
+                    AccountMeta::new(
+                        get_tick_array_key(raydium_v3_program, &hop.pool_id, *start_index),
+                        false,
+                    )
+                }),
+        );
+        accounts
+    }
+
+    fn user_token_account(&self, owner: &Pubkey, mint: &Pubkey) -> Result<Pubkey> {
+        Ok(get_associated_token_address_with_program_id(
+            owner,
+            mint,
+            &self.mint_info(mint)?.token_program,
+        ))
+    }
+
+    /// `SwapV2` for a single pool, `SwapRouterBaseIn` through the hops otherwise.
+    fn route_instructions(
+        &self,
+        config: &ClientConfig,
+        owner: &Pubkey,
+        route: &Route,
+        slippage: f64,
+    ) -> Result<Vec<Instruction>> {
+        let amount_out_minimum = amount_with_slippage(route.amount_out, slippage, false);
+        if let [hop] = route.hops.as_slice() {
+            let pool_state = &self.pool(&hop.pool_id)?.pool_state;
+            let (input_vault, output_vault) = if hop.zero_for_one {
+                (pool_state.token_vault_0, pool_state.token_vault_1)
+            } else {
+                (pool_state.token_vault_1, pool_state.token_vault_0)
+            };
+            return swap_v2_instr(
+                config,
+                *owner,
+                pool_state.amm_config,
+                hop.pool_id,
+                input_vault,
+                output_vault,
+                pool_state.observation_key,
+                self.user_token_account(owner, &hop.input_mint)?,
+                self.user_token_account(owner, &hop.output_mint)?,
+                hop.input_mint,
+                hop.output_mint,
+                self.tick_array_accounts(&config.raydium_v3_program, hop),
+                route.amount_in,
+                amount_out_minimum,
+                None,
+                true,
+            );
+        }
+
+        // each hop starts with its amm config, which the program looks for
+        let mut remaining_accounts = Vec::new();
+        for hop in &route.hops {
+            let pool_state = &self.pool(&hop.pool_id)?.pool_state;
+            let (input_vault, output_vault) = if hop.zero_for_one {
+                (pool_state.token_vault_0, pool_state.token_vault_1)
+            } else {
+                (pool_state.token_vault_1, pool_state.token_vault_0)
+            };
+            remaining_accounts.extend([
+                AccountMeta::new_readonly(pool_state.amm_config, false),
+                AccountMeta::new(hop.pool_id, false),
+                AccountMeta::new(self.user_token_account(owner, &hop.output_mint)?, false),
+                AccountMeta::new(input_vault, false),
+                AccountMeta::new(output_vault, false),
+                AccountMeta::new_readonly(hop.output_mint, false),
+                AccountMeta::new(pool_state.observation_key, false),
+            ]);
+            remaining_accounts.extend(self.tick_array_accounts(&config.raydium_v3_program, hop));
+        }
+        swap_router_base_in_instr(
+            config,
+            *owner,
+            self.user_token_account(owner, &self.input_mint)?,
+            self.input_mint,
+            remaining_accounts,
+            route.amount_in,
+            amount_out_minimum,
+        )
+    }
+
+    /// Instructions swapping through `routes` for `owner`: creating the token accounts of
+    /// every mint met, wrapping the SOL input `owner`'s WSOL ATA lacks, one swap per route and
+    /// unwrapping WSOL at the end when the ATA did not exist, `wsol_balance` being `None`.
+    /// Each route's output is held to its quote less `slippage`.
+    pub fn swap_instructions(
+        &self,
+        config: &ClientConfig,
+        owner: &Pubkey,
+        routes: &[Route],
+        slippage: f64,
+        wsol_balance: Option<u64>,
+    ) -> Result<Vec<Instruction>> {
+        let amount_in = routes.iter().map(|route| route.amount_in).sum::<u64>();
+        let mut instructions = create_ata_token_account_instr(
+            owner,
+            owner,
+            &self.input_mint,
+            &self.mint_info(&self.input_mint)?.token_program,
+            wsol_shortfall(amount_in, wsol_balance),
+        )?;
+        let output_mints = routes
+            .iter()
+            .flat_map(|route| route.hops.iter().map(|hop| hop.output_mint))
+            .collect::<BTreeSet<_>>();
+        for mint in &output_mints {
+            instructions.extend(create_ata_token_account_instr(
+                owner,
+                owner,
+                mint,
+                &self.mint_info(mint)?.token_program,
+                0,
+            )?);
+        }
+        for route in routes {
+            instructions.extend(self.route_instructions(config, owner, route, slippage)?);
+        }
+        let uses_sol = is_native_mint(&self.input_mint) || output_mints.iter().any(is_native_mint);
+        if uses_sol && wsol_balance.is_none() {
+            instructions.extend(close_wsol_account_instr(owner)?);
+        }
+        Ok(instructions)
+    }
+
+    /// [`Router::swap_instructions`] of `routes`, or of the best single route for the same
+    /// input when the split across `routes` does not fit in one transaction, with the routes
+    /// taken. Fails when even a single route does not fit.
+    pub fn fitting_swap_instructions(
+        &self,
+        config: &ClientConfig,
+        owner: &Pubkey,
+        mut routes: Vec<Route>,
+        slippage: f64,
+        wsol_balance: Option<u64>,
+    ) -> Result<(Vec<Route>, Vec<Instruction>)> {
+        let mut instructions =
+            self.swap_instructions(config, owner, &routes, slippage, wsol_balance)?;
+        if routes.len() > 1 && !fits_in_transaction(&instructions, owner) {
+            log::warn!("split routes do not fit in one transaction, taking the best route");
+            let amount_in = routes.iter().map(|route| route.amount_in).sum();
+            routes = vec![self.best_route(amount_in)?];
+            instructions =
+                self.swap_instructions(config, owner, &routes, slippage, wsol_balance)?;
+        }
+        if !fits_in_transaction(&instructions, owner) {
+            return Err(ClientError::InvalidArgument(
+                "the route does not fit in one transaction".to_string(),
+            ));
+        }
+        Ok((routes, instructions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_amm_v3::libraries::tick_math;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    fn tick_array(tick: i32, liquidity_net: i128) -> TickArrayState {
+        let mut tick_array = TickArrayState::default();
+        // copied out of the packed account
+        let mut tick_states = tick_array.ticks;
+        tick_states[0].tick = tick;
+        tick_states[0].liquidity_net = liquidity_net;
+        tick_states[0].liquidity_gross = liquidity_net.unsigned_abs();
+        tick_array.ticks = tick_states;
+        tick_array
+    }
+
+    /// A pool at price 1 with `liquidity` over [-500, 500].
+    fn pool(token_mint_0: Pubkey, token_mint_1: Pubkey, liquidity: u128) -> RoutePool {
+        let mut pool_state = PoolState::default();
+        pool_state.amm_config = Pubkey::new_unique();
+        pool_state.token_mint_0 = token_mint_0;
+        pool_state.token_mint_1 = token_mint_1;
+        pool_state.token_vault_0 = Pubkey::new_unique();
+        pool_state.token_vault_1 = Pubkey::new_unique();
+        pool_state.observation_key = Pubkey::new_unique();
+        pool_state.tick_spacing = 10;
+        pool_state.tick_current = 0;
+        pool_state.sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(0).unwrap();
+        pool_state.liquidity = liquidity;
+        let liquidity = liquidity as i128;
+        RoutePool {
+            pool_id: Pubkey::new_unique(),
+            pool_state,
+            trade_fee_rate: 2_500,
+            tick_arrays: BTreeMap::from([
+                (-600, tick_array(-500, liquidity)),
+                (0, tick_array(500, -liquidity)),
+            ]),
+        }
+    }
+
+    fn mint_info(mint: Pubkey) -> MintInfo {
+        MintInfo {
+            mint,
+            token_program: spl_token::id(),
+            decimals: 6,
+            extensions: Vec::new(),
+            transfer_fee_config: None,
+            interest_bearing_config: None,
+            transfer_hook_program: None,
+        }
+    }
+
+    /// Mints A, B and C, and a router from A to B through `pools`.
+    fn router(mints: [Pubkey; 3], pools: &[&RoutePool]) -> Router {
+        Router {
+            input_mint: mints[0],
+            output_mint: mints[1],
+            pools: pools
+                .iter()
+                .map(|pool| (pool.pool_id, (*pool).clone()))
+                .collect(),
+            mint_infos: mints
+                .into_iter()
+                .map(|mint| (mint, mint_info(mint)))
+                .collect(),
+            epoch: 0,
+        }
+    }
+
+    fn mints() -> [Pubkey; 3] {
+        [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ]
+    }
+
+    fn config() -> ClientConfig {
+        ClientConfig {
+            http_url: "http://localhost:8899".to_string(),
+            ws_url: "ws://localhost:8900".to_string(),
+            payer_path: String::new(),
+            admin_path: String::new(),
+            raydium_v3_program: Pubkey::new_unique(),
+            slippage: 0.01,
+            amm_config_key: Pubkey::new_unique(),
+            mint0: None,
+            mint1: None,
+            pool_id_account: None,
+            tickarray_bitmap_extension: None,
+            amm_config_index: 0,
+            protocol_fee_recipient: None,
+            fund_fee_recipient: None,
+        }
+    }
+
+    #[test]
+    fn paths_go_direct_or_through_one_mint() {
+        let [a, b, c] = mints();
+        let direct = pool(a, b, LIQUIDITY);
+        // the other way around
+        let first = pool(c, a, LIQUIDITY);
+        let second = pool(c, b, LIQUIDITY);
+        let unrelated = pool(c, Pubkey::new_unique(), LIQUIDITY);
+        let router = router([a, b, c], &[&direct, &first, &second, &unrelated]);
+
+        let mut expected = vec![vec![direct.pool_id], vec![first.pool_id, second.pool_id]];
+        expected.sort();
+        assert_eq!(router.paths(), expected);
+    }
+
+    #[test]
+    fn quotes_a_route_hop_by_hop() {
+        let [a, b, c] = mints();
+        let first = pool(a, c, LIQUIDITY);
+        let second = pool(b, c, LIQUIDITY);
+        let router = router([a, b, c], &[&first, &second]);
+
+        let route = router
+            .quote_route(&[first.pool_id, second.pool_id], 1_000_000)
+            .unwrap();
+        let first_quote = quote_exact_input(
+            &first.pool_state,
+            &first.tick_arrays,
+            2_500,
+            1_000_000,
+            true,
+        )
+        .unwrap();
+        let second_quote = quote_exact_input(
+            &second.pool_state,
+            &second.tick_arrays,
+            2_500,
+            first_quote.amount_out,
+            false,
+        )
+        .unwrap();
+        assert_eq!(route.amount_in, 1_000_000);
+        assert_eq!(route.amount_out, second_quote.amount_out);
+        assert_eq!(
+            route.hops,
+            vec![
+                Hop {
+                    pool_id: first.pool_id,
+                    input_mint: a,
+                    output_mint: c,
+                    zero_for_one: true,
+                    amount_in: 1_000_000,
+                    amount_out: first_quote.amount_out,
+                    quote: first_quote,
+                },
+                Hop {
+                    pool_id: second.pool_id,
+                    input_mint: c,
+                    output_mint: b,
+                    zero_for_one: false,
+                    amount_in: route.hops[0].amount_out,
+                    amount_out: second_quote.amount_out,
+                    quote: second_quote,
+                },
+            ]
+        );
+
+        // a pool not trading the input, and a path stopping short of the output
+        assert!(router.quote_route(&[second.pool_id], 1_000_000).is_err());
+        assert!(router.quote_route(&[first.pool_id], 1_000_000).is_err());
+    }
+
+    #[test]
+    fn best_route_gives_the_most_output() {
+        let [a, b, c] = mints();
+        let shallow = pool(a, b, LIQUIDITY / 1_000);
+        let first = pool(a, c, LIQUIDITY);
+        let second = pool(c, b, LIQUIDITY);
+        let router = router([a, b, c], &[&shallow, &first, &second]);
+
+        // two fees through deep pools beat one through a shallow pool for a large input
+        let two_hops = router
+            .quote_route(&[first.pool_id, second.pool_id], 100_000_000)
+            .unwrap();
+        assert_eq!(router.best_route(100_000_000).unwrap(), two_hops);
+        // not for a small one
+        let direct = router.quote_route(&[shallow.pool_id], 1_000).unwrap();
+        assert_eq!(router.best_route(1_000).unwrap(), direct);
+        // nor past what every path takes
+        assert!(router.best_route(100_000_000_000).is_err());
+    }
+
+    #[test]
+    fn best_split_spreads_an_input_across_pools() {
+        let [a, b, c] = mints();
+        let first = pool(a, b, LIQUIDITY / 1_000);
+        let second = pool(a, b, LIQUIDITY / 1_000);
+        let router = router([a, b, c], &[&first, &second]);
+
+        let routes = router.best_split(10_000_000).unwrap();
+        // equal pools take half of the input each
+        assert_eq!(
+            routes
+                .iter()
+                .map(|route| route.amount_in)
+                .collect::<Vec<_>>(),
+            vec![5_000_000, 5_000_000]
+        );
+        assert!(
+            routes.iter().map(|route| route.amount_out).sum::<u64>()
+                > router.best_route(10_000_000).unwrap().amount_out
+        );
+        // inputs too small to cut in parts are not split
+        assert_eq!(
+            router.best_split(9).unwrap(),
+            vec![router.best_route(9).unwrap()]
+        );
+    }
+
+    #[test]
+    fn best_split_keeps_a_much_better_route_whole() {
+        let [a, b, c] = mints();
+        let deep = pool(a, b, LIQUIDITY);
+        let shallow = pool(a, b, 1_000);
+        let router = router([a, b, c], &[&deep, &shallow]);
+
+        assert_eq!(
+            router.best_split(1_000_000).unwrap(),
+            vec![router.quote_route(&[deep.pool_id], 1_000_000).unwrap()]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_one_route_when_the_split_does_not_fit() {
+        let [a, b, c] = mints();
+        let pools = [
+            pool(a, c, LIQUIDITY / 1_000),
+            pool(c, b, LIQUIDITY / 1_000),
+            pool(a, c, LIQUIDITY / 1_000),
+            pool(c, b, LIQUIDITY / 1_000),
+        ];
+        let router = router([a, b, c], &pools.iter().collect::<Vec<_>>());
+        let (config, owner) = (config(), Pubkey::new_unique());
+
+        // two routes of two hops through four pools
+        let routes = router.best_split(10_000_000).unwrap();
+        assert_eq!(routes.len(), 2);
+        let instructions = router
+            .swap_instructions(&config, &owner, &routes, 0.01, None)
+            .unwrap();
+        assert!(!fits_in_transaction(&instructions, &owner));
+
+        let (taken, instructions) = router
+            .fitting_swap_instructions(&config, &owner, routes, 0.01, None)
+            .unwrap();
+        assert_eq!(taken, vec![router.best_route(10_000_000).unwrap()]);
+        assert!(fits_in_transaction(&instructions, &owner));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::cache::AccountCache;
+use crate::client::{deserialize_anchor_account, get_accounts_with_slot};
+use crate::error::{ClientError, Result};
+use crate::pda::{get_tick_array_key, get_tickarray_bitmap_extension_key};
+use crate::tick_array::get_all_initialized_tick_array_start_indexes;
+use raydium_amm_v3::states::{
+    ObservationState, PoolState, TickArrayBitmapExtension, TickArrayState,
+};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Attempts at reading the tick arrays without the set of initialized ones changing.
+const SNAPSHOT_ATTEMPTS: usize = 3;
+
+/// Every account needed to quote against a pool, read at `slot` or later: the pool, its amm
+/// config, observation, bitmap extension, mints and initialized tick arrays.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+    pub slot: u64,
+    pub pool_id: Pubkey,
+    pub accounts: BTreeMap<Pubkey, Account>,
+}
+
+/// JSON layout of a snapshot, with accounts encoded like RPC responses.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolSnapshotFile {
+    slot: u64,
+    pool_id: String,
+    accounts: BTreeMap<String, UiAccount>,
+}
+
+impl PoolSnapshot {
+    fn account(&self, key: &Pubkey) -> Result<&Account> {
+        self.accounts
+            .get(key)
+            .ok_or(ClientError::AccountNotFound(*key))
+    }
+
+    pub fn pool_state(&self) -> Result<PoolState> {
+        deserialize_anchor_account(self.account(&self.pool_id)?)
+    }
+
+    pub fn observation_state(&self) -> Result<ObservationState> {
+        deserialize_anchor_account(self.account(&self.pool_state()?.observation_key)?)
+    }
+
+    /// Bitmap extension of the pool, `None` when it has not been created.
+    pub fn tickarray_bitmap_extension(
+        &self,
+        raydium_v3_program: &Pubkey,
+    ) -> Result<Option<TickArrayBitmapExtension>> {
+        self.accounts
+            .get(&get_tickarray_bitmap_extension_key(
+                raydium_v3_program,
+                &self.pool_id,
+            ))
+            .map(deserialize_anchor_account)
+            .transpose()
+    }
+
+    /// Tick arrays of the pool, by start index.
+    pub fn tick_arrays(&self) -> BTreeMap<i32, TickArrayState> {
+        self.accounts
+            .values()
+            .filter_map(|account| deserialize_anchor_account::<TickArrayState>(account).ok())
+            .filter(|tick_array| tick_array.pool_id == self.pool_id)
+            .map(|tick_array| (tick_array.start_tick_index, tick_array))
+            .collect()
+    }
+
+    /// Seed `cache` with every account of the snapshot, as read at its slot. A bitmap
+    /// extension missing from the snapshot is cached as missing, rather than fetched.
+    pub fn load_into(&self, cache: &AccountCache) {
+        for (key, account) in &self.accounts {
+            cache.insert(*key, Some(account.clone()), self.slot);
+        }
+        if let Some(pool_account) = self.accounts.get(&self.pool_id) {
+            // the pool is owned by the program its extension derives from
+            let tickarray_bitmap_extension_key =
+                get_tickarray_bitmap_extension_key(&pool_account.owner, &self.pool_id);
+            if !self.accounts.contains_key(&tickarray_bitmap_extension_key) {
+                cache.insert(tickarray_bitmap_extension_key, None, self.slot);
+            }
+        }
+    }
+
+    /// Write to `path`, as JSON when it ends with `.json` and as bincode otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let data = if is_json(path) {
+            let file = PoolSnapshotFile {
+                slot: self.slot,
+                pool_id: self.pool_id.to_string(),
+                accounts: self
+                    .accounts
+                    .iter()
+                    .map(|(key, account)| {
+                        (
+                            key.to_string(),
+                            UiAccount::encode(key, account, UiAccountEncoding::Base64, None, None),
+                        )
+                    })
+                    .collect(),
+            };
+            serde_json::to_vec_pretty(&file)
+                .map_err(|err| ClientError::Snapshot(err.to_string()))?
+        } else {
+            bincode::serialize(self).map_err(|err| ClientError::Snapshot(err.to_string()))?
+        };
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Read a snapshot written by [`PoolSnapshot::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        if !is_json(path) {
+            return bincode::deserialize(&data)
+                .map_err(|err| ClientError::Snapshot(err.to_string()));
+        }
+        let file: PoolSnapshotFile =
+            serde_json::from_slice(&data).map_err(|err| ClientError::Snapshot(err.to_string()))?;
+        let mut accounts = BTreeMap::new();
+        for (key, ui_account) in file.accounts {
+            let account = ui_account
+                .decode::<Account>()
+                .ok_or_else(|| ClientError::Snapshot(format!("undecodable account {}", key)))?;
+            accounts.insert(parse_pubkey(&key)?, account);
+        }
+        Ok(Self {
+            slot: file.slot,
+            pool_id: parse_pubkey(&file.pool_id)?,
+            accounts,
+        })
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+fn parse_pubkey(key: &str) -> Result<Pubkey> {
+    Pubkey::from_str(key).map_err(|err| ClientError::Snapshot(format!("{}: {}", key, err)))
+}
+
+/// Read a [`PoolSnapshot`] of `pool_id`. The tick arrays to read follow from the pool and
+/// bitmap extension, which are read again along with them; the snapshot is retried when
+/// either bitmap changed in between.
+pub async fn fetch_pool_snapshot(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    pool_id: &Pubkey,
+) -> Result<PoolSnapshot> {
+    let tickarray_bitmap_extension_key =
+        get_tickarray_bitmap_extension_key(raydium_v3_program, pool_id);
+    for _ in 0..SNAPSHOT_ATTEMPTS {
+        let (min_slot, accounts) = get_accounts_with_slot(
+            rpc_client,
+            &[*pool_id, tickarray_bitmap_extension_key],
+            None,
+        )
+        .await?;
+        let pool_account = accounts[0]
+            .as_ref()
+            .ok_or(ClientError::AccountNotFound(*pool_id))?;
+        // `None` when the pool has not created it, as in the snapshot
+        let tickarray_bitmap_extension_account = accounts[1].as_ref();
+        let pool = deserialize_anchor_account::<PoolState>(pool_account)?;
+        let tickarray_bitmap_extension = tickarray_bitmap_extension_account
+            .map(deserialize_anchor_account::<TickArrayBitmapExtension>)
+            .transpose()?;
+        let start_indexes = get_all_initialized_tick_array_start_indexes(
+            &pool,
+            tickarray_bitmap_extension.as_ref(),
+        )?;
+
+        let mut keys = vec![
+            *pool_id,
+            tickarray_bitmap_extension_key,
+            pool.amm_config,
+            pool.observation_key,
+            pool.token_mint_0,
+            pool.token_mint_1,
+        ];
+        keys.extend(
+            start_indexes
+                .iter()
+                .map(|start_index| get_tick_array_key(raydium_v3_program, pool_id, *start_index)),
+        );
+        let (slot, accounts) = get_accounts_with_slot(rpc_client, &keys, Some(min_slot)).await?;
+        let latest_pool = deserialize_anchor_account::<PoolState>(
+            accounts[0]
+                .as_ref()
+                .ok_or(ClientError::AccountNotFound(*pool_id))?,
+        )?;
+        // copied out of the packed accounts before comparing
+        let (tick_array_bitmap, latest_tick_array_bitmap) =
+            (pool.tick_array_bitmap, latest_pool.tick_array_bitmap);
+        if latest_tick_array_bitmap != tick_array_bitmap
+            || accounts[1].as_ref().map(|account| &account.data)
+                != tickarray_bitmap_extension_account.map(|account| &account.data)
+        {
+            continue;
+        }
+        return Ok(PoolSnapshot {
+            slot,
+            pool_id: *pool_id,
+            accounts: keys
+                .into_iter()
+                .zip(accounts)
+                .filter_map(|(key, account)| account.map(|account| (key, account)))
+                .collect(),
+        });
+    }
+    Err(ClientError::Snapshot(format!(
+        "tick arrays of pool {} kept changing",
+        pool_id
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> PoolSnapshot {
+        let pool_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let accounts = (0..3u8)
+            .map(|index| {
+                let account = Account {
+                    lamports: 1_000_000 + u64::from(index),
+                    data: vec![index; 8 + usize::from(index)],
+                    owner,
+                    executable: false,
+                    rent_epoch: u64::from(index),
+                };
+                (Pubkey::new_unique(), account)
+            })
+            .chain([(pool_id, Account::new(42, 16, &owner))])
+            .collect();
+        PoolSnapshot {
+            slot: 123_456,
+            pool_id,
+            accounts,
+        }
+    }
+
+    fn round_trip(file_name: &str) {
+        let path = std::env::temp_dir().join(format!("{}-{}", Pubkey::new_unique(), file_name));
+        let snapshot = snapshot();
+        snapshot.save(&path).unwrap();
+        let loaded = PoolSnapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), snapshot);
+    }
+
+    #[test]
+    fn json_snapshot_round_trips() {
+        round_trip("snapshot.json");
+    }
+
+    #[test]
+    fn bincode_snapshot_round_trips() {
+        round_trip("snapshot.bin");
+    }
+
+    #[test]
+    fn loaded_snapshot_is_served_from_the_cache() {
+        let snapshot = snapshot();
+        let cache = AccountCache::new(None);
+        snapshot.load_into(&cache);
+        for (key, account) in &snapshot.accounts {
+            assert_eq!(
+                cache.get_cached(key),
+                Some((Some(account.clone()), snapshot.slot))
+            );
+        }
+        // without its bitmap extension, which the pool has not created
+        let owner = snapshot.accounts[&snapshot.pool_id].owner;
+        assert_eq!(
+            cache.get_cached(&get_tickarray_bitmap_extension_key(
+                &owner,
+                &snapshot.pool_id
+            )),
+            Some((None, snapshot.slot))
+        );
+        assert_eq!(cache.len(), snapshot.accounts.len() + 1);
+    }
+}
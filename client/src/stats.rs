@@ -0,0 +1,492 @@
+use crate::client::get_anchor_account;
+use crate::discovery::{get_pool_infos, PoolInfo};
+use crate::error::{ClientError, Result};
+use crate::range::{get_range_ticks, RangeStrategy};
+use crate::transactions::unix_now;
+use crate::utils::{multipler, serialize_display, serialize_pubkey, sqrt_price_x64_to_f64};
+use raydium_amm_v3::libraries::{fixed_point_64, tick_math};
+use raydium_amm_v3::states::{AmmConfig, PoolState, FEE_RATE_DENOMINATOR_VALUE};
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub(crate) const SECONDS_PER_DAY: i64 = 24 * 3600;
+const SECONDS_PER_WEEK: i64 = 7 * SECONDS_PER_DAY;
+const SECONDS_PER_YEAR: f64 = 365.0 * SECONDS_PER_DAY as f64;
+
+/// Where the swaps of a pool are taken from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatsSource {
+    /// A database written by the indexer.
+    Indexed(String),
+    /// Two reads of the pool this far apart, extrapolated to a day and a week.
+    Sampled(Duration),
+}
+
+/// Swaps of a pool over a period.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolActivity {
+    pub seconds: f64,
+    /// Raw amounts swapped in, trade fees included.
+    pub amount_in_0: f64,
+    pub amount_in_1: f64,
+    /// Raw trade fees paid to liquidity providers per unit of in-range liquidity.
+    pub fee_growth_0: f64,
+    pub fee_growth_1: f64,
+}
+
+impl PoolActivity {
+    /// The same rate of activity over `seconds`.
+    pub fn extrapolate(&self, seconds: i64) -> Self {
+        let ratio = if self.seconds > 0.0 {
+            seconds as f64 / self.seconds
+        } else {
+            0.0
+        };
+        Self {
+            seconds: seconds as f64,
+            amount_in_0: self.amount_in_0 * ratio,
+            amount_in_1: self.amount_in_1 * ratio,
+            fee_growth_0: self.fee_growth_0 * ratio,
+            fee_growth_1: self.fee_growth_1 * ratio,
+        }
+    }
+}
+
+/// Share of the trade fee left to liquidity providers.
+fn lp_fee_share(amm_config: &AmmConfig) -> f64 {
+    1.0 - (amm_config.protocol_fee_rate + amm_config.fund_fee_rate) as f64
+        / FEE_RATE_DENOMINATOR_VALUE as f64
+}
+
+/// Activity of `pool_id` over each of `periods` in seconds ending at `end`, from the swaps a
+/// database written by the indexer holds. Also returns the block time of the newest indexed
+/// swap, past which the activity is unknown.
+pub fn get_indexed_activity(
+    path: &str,
+    pool_id: &Pubkey,
+    amm_config: &AmmConfig,
+    end: i64,
+    periods: &[i64],
+) -> Result<(i64, Vec<PoolActivity>)> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let pool = pool_id.to_string();
+    let indexed_until: Option<i64> = connection.query_row(
+        "SELECT MAX(block_time) FROM swaps WHERE pool = ?1",
+        params![pool],
+        |row| row.get(0),
+    )?;
+    let indexed_until = indexed_until.ok_or_else(|| {
+        ClientError::InvalidArgument(format!("no swap of pool {} in {}", pool_id, path))
+    })?;
+    let lp_fee_rate = amm_config.trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64
+        * lp_fee_share(amm_config);
+
+    let mut statement = connection.prepare(
+        "SELECT zero_for_one, amount_0, amount_1, liquidity FROM swaps \
+         WHERE pool = ?1 AND block_time > ?2 AND block_time <= ?3",
+    )?;
+    let mut activities = Vec::with_capacity(periods.len());
+    for seconds in periods {
+        let mut activity = PoolActivity {
+            seconds: *seconds as f64,
+            ..PoolActivity::default()
+        };
+        let rows = statement.query_map(params![pool, end - seconds, end], |row| {
+            Ok((
+                row.get::<_, bool>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, u64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        for row in rows {
+            let (zero_for_one, amount_0, amount_1, liquidity) = row?;
+            let liquidity = liquidity.parse::<u128>().map_err(|_| {
+                ClientError::InvalidResponse(format!("invalid liquidity {}", liquidity))
+            })?;
+            // the fee is shared by the liquidity in range, taken after the swap
+            let fee_growth = |amount_in: u64| {
+                if liquidity == 0 {
+                    0.0
+                } else {
+                    amount_in as f64 * lp_fee_rate / liquidity as f64
+                }
+            };
+            if zero_for_one {
+                activity.amount_in_0 += amount_0 as f64;
+                activity.fee_growth_0 += fee_growth(amount_0);
+            } else {
+                activity.amount_in_1 += amount_1 as f64;
+                activity.fee_growth_1 += fee_growth(amount_1);
+            }
+        }
+        activities.push(activity);
+    }
+    Ok((indexed_until, activities))
+}
+
+/// Activity of `pool_id` between two reads `interval` apart, from the changes of its swapped
+/// amounts and fee growth.
+pub async fn sample_activity(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    interval: Duration,
+) -> Result<PoolActivity> {
+    let before = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    let start = Instant::now();
+    tokio::time::sleep(interval).await;
+    let after = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    let delta = |after: u128, before: u128| after.wrapping_sub(before) as f64;
+    Ok(PoolActivity {
+        seconds: start.elapsed().as_secs_f64(),
+        amount_in_0: delta(after.swap_in_amount_token_0, before.swap_in_amount_token_0),
+        amount_in_1: delta(after.swap_in_amount_token_1, before.swap_in_amount_token_1),
+        fee_growth_0: delta(
+            after.fee_growth_global_0_x64,
+            before.fee_growth_global_0_x64,
+        ) / fixed_point_64::Q64 as f64,
+        fee_growth_1: delta(
+            after.fee_growth_global_1_x64,
+            before.fee_growth_global_1_x64,
+        ) / fixed_point_64::Q64 as f64,
+    })
+}
+
+/// Figures of a pool over a period, values being in token1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PeriodStats {
+    pub seconds: i64,
+    pub volume: f64,
+    /// Trade fees, and the part of them paid to liquidity providers.
+    pub fees: f64,
+    pub lp_fees: f64,
+    /// Of a small position on the stats range, in range for the whole period.
+    pub fee_apr: f64,
+    /// Average daily volume over the value locked.
+    pub utilization: f64,
+}
+
+/// Volume, fees and fee APR of a pool over the last day and week.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolStats {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    pub trade_fee_rate: u32,
+    /// Price of token0 in token1, amounts and values being in UI units.
+    pub price: f64,
+    #[serde(serialize_with = "serialize_display")]
+    pub liquidity: u128,
+    pub amount_0: f64,
+    pub amount_1: f64,
+    pub tvl: f64,
+    /// Range the fee APR is given for, `±range_width` around the price.
+    pub range_width: f64,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    /// Figures come from a short sample rather than from the swaps of the whole period.
+    pub extrapolated: bool,
+    /// Block time of the newest indexed swap when the figures come from the indexer, the
+    /// periods ending now regardless.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexed_until: Option<i64>,
+    pub day: PeriodStats,
+    pub week: PeriodStats,
+}
+
+/// Raw token1 value of one unit of liquidity on `[tick_lower, tick_upper)` at the price of
+/// `pool`.
+fn liquidity_value(pool: &PoolState, tick_lower: i32, tick_upper: i32) -> Result<f64> {
+    let sqrt_price = sqrt_price_x64_to_f64(pool.sqrt_price_x64);
+    let sqrt_price_lower = sqrt_price_x64_to_f64(tick_math::get_sqrt_price_at_tick(tick_lower)?);
+    let sqrt_price_upper = sqrt_price_x64_to_f64(tick_math::get_sqrt_price_at_tick(tick_upper)?);
+    let sqrt_price = sqrt_price.clamp(sqrt_price_lower, sqrt_price_upper);
+    let amount_0 = 1.0 / sqrt_price - 1.0 / sqrt_price_upper;
+    let amount_1 = sqrt_price - sqrt_price_lower;
+    Ok(amount_0 * sqrt_price * sqrt_price + amount_1)
+}
+
+/// Put together the stats of a pool from its activity over a day and a week.
+pub fn compute_pool_stats(
+    info: &PoolInfo,
+    pool: &PoolState,
+    amm_config: &AmmConfig,
+    range_width: f64,
+    day: &PoolActivity,
+    week: &PoolActivity,
+    extrapolated: bool,
+    indexed_until: Option<i64>,
+) -> Result<PoolStats> {
+    let (tick_lower, tick_upper) = get_range_ticks(
+        RangeStrategy::Percent(range_width),
+        pool.tick_current,
+        pool.tick_spacing,
+    )?;
+    let liquidity_value = liquidity_value(pool, tick_lower, tick_upper)?;
+    let sqrt_price = sqrt_price_x64_to_f64(pool.sqrt_price_x64);
+    let raw_price = sqrt_price * sqrt_price;
+    let quote_multiplier = multipler(pool.mint_decimals_1);
+    let trade_fee_rate = amm_config.trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64;
+    let period_stats = |activity: &PoolActivity| {
+        let volume = (activity.amount_in_0 * raw_price + activity.amount_in_1) / quote_multiplier;
+        let fees = volume * trade_fee_rate;
+        let (fee_apr, utilization) = if activity.seconds > 0.0 {
+            let fee_growth = activity.fee_growth_0 * raw_price + activity.fee_growth_1;
+            (
+                fee_growth / liquidity_value * SECONDS_PER_YEAR / activity.seconds,
+                if info.tvl > 0.0 {
+                    volume * SECONDS_PER_DAY as f64 / activity.seconds / info.tvl
+                } else {
+                    0.0
+                },
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        PeriodStats {
+            seconds: activity.seconds.round() as i64,
+            volume,
+            fees,
+            lp_fees: fees * lp_fee_share(amm_config),
+            fee_apr,
+            utilization,
+        }
+    };
+    Ok(PoolStats {
+        pool_id: info.pool_id,
+        trade_fee_rate: amm_config.trade_fee_rate,
+        price: info.price,
+        liquidity: info.liquidity,
+        amount_0: info.amount_0,
+        amount_1: info.amount_1,
+        tvl: info.tvl,
+        range_width,
+        tick_lower,
+        tick_upper,
+        extrapolated,
+        indexed_until,
+        day: period_stats(day),
+        week: period_stats(week),
+    })
+}
+
+/// Load `pool_id` with its vaults and fee tier, and compute its stats from `source`.
+pub async fn get_pool_stats(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    source: &StatsSource,
+    range_width: f64,
+) -> Result<PoolStats> {
+    let pool = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    let amm_config = get_anchor_account::<AmmConfig>(rpc_client, &pool.amm_config).await?;
+    let (day, week, extrapolated, indexed_until) = match source {
+        StatsSource::Indexed(path) => {
+            let (indexed_until, activities) = get_indexed_activity(
+                path,
+                pool_id,
+                &amm_config,
+                unix_now()?,
+                &[SECONDS_PER_DAY, SECONDS_PER_WEEK],
+            )?;
+            (activities[0], activities[1], false, Some(indexed_until))
+        }
+        StatsSource::Sampled(interval) => {
+            let activity = sample_activity(rpc_client, pool_id, *interval).await?;
+            (
+                activity.extrapolate(SECONDS_PER_DAY),
+                activity.extrapolate(SECONDS_PER_WEEK),
+                true,
+                None,
+            )
+        }
+    };
+    // the pool is read again so that TVL and price are as of after the sample
+    let pool = get_anchor_account::<PoolState>(rpc_client, pool_id).await?;
+    let amm_configs = HashMap::from([(pool.amm_config, amm_config)]);
+    let info = get_pool_infos(rpc_client, &[(*pool_id, pool)], &amm_configs)
+        .await?
+        .remove(0);
+    compute_pool_stats(
+        &info,
+        &pool,
+        &amm_configs[&pool.amm_config],
+        range_width,
+        &day,
+        &week,
+        extrapolated,
+        indexed_until,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::Indexer;
+
+    fn amm_config() -> AmmConfig {
+        AmmConfig {
+            trade_fee_rate: 2_500,
+            protocol_fee_rate: 120_000,
+            fund_fee_rate: 40_000,
+            ..Default::default()
+        }
+    }
+
+    fn activity(seconds: f64) -> PoolActivity {
+        PoolActivity {
+            seconds,
+            amount_in_0: 3e11,
+            amount_in_1: 1e11,
+            fee_growth_0: 1e-3,
+            fee_growth_1: 2e-3,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9 * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn pool_stats_of_a_day_and_a_week() {
+        // price 1 with 6 decimals on both sides
+        let mut pool = PoolState::default();
+        pool.sqrt_price_x64 = fixed_point_64::Q64;
+        pool.tick_spacing = 10;
+        pool.mint_decimals_0 = 6;
+        pool.mint_decimals_1 = 6;
+        let info = PoolInfo {
+            pool_id: Pubkey::new_unique(),
+            amm_config: Pubkey::new_unique(),
+            amm_config_index: 0,
+            trade_fee_rate: 2_500,
+            tick_spacing: 10,
+            token_mint_0: Pubkey::new_unique(),
+            token_mint_1: Pubkey::new_unique(),
+            price: 1.0,
+            liquidity: 1_000_000,
+            amount_0: 1_000_000.0,
+            amount_1: 1_000_000.0,
+            tvl: 2_000_000.0,
+        };
+        let stats = compute_pool_stats(
+            &info,
+            &pool,
+            &amm_config(),
+            0.05,
+            &activity(SECONDS_PER_DAY as f64),
+            &activity(SECONDS_PER_WEEK as f64),
+            false,
+            Some(1_700_000_000),
+        )
+        .unwrap();
+        // ±5% is -513 and +488 ticks, aligned down to the spacing
+        assert_eq!((stats.tick_lower, stats.tick_upper), (-520, 480));
+        assert_eq!(stats.indexed_until, Some(1_700_000_000));
+
+        // 3e5 of token0 and 1e5 of token1 swapped in, at 0.25% of which 84% is left to LPs
+        assert_eq!(stats.day.seconds, SECONDS_PER_DAY);
+        assert_close(stats.day.volume, 400_000.0);
+        assert_close(stats.day.fees, 1_000.0);
+        assert_close(stats.day.lp_fees, 840.0);
+        assert_close(stats.day.utilization, 0.2);
+        // a unit of liquidity on the range holds `2 - 1.0001^-240 - 1.0001^-260` of token1
+        // and earned 3e-3 of it in a day
+        assert!((stats.day.fee_apr - 22.1764235).abs() < 1e-6);
+
+        // the same swaps spread over a week
+        assert_close(stats.week.volume, 400_000.0);
+        assert_close(stats.week.utilization, 0.2 / 7.0);
+        assert!((stats.week.fee_apr - 3.1680605).abs() < 1e-6);
+
+        let idle = compute_pool_stats(
+            &info,
+            &pool,
+            &amm_config(),
+            0.05,
+            &PoolActivity::default(),
+            &activity(0.0),
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!((idle.day.volume, idle.day.fee_apr), (0.0, 0.0));
+        assert_eq!((idle.week.fee_apr, idle.week.utilization), (0.0, 0.0));
+    }
+
+    #[test]
+    fn extrapolates_the_rate_of_a_sample() {
+        let day = activity(43_200.5).extrapolate(SECONDS_PER_DAY);
+        assert_eq!(day.seconds, SECONDS_PER_DAY as f64);
+        assert_close(day.amount_in_0, 3e11 * 86_400.0 / 43_200.5);
+        assert_close(day.fee_growth_1, 2e-3 * 86_400.0 / 43_200.5);
+        assert_eq!(
+            PoolActivity::default()
+                .extrapolate(SECONDS_PER_DAY)
+                .amount_in_0,
+            0.0
+        );
+    }
+
+    #[test]
+    fn indexed_activity_ends_at_the_given_time() {
+        let pool_id = Pubkey::new_unique();
+        let path = std::env::temp_dir().join(format!("{}-stats.db", pool_id));
+        let path = path.to_str().unwrap();
+        Indexer::open(path, Pubkey::new_unique(), pool_id).unwrap();
+        let connection = Connection::open(path).unwrap();
+        let end = 1_700_000_000;
+        // a swap in the last day, one in the last week and one after the end
+        for (index, (block_time, zero_for_one, amount_0, amount_1)) in [
+            (end - 100, true, 1_000u64, 990u64),
+            (end - 2 * SECONDS_PER_DAY, false, 495, 500),
+            (end + 50, true, 7_000, 6_930),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            connection
+                .execute(
+                    "INSERT INTO swaps \
+                     VALUES (?1, 0, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0, 0, ?9, '1000000', 0)",
+                    params![
+                        index.to_string(),
+                        pool_id.to_string(),
+                        index as u64,
+                        block_time,
+                        Pubkey::new_unique().to_string(),
+                        zero_for_one,
+                        amount_0,
+                        amount_1,
+                        fixed_point_64::Q64.to_string(),
+                    ],
+                )
+                .unwrap();
+        }
+
+        let (indexed_until, activities) = get_indexed_activity(
+            path,
+            &pool_id,
+            &amm_config(),
+            end,
+            &[SECONDS_PER_DAY, SECONDS_PER_WEEK],
+        )
+        .unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(indexed_until, end + 50);
+        let (day, week) = (activities[0], activities[1]);
+        assert_eq!(day.seconds, SECONDS_PER_DAY as f64);
+        assert_eq!((day.amount_in_0, day.amount_in_1), (1_000.0, 0.0));
+        // 0.25% of the amount in, 84% of it to the 1e6 of liquidity in range
+        assert_close(day.fee_growth_0, 2.1e-6);
+        assert_eq!((week.amount_in_0, week.amount_in_1), (1_000.0, 500.0));
+        assert_close(week.fee_growth_1, 1.05e-6);
+    }
+}
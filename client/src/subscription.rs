@@ -0,0 +1,480 @@
+use crate::client::{
+    deserialize_anchor_account, get_anchor_account, get_anchor_accounts,
+    get_nft_account_and_position_by_owner,
+};
+use crate::error::{ClientError, Result};
+use crate::events::{parse_log_events, ClmmEvent};
+use crate::pda::{get_tick_array_key, get_tickarray_bitmap_extension_key};
+use crate::tick_array::{get_all_initialized_tick_array_start_indexes, get_pool_tick_arrays};
+use crate::utils::{multipler, serialize_display, serialize_pubkey, sqrt_price_x64_to_f64};
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt};
+use raydium_amm_v3::states::{
+    PersonalPositionState, PoolState, TickArrayBitmapExtension, TickArrayState,
+};
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// Accounts and logs to follow over the websocket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subscription {
+    Pool(Pubkey),
+    TickArray(Pubkey),
+    TickArrayBitmapExtension(Pubkey),
+    /// A personal position, by its key.
+    Position(Pubkey),
+    /// Transactions mentioning an account, e.g. a pool.
+    Logs(Pubkey),
+}
+
+/// A decoded update pushed by the websocket, with the slot it was observed at.
+#[derive(Clone, Debug)]
+pub enum Update {
+    Pool {
+        key: Pubkey,
+        slot: u64,
+        pool_state: Box<PoolState>,
+    },
+    TickArray {
+        key: Pubkey,
+        slot: u64,
+        tick_array: Box<TickArrayState>,
+    },
+    TickArrayBitmapExtension {
+        key: Pubkey,
+        slot: u64,
+        tickarray_bitmap_extension: Box<TickArrayBitmapExtension>,
+    },
+    Position {
+        key: Pubkey,
+        slot: u64,
+        position: Box<PersonalPositionState>,
+    },
+    Logs {
+        slot: u64,
+        signature: String,
+        failed: bool,
+        logs: Vec<String>,
+    },
+}
+
+type Unsubscribe = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// Updates of several subscriptions merged into one stream, see [`subscribe_updates`].
+pub struct Updates<'a> {
+    stream: BoxStream<'a, Result<Update>>,
+    unsubscribes: Vec<Unsubscribe>,
+}
+
+impl Updates<'_> {
+    /// The next update, `None` once the websocket closed.
+    pub async fn next(&mut self) -> Option<Result<Update>> {
+        self.stream.next().await
+    }
+
+    /// Whether there is no subscription, and so no update to wait for.
+    pub fn is_empty(&self) -> bool {
+        self.unsubscribes.is_empty()
+    }
+
+    /// End the subscriptions on the server.
+    pub async fn unsubscribe(self) {
+        drop(self.stream);
+        for unsubscribe in self.unsubscribes {
+            unsubscribe().await;
+        }
+    }
+}
+
+/// Subscribe to `subscriptions` and merge their updates, which end when the websocket closes.
+/// An account that cannot be decoded, e.g. a closed position, yields an error item without
+/// ending them.
+pub async fn subscribe_updates<'a>(
+    pubsub_client: &'a PubsubClient,
+    subscriptions: &[Subscription],
+    commitment: CommitmentConfig,
+) -> Result<Updates<'a>> {
+    let mut streams = Vec::with_capacity(subscriptions.len());
+    let mut unsubscribes = Vec::with_capacity(subscriptions.len());
+    for subscription in subscriptions {
+        let (updates, unsubscribe) = match *subscription {
+            Subscription::Pool(key) => {
+                account_updates(pubsub_client, key, commitment, move |slot, account| {
+                    Ok(Update::Pool {
+                        key,
+                        slot,
+                        pool_state: Box::new(deserialize_anchor_account(account)?),
+                    })
+                })
+                .await?
+            }
+            Subscription::TickArray(key) => {
+                account_updates(pubsub_client, key, commitment, move |slot, account| {
+                    Ok(Update::TickArray {
+                        key,
+                        slot,
+                        tick_array: Box::new(deserialize_anchor_account(account)?),
+                    })
+                })
+                .await?
+            }
+            Subscription::TickArrayBitmapExtension(key) => {
+                account_updates(pubsub_client, key, commitment, move |slot, account| {
+                    Ok(Update::TickArrayBitmapExtension {
+                        key,
+                        slot,
+                        tickarray_bitmap_extension: Box::new(deserialize_anchor_account(account)?),
+                    })
+                })
+                .await?
+            }
+            Subscription::Position(key) => {
+                account_updates(pubsub_client, key, commitment, move |slot, account| {
+                    Ok(Update::Position {
+                        key,
+                        slot,
+                        position: Box::new(deserialize_anchor_account(account)?),
+                    })
+                })
+                .await?
+            }
+            Subscription::Logs(key) => {
+                let (logs, unsubscribe) = pubsub_client
+                    .logs_subscribe(
+                        RpcTransactionLogsFilter::Mentions(vec![key.to_string()]),
+                        RpcTransactionLogsConfig {
+                            commitment: Some(commitment),
+                        },
+                    )
+                    .await?;
+                let logs = logs
+                    .map(|response| {
+                        Ok(Update::Logs {
+                            slot: response.context.slot,
+                            signature: response.value.signature,
+                            failed: response.value.err.is_some(),
+                            logs: response.value.logs,
+                        })
+                    })
+                    .boxed();
+                (logs, unsubscribe)
+            }
+        };
+        streams.push(updates);
+        unsubscribes.push(unsubscribe);
+    }
+    Ok(Updates {
+        stream: stream::select_all(streams).boxed(),
+        unsubscribes,
+    })
+}
+
+async fn account_updates<'a, F>(
+    pubsub_client: &'a PubsubClient,
+    key: Pubkey,
+    commitment: CommitmentConfig,
+    decode: F,
+) -> Result<(BoxStream<'a, Result<Update>>, Unsubscribe)>
+where
+    F: Fn(u64, &Account) -> Result<Update> + Send + 'a,
+{
+    let (accounts, unsubscribe) = pubsub_client
+        .account_subscribe(
+            &key,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(commitment),
+                ..RpcAccountInfoConfig::default()
+            }),
+        )
+        .await?;
+    let accounts = accounts
+        .map(move |response| {
+            let account = response.value.decode::<Account>().ok_or_else(|| {
+                ClientError::InvalidResponse(format!("undecodable account {}", key))
+            })?;
+            decode(response.context.slot, &account)
+        })
+        .boxed();
+    Ok((accounts, unsubscribe))
+}
+
+/// A change seen by [`watch_pool`]. The first pool and position states come without a slot.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatchEvent {
+    /// Price, in UI units, tick or liquidity of the pool changed.
+    Pool {
+        slot: Option<u64>,
+        price: f64,
+        tick: i32,
+        #[serde(serialize_with = "serialize_display")]
+        liquidity: u128,
+    },
+    /// A position changed, or went in or out of range.
+    Position {
+        slot: Option<u64>,
+        #[serde(serialize_with = "serialize_pubkey")]
+        nft_mint: Pubkey,
+        #[serde(serialize_with = "serialize_display")]
+        liquidity: u128,
+        in_range: bool,
+    },
+    TickArray {
+        slot: u64,
+        start_tick_index: i32,
+        initialized_tick_count: u8,
+    },
+    /// Tick arrays were initialized or closed, the watch now follows these.
+    TickArrays {
+        slot: u64,
+        start_tick_indexes: Vec<i32>,
+    },
+    /// A transaction mentioning the pool, with the events the program emitted in it.
+    Transaction {
+        slot: u64,
+        signature: String,
+        failed: bool,
+        events: Vec<ClmmEvent>,
+    },
+}
+
+fn is_in_range(position: &PersonalPositionState, tick_current: i32) -> bool {
+    position.tick_lower_index <= tick_current && tick_current < position.tick_upper_index
+}
+
+/// Pass to `on_event` the price, tick and liquidity of a pool as they change, its
+/// transactions with their events, and when the positions of `owner` on it go in or out of
+/// range. With `with_tick_arrays`, changes of its initialized tick arrays are passed too, the
+/// watch following tick arrays as they are initialized or closed. Runs until the websocket
+/// closes or `on_event` fails, then unsubscribes; updates that cannot be decoded are logged.
+pub async fn watch_pool(
+    rpc_client: &RpcClient,
+    ws_url: &str,
+    raydium_v3_program: &Pubkey,
+    pool_id: Pubkey,
+    owner: Option<&Pubkey>,
+    with_tick_arrays: bool,
+    mut on_event: impl FnMut(&WatchEvent) -> Result<()>,
+) -> Result<()> {
+    let mut subscriptions = vec![Subscription::Pool(pool_id), Subscription::Logs(pool_id)];
+    let mut tickarray_bitmap_extension = None;
+    let mut tick_array_start_indexes = Vec::new();
+    let mut pool_state = if with_tick_arrays {
+        let tickarray_bitmap_extension_key =
+            get_tickarray_bitmap_extension_key(raydium_v3_program, &pool_id);
+        let ((pool_state, tick_arrays), mut tickarray_bitmap_extensions) = tokio::try_join!(
+            get_pool_tick_arrays(rpc_client, raydium_v3_program, &pool_id),
+            get_anchor_accounts::<TickArrayBitmapExtension>(
+                rpc_client,
+                &[tickarray_bitmap_extension_key]
+            )
+        )?;
+        subscriptions.push(Subscription::TickArrayBitmapExtension(
+            tickarray_bitmap_extension_key,
+        ));
+        tickarray_bitmap_extension = tickarray_bitmap_extensions.remove(0);
+        tick_array_start_indexes = tick_arrays.into_keys().collect();
+        pool_state
+    } else {
+        get_anchor_account::<PoolState>(rpc_client, &pool_id).await?
+    };
+    let mut positions = HashMap::new();
+    if let Some(owner) = owner {
+        let (_nft_tokens, position_keys) =
+            get_nft_account_and_position_by_owner(rpc_client, owner, raydium_v3_program).await?;
+        let user_positions =
+            get_anchor_accounts::<PersonalPositionState>(rpc_client, &position_keys).await?;
+        for (key, position) in position_keys.into_iter().zip(user_positions) {
+            if let Some(position) = position.filter(|position| position.pool_id == pool_id) {
+                subscriptions.push(Subscription::Position(key));
+                positions.insert(key, position);
+            }
+        }
+    }
+    let price_multiplier =
+        multipler(pool_state.mint_decimals_0) / multipler(pool_state.mint_decimals_1);
+    let price = |pool_state: &PoolState| {
+        let sqrt_price = sqrt_price_x64_to_f64(pool_state.sqrt_price_x64);
+        sqrt_price * sqrt_price * price_multiplier
+    };
+    on_event(&WatchEvent::Pool {
+        slot: None,
+        price: price(&pool_state),
+        tick: pool_state.tick_current,
+        liquidity: pool_state.liquidity,
+    })?;
+    for position in positions.values() {
+        on_event(&WatchEvent::Position {
+            slot: None,
+            nft_mint: position.nft_mint,
+            liquidity: position.liquidity,
+            in_range: is_in_range(position, pool_state.tick_current),
+        })?;
+    }
+
+    let tick_array_subscriptions = |start_indexes: &[i32]| {
+        start_indexes
+            .iter()
+            .map(|start_index| {
+                Subscription::TickArray(get_tick_array_key(
+                    raydium_v3_program,
+                    &pool_id,
+                    *start_index,
+                ))
+            })
+            .collect::<Vec<_>>()
+    };
+    let commitment = rpc_client.commitment();
+    let pubsub_client = PubsubClient::new(ws_url).await?;
+    let mut updates = subscribe_updates(&pubsub_client, &subscriptions, commitment).await?;
+    let mut tick_array_updates = subscribe_updates(
+        &pubsub_client,
+        &tick_array_subscriptions(&tick_array_start_indexes),
+        commitment,
+    )
+    .await?;
+    let result: Result<()> = async {
+        loop {
+            let update = tokio::select! {
+                update = updates.next() => update,
+                update = tick_array_updates.next(), if !tick_array_updates.is_empty() => update,
+            };
+            let Some(update) = update else {
+                return Ok(());
+            };
+            // slot at which the initialized tick arrays may have changed
+            let mut tick_arrays_slot = None;
+            match update {
+                Ok(Update::Pool {
+                    slot,
+                    pool_state: new_pool_state,
+                    ..
+                }) => {
+                    if (
+                        new_pool_state.sqrt_price_x64,
+                        new_pool_state.tick_current,
+                        new_pool_state.liquidity,
+                    ) != (
+                        pool_state.sqrt_price_x64,
+                        pool_state.tick_current,
+                        pool_state.liquidity,
+                    ) {
+                        on_event(&WatchEvent::Pool {
+                            slot: Some(slot),
+                            price: price(&new_pool_state),
+                            tick: new_pool_state.tick_current,
+                            liquidity: new_pool_state.liquidity,
+                        })?;
+                    }
+                    for position in positions.values() {
+                        let in_range = is_in_range(position, new_pool_state.tick_current);
+                        if in_range != is_in_range(position, pool_state.tick_current) {
+                            on_event(&WatchEvent::Position {
+                                slot: Some(slot),
+                                nft_mint: position.nft_mint,
+                                liquidity: position.liquidity,
+                                in_range,
+                            })?;
+                        }
+                    }
+                    // copied out of the packed accounts
+                    let (bitmap, new_bitmap) = (
+                        pool_state.tick_array_bitmap,
+                        new_pool_state.tick_array_bitmap,
+                    );
+                    if bitmap != new_bitmap {
+                        tick_arrays_slot = Some(slot);
+                    }
+                    pool_state = *new_pool_state;
+                }
+                Ok(Update::TickArray {
+                    slot, tick_array, ..
+                }) => {
+                    on_event(&WatchEvent::TickArray {
+                        slot,
+                        start_tick_index: tick_array.start_tick_index,
+                        initialized_tick_count: tick_array.initialized_tick_count,
+                    })?;
+                }
+                Ok(Update::TickArrayBitmapExtension {
+                    slot,
+                    tickarray_bitmap_extension: new_tickarray_bitmap_extension,
+                    ..
+                }) => {
+                    tickarray_bitmap_extension = Some(*new_tickarray_bitmap_extension);
+                    tick_arrays_slot = Some(slot);
+                }
+                Ok(Update::Position {
+                    key,
+                    slot,
+                    position,
+                }) => {
+                    on_event(&WatchEvent::Position {
+                        slot: Some(slot),
+                        nft_mint: position.nft_mint,
+                        liquidity: position.liquidity,
+                        in_range: is_in_range(&position, pool_state.tick_current),
+                    })?;
+                    positions.insert(key, *position);
+                }
+                Ok(Update::Logs {
+                    slot,
+                    signature,
+                    failed,
+                    logs,
+                }) => {
+                    let events =
+                        parse_log_events(raydium_v3_program, &logs).unwrap_or_else(|err| {
+                            log::warn!("events of {} undecodable: {}", signature, err);
+                            Vec::new()
+                        });
+                    on_event(&WatchEvent::Transaction {
+                        slot,
+                        signature,
+                        failed,
+                        events,
+                    })?;
+                }
+                Err(err) => log::warn!("{}", err),
+            }
+
+            let Some(slot) = tick_arrays_slot.filter(|_| with_tick_arrays) else {
+                continue;
+            };
+            let start_indexes = get_all_initialized_tick_array_start_indexes(
+                &pool_state,
+                tickarray_bitmap_extension.as_ref(),
+            )?;
+            if start_indexes != tick_array_start_indexes {
+                let new_tick_array_updates = subscribe_updates(
+                    &pubsub_client,
+                    &tick_array_subscriptions(&start_indexes),
+                    commitment,
+                )
+                .await?;
+                std::mem::replace(&mut tick_array_updates, new_tick_array_updates)
+                    .unsubscribe()
+                    .await;
+                on_event(&WatchEvent::TickArrays {
+                    slot,
+                    start_tick_indexes: start_indexes.clone(),
+                })?;
+                tick_array_start_indexes = start_indexes;
+            }
+        }
+    }
+    .await;
+    updates.unsubscribe().await;
+    tick_array_updates.unsubscribe().await;
+    result
+}
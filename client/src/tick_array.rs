@@ -0,0 +1,157 @@
+use crate::cache::AccountCache;
+use crate::client::{deserialize_anchor_account, get_anchor_accounts};
+use crate::error::{ClientError, Result};
+use crate::pda::{get_tick_array_key, get_tickarray_bitmap_extension_key};
+use raydium_amm_v3::states::{PoolState, TickArrayBitmapExtension, TickArrayState};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+
+/// Start indexes of the initialized tick arrays met when moving from the current tick, down
+/// when `zero_for_one` and up otherwise, in that order. The tick array holding the current
+/// tick comes first when initialized. `tickarray_bitmap_extension` is `None` when the pool
+/// has not created it.
+pub fn get_initialized_tick_array_start_indexes(
+    pool_state: &PoolState,
+    tickarray_bitmap_extension: Option<&TickArrayBitmapExtension>,
+    zero_for_one: bool,
+) -> Result<Vec<i32>> {
+    let mut start_index = match pool_state
+        .get_first_initialized_tick_array(&tickarray_bitmap_extension, zero_for_one)
+    {
+        Ok((_, start_index)) => start_index,
+        Err(err) => {
+            // fails when no tick array is initialized in that direction
+            let current_start_index = TickArrayState::get_array_start_index(
+                pool_state.tick_current,
+                pool_state.tick_spacing,
+            );
+            if pool_state
+                .next_initialized_tick_array_start_index(
+                    &tickarray_bitmap_extension,
+                    current_start_index,
+                    zero_for_one,
+                )?
+                .is_none()
+            {
+                return Ok(Vec::new());
+            }
+            return Err(err.into());
+        }
+    };
+    let mut start_indexes = vec![start_index];
+    while let Some(next_start_index) = pool_state.next_initialized_tick_array_start_index(
+        &tickarray_bitmap_extension,
+        start_index,
+        zero_for_one,
+    )? {
+        start_indexes.push(next_start_index);
+        start_index = next_start_index;
+    }
+    Ok(start_indexes)
+}
+
+/// Start indexes of every initialized tick array of the pool, ascending.
+pub fn get_all_initialized_tick_array_start_indexes(
+    pool_state: &PoolState,
+    tickarray_bitmap_extension: Option<&TickArrayBitmapExtension>,
+) -> Result<Vec<i32>> {
+    let mut start_indexes =
+        get_initialized_tick_array_start_indexes(pool_state, tickarray_bitmap_extension, true)?;
+    start_indexes.reverse();
+    start_indexes.extend(get_initialized_tick_array_start_indexes(
+        pool_state,
+        tickarray_bitmap_extension,
+        false,
+    )?);
+    // the current tick array is met in both directions
+    start_indexes.dedup();
+    Ok(start_indexes)
+}
+
+/// Load the tick arrays of `pool_id` starting at `start_indexes`, by start index.
+pub async fn get_tick_arrays(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    pool_id: &Pubkey,
+    start_indexes: &[i32],
+) -> Result<BTreeMap<i32, TickArrayState>> {
+    let keys = start_indexes
+        .iter()
+        .map(|start_index| get_tick_array_key(raydium_v3_program, pool_id, *start_index))
+        .collect::<Vec<_>>();
+    get_anchor_accounts::<TickArrayState>(rpc_client, &keys)
+        .await?
+        .into_iter()
+        .zip(start_indexes.iter().zip(&keys))
+        .map(|(tick_array, (start_index, key))| {
+            tick_array
+                .map(|tick_array| (*start_index, tick_array))
+                .ok_or(ClientError::AccountNotFound(*key))
+        })
+        .collect()
+}
+
+/// Load a pool together with every one of its initialized tick arrays.
+pub async fn get_pool_tick_arrays(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    pool_id: &Pubkey,
+) -> Result<(PoolState, BTreeMap<i32, TickArrayState>)> {
+    load_pool_tick_arrays(
+        rpc_client,
+        &AccountCache::new(None),
+        raydium_v3_program,
+        pool_id,
+    )
+    .await
+}
+
+/// Load a pool together with every one of its initialized tick arrays through `cache`, which
+/// only fetches the accounts it does not hold, none when a snapshot of the pool was loaded
+/// into it.
+pub async fn load_pool_tick_arrays(
+    rpc_client: &RpcClient,
+    cache: &AccountCache,
+    raydium_v3_program: &Pubkey,
+    pool_id: &Pubkey,
+) -> Result<(PoolState, BTreeMap<i32, TickArrayState>)> {
+    let tickarray_bitmap_extension_key =
+        get_tickarray_bitmap_extension_key(raydium_v3_program, pool_id);
+    let accounts = cache
+        .get_accounts(
+            rpc_client,
+            &[*pool_id, tickarray_bitmap_extension_key],
+            None,
+        )
+        .await?;
+    let pool_state = deserialize_anchor_account::<PoolState>(
+        accounts[0]
+            .as_ref()
+            .ok_or(ClientError::AccountNotFound(*pool_id))?,
+    )?;
+    // only created once the pool has tick arrays beyond the range of its own bitmap
+    let tickarray_bitmap_extension = accounts[1]
+        .as_ref()
+        .map(deserialize_anchor_account::<TickArrayBitmapExtension>)
+        .transpose()?;
+    let start_indexes = get_all_initialized_tick_array_start_indexes(
+        &pool_state,
+        tickarray_bitmap_extension.as_ref(),
+    )?;
+    let keys = start_indexes
+        .iter()
+        .map(|start_index| get_tick_array_key(raydium_v3_program, pool_id, *start_index))
+        .collect::<Vec<_>>();
+    let tick_arrays = cache
+        .get_accounts(rpc_client, &keys, None)
+        .await?
+        .into_iter()
+        .zip(start_indexes.iter().zip(&keys))
+        .map(|(account, (start_index, key))| {
+            let account = account.ok_or(ClientError::AccountNotFound(*key))?;
+            Ok((*start_index, deserialize_anchor_account(&account)?))
+        })
+        .collect::<Result<_>>()?;
+    Ok((pool_state, tick_arrays))
+}
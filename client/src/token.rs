@@ -0,0 +1,120 @@
+use crate::client::get_accounts;
+use crate::error::{ClientError, Result};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::interest_bearing_mint::InterestBearingConfig;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::transfer_hook::TransferHook;
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::Mint;
+
+/// Mint extensions Raydium accepts in a pool.
+const SUPPORTED_MINT_EXTENSIONS: [ExtensionType; 4] = [
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+];
+
+/// A mint of either token program, with the extensions that change how it is moved and priced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MintInfo {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub decimals: u8,
+    pub extensions: Vec<ExtensionType>,
+    pub transfer_fee_config: Option<TransferFeeConfig>,
+    pub interest_bearing_config: Option<InterestBearingConfig>,
+    pub transfer_hook_program: Option<Pubkey>,
+}
+
+impl MintInfo {
+    pub fn unpack(mint: Pubkey, account: &Account) -> Result<Self> {
+        if account.owner != spl_token::id() && account.owner != spl_token_2022::id() {
+            return Err(ClientError::UnsupportedMint {
+                mint,
+                reason: format!("owned by {} which is not a token program", account.owner),
+            });
+        }
+        let mint_state = StateWithExtensions::<Mint>::unpack(&account.data)?;
+        Ok(Self {
+            mint,
+            token_program: account.owner,
+            decimals: mint_state.base.decimals,
+            extensions: mint_state.get_extension_types()?,
+            transfer_fee_config: mint_state
+                .get_extension::<TransferFeeConfig>()
+                .ok()
+                .copied(),
+            interest_bearing_config: mint_state
+                .get_extension::<InterestBearingConfig>()
+                .ok()
+                .copied(),
+            transfer_hook_program: mint_state
+                .get_extension::<TransferHook>()
+                .ok()
+                .and_then(|transfer_hook| Option::<Pubkey>::from(transfer_hook.program_id)),
+        })
+    }
+
+    /// Fail on extensions Raydium refuses, such as transfer hooks.
+    pub fn check_supported(&self) -> Result<()> {
+        if let Some(program_id) = self.transfer_hook_program {
+            return Err(ClientError::UnsupportedMint {
+                mint: self.mint,
+                reason: format!(
+                    "transfer hook to {} is not supported by Raydium",
+                    program_id
+                ),
+            });
+        }
+        match self
+            .extensions
+            .iter()
+            .find(|extension| !SUPPORTED_MINT_EXTENSIONS.contains(extension))
+        {
+            Some(extension) => Err(ClientError::UnsupportedMint {
+                mint: self.mint,
+                reason: format!("{:?} extension is not supported by Raydium", extension),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Ratio between UI and raw amounts beyond decimals, which grows over time for
+    /// interest-bearing mints.
+    pub fn ui_amount_multiplier(&self, unix_timestamp: i64) -> f64 {
+        // one raw unit at zero decimals is the interest scale alone, whatever the decimals
+        self.interest_bearing_config
+            .and_then(|config| config.amount_to_ui_amount(1, 0, unix_timestamp))
+            .and_then(|ui_amount| ui_amount.parse::<f64>().ok())
+            .unwrap_or(1.0)
+    }
+}
+
+/// Factor turning a price of token0 in token1 between UI amounts into one between raw amounts,
+/// decimals aside.
+pub fn ui_price_multiplier(
+    mint_info_0: &MintInfo,
+    mint_info_1: &MintInfo,
+    unix_timestamp: i64,
+) -> f64 {
+    mint_info_0.ui_amount_multiplier(unix_timestamp)
+        / mint_info_1.ui_amount_multiplier(unix_timestamp)
+}
+
+/// Load and check every mint of `mints`, in the same order.
+pub async fn get_mint_infos(rpc_client: &RpcClient, mints: &[Pubkey]) -> Result<Vec<MintInfo>> {
+    get_accounts(rpc_client, mints)
+        .await?
+        .into_iter()
+        .zip(mints)
+        .map(|(account, mint)| {
+            let account = account.ok_or(ClientError::AccountNotFound(*mint))?;
+            let mint_info = MintInfo::unpack(*mint, &account)?;
+            mint_info.check_supported()?;
+            Ok(mint_info)
+        })
+        .collect()
+}
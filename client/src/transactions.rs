@@ -1,23 +1,268 @@
-use std::sync::Arc;
-
-use crate::client::{deserialize_anchor_account, get_nft_account_and_position_by_owner, send_txn};
-use crate::instructions::{create_pool_instr, increase_liquidity_instr};
+use crate::admin::{update_pool_status, OperationFlags, OperationUpdate, PoolOperation};
+use crate::client::{
+    get_accounts, get_anchor_account, get_anchor_accounts, get_cur_and_next_tick_array_keys,
+    get_epoch, get_nft_account_and_position_by_owner, get_token_balance, get_wsol_balance,
+    send_txn,
+};
+use crate::config::ClientConfig;
+use crate::error::{ClientError, Result};
+use crate::events::{get_transaction_events, ClmmEvent};
+use crate::fees::{collect_fees_instructions, get_pool_fees, FeeKind, FeeScope};
+use crate::instructions::{
+    assert_token_balance_instr, close_position_instr, close_wsol_account_instr,
+    create_ata_token_account_instr, create_operation_account_instr, create_pool_instr,
+    decrease_liquidity_instr, increase_liquidity_instr, initialize_reward_instr,
+    open_position_instr, set_reward_params_instr, swap_v2_instr, update_operation_account_instr,
+    update_pool_status_instr, update_reward_infos_instr,
+};
+use crate::pda::{
+    get_amm_config_key, get_observation_key, get_operation_key, get_personal_position_key,
+    get_pool_key, get_pool_vault_key, get_tickarray_bitmap_extension_key,
+};
+use crate::quote::quote_exact_input;
+use crate::router::Router;
+use crate::tick_array::get_pool_tick_arrays;
+use crate::token::{get_mint_infos, ui_price_multiplier};
+use crate::transfer_fee::{get_pool_mints_inverse_fee, post_fee_amount, pre_fee_amount};
 use crate::utils::{
-    amount_with_slippage, get_pool_mints_inverse_fee, price_to_sqrt_price_x64, tick_with_spacing,
+    amount_with_slippage, fits_in_transaction, get_zap_in_swap_amount, is_native_mint, multipler,
+    price_to_sqrt_price_x64, price_to_x64, serialize_display, serialize_pubkey, serialize_pubkeys,
+    tick_with_spacing, wsol_shortfall,
 };
-use crate::ClientConfig;
-use anchor_client::Program;
-use anyhow::Result;
 use raydium_amm_v3::libraries::{liquidity_math, tick_math};
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::instruction::AccountMeta;
-use solana_sdk::program_pack::Pack;
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::Keypair;
+use solana_sdk::signature::{Keypair, Signature};
 use solana_sdk::signer::Signer;
 use solana_sdk::transaction::Transaction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// Compute units of a move, which withdraws, swaps and opens a position in one transaction.
+const REBALANCE_COMPUTE_UNITS: u32 = 600_000;
+
+/// A confirmed transaction with the events the program emitted in it, empty when they could
+/// not be fetched.
+#[derive(Debug, Serialize)]
+pub struct SentTransaction {
+    #[serde(serialize_with = "serialize_display")]
+    pub signature: Signature,
+    pub events: Vec<ClmmEvent>,
+}
+
+/// A created pool with its derived accounts and initial price.
+#[derive(Debug, Serialize)]
+pub struct CreatePoolResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub amm_config: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_1: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_vault_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_vault_1: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub observation: Pubkey,
+    /// Price of token0 in token1, in UI units.
+    pub price: f64,
+    #[serde(serialize_with = "serialize_display")]
+    pub sqrt_price_x64: u128,
+    pub tick: i32,
+    pub open_time: u64,
+    #[serde(flatten)]
+    pub transaction: SentTransaction,
+}
+
+/// A swap sent in the same transaction as the instructions it prepares tokens for.
+#[derive(Clone, Debug, Serialize)]
+pub struct SwapLeg {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    pub zero_for_one: bool,
+    pub amount_in: u64,
+    pub other_amount_threshold: u64,
+}
+
+/// Liquidity added to the payer's position over the range, opened when there was none.
+/// `amount_0` and `amount_1` are what the vaults are expected to receive, the maximums include
+/// slippage and transfer fees.
+#[derive(Debug, Serialize)]
+pub struct DepositResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    pub position_opened: bool,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    #[serde(serialize_with = "serialize_display")]
+    pub liquidity: u128,
+    pub amount_0: u64,
+    pub amount_1: u64,
+    pub transfer_fee_0: u64,
+    pub transfer_fee_1: u64,
+    pub amount_0_max: u64,
+    pub amount_1_max: u64,
+    /// Wallet amounts the liquidity leaves unused, when depositing both tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leftover_0: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leftover_1: Option<u64>,
+    #[serde(flatten)]
+    pub transaction: SentTransaction,
+    /// Swap of the excess token made before depositing, in the same transaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zap_in: Option<SwapLeg>,
+}
+
+/// A withdrawal into a single token. Amounts are in the wallet, net of transfer fees, and
+/// the received ones net of the swap. Wrapped SOL is unwrapped.
+#[derive(Debug, Serialize)]
+pub struct ZapOutResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_display")]
+    pub liquidity: u128,
+    pub amount_0: u64,
+    pub amount_1: u64,
+    pub expected_amount: u64,
+    /// Least the wallet has to receive of the target token for the transaction to go through.
+    pub minimum_amount: u64,
+    pub received_0: u64,
+    pub received_1: u64,
+    pub position_closed: bool,
+    #[serde(flatten)]
+    pub transaction: SentTransaction,
+    /// Swap of the other token into the target one, in the withdrawal's transaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap: Option<SwapLeg>,
+}
+
+/// A position moved to a new range. Amounts are wallet amounts, net of transfer fees.
+#[derive(Debug, Serialize)]
+pub struct RebalanceResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub new_nft_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    /// Least the withdrawal of the old position's liquidity brings.
+    pub amount_0_min: u64,
+    pub amount_1_min: u64,
+    #[serde(serialize_with = "serialize_display")]
+    pub liquidity: u128,
+    pub amount_0_max: u64,
+    pub amount_1_max: u64,
+    /// Swap to the ratio of the new range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap: Option<SwapLeg>,
+    /// Withdrawal, swap, opening of the new position and closing of the old one.
+    #[serde(flatten)]
+    pub transaction: SentTransaction,
+}
+
+/// A route of a routed swap as it was quoted, through one or two pools.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RouteSummary {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    #[serde(serialize_with = "serialize_pubkeys")]
+    pub pools: Vec<Pubkey>,
+}
+
+/// A swap along one route or split across several.
+#[derive(Debug, Serialize)]
+pub struct RoutedSwapResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub input_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    /// Quoted output of all the routes.
+    pub amount_out: u64,
+    #[serde(flatten)]
+    pub transaction: SentTransaction,
+    pub routes: Vec<RouteSummary>,
+}
+
+/// Parameters a reward was started or changed with.
+#[derive(Debug, Serialize)]
+pub struct RewardResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward_index: Option<u8>,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub reward_mint: Pubkey,
+    #[serde(serialize_with = "serialize_display")]
+    pub emissions_per_second_x64: u128,
+    pub open_time: u64,
+    pub end_time: u64,
+    /// Amount transferred from the payer to fund a new reward, transfer fee included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funding: Option<u64>,
+    #[serde(flatten)]
+    pub transaction: SentTransaction,
+}
+
+/// The `kind` fees of one pool, in raw amounts.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolFeeAmounts {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_0: Pubkey,
+    pub amount_0: u64,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_mint_1: Pubkey,
+    pub amount_1: u64,
+}
+
+/// Total fees of a mint across the pools.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MintTotal {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint: Pubkey,
+    pub total: u64,
+}
+
+/// Fees reported, and unless a dry run collected, with the transactions that swept them.
+#[derive(Debug, Serialize)]
+pub struct CollectFeesResult {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub recipient: Pubkey,
+    pub pools: Vec<PoolFeeAmounts>,
+    pub totals: Vec<MintTotal>,
+    pub transactions: Vec<SentTransaction>,
+}
 
-pub(crate) fn create_pool_tx(
+/// Status of a pool before and after an update, with the transaction when it changed.
+#[derive(Debug, Serialize)]
+pub struct PoolStatusUpdate {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    pub previous_status: u8,
+    pub status: u8,
+    pub previous_operations: OperationFlags,
+    pub operations: OperationFlags,
+    #[serde(flatten)]
+    pub transaction: Option<SentTransaction>,
+}
+
+pub async fn create_pool_tx(
     rpc_client: &RpcClient,
     pool_config: &ClientConfig,
     config_index: u16,
@@ -26,7 +271,7 @@ pub(crate) fn create_pool_tx(
     mint0: Pubkey,
     mint1: Pubkey,
     open_time: u64,
-) -> Result<()> {
+) -> Result<CreatePoolResult> {
     let mut price = price;
     let mut mint0 = mint0;
     let mut mint1 = mint1;
@@ -34,108 +279,91 @@ pub(crate) fn create_pool_tx(
         std::mem::swap(&mut mint0, &mut mint1);
         price = 1.0 / price;
     }
-    println!("mint0:{}, mint1:{}, price:{}", mint0, mint1, price);
-    let load_pubkeys = vec![mint0, mint1];
-    let rsps = rpc_client.get_multiple_accounts(&load_pubkeys)?;
-    let mint0_owner = rsps[0].clone().unwrap().owner;
-    let mint1_owner = rsps[1].clone().unwrap().owner;
-    let mint0_account = spl_token::state::Mint::unpack(&rsps[0].as_ref().unwrap().data).unwrap();
-    let mint1_account = spl_token::state::Mint::unpack(&rsps[1].as_ref().unwrap().data).unwrap();
+    let mint_infos = get_mint_infos(rpc_client, &[mint0, mint1]).await?;
+    let mint0_owner = mint_infos[0].token_program;
+    let mint1_owner = mint_infos[1].token_program;
+    // the price is given in UI amounts, which accrue interest for interest-bearing mints
+    let raw_price = price * ui_price_multiplier(&mint_infos[0], &mint_infos[1], unix_now()?);
     let sqrt_price_x64 =
-        price_to_sqrt_price_x64(price, mint0_account.decimals, mint1_account.decimals);
-    let (amm_config_key, __bump) = Pubkey::find_program_address(
-        &[
-            raydium_amm_v3::states::AMM_CONFIG_SEED.as_bytes(),
-            &config_index.to_be_bytes(),
-        ],
+        price_to_sqrt_price_x64(raw_price, mint_infos[0].decimals, mint_infos[1].decimals);
+    let amm_config_key = get_amm_config_key(&pool_config.raydium_v3_program, config_index);
+    let tick = tick_math::get_tick_at_sqrt_price(sqrt_price_x64)?;
+    let pool_id = get_pool_key(
         &pool_config.raydium_v3_program,
+        &amm_config_key,
+        &mint0,
+        &mint1,
     );
-    let tick = tick_math::get_tick_at_sqrt_price(sqrt_price_x64).unwrap();
-    println!(
-        "tick:{}, price:{}, sqrt_price_x64:{}, amm_config_key:{}",
-        tick, price, sqrt_price_x64, amm_config_key
+    log::info!(
+        "pool_id:{}, mint0:{}, mint1:{}, price:{}, tick:{}",
+        pool_id,
+        mint0,
+        mint1,
+        price,
+        tick
     );
 
     let create_pool_instr = create_pool_instr(
         &pool_config.clone(),
+        payer.pubkey(),
         amm_config_key,
         mint0,
         mint1,
         mint0_owner,
         mint1_owner,
-        pool_config.tickarray_bitmap_extension.unwrap(),
+        pool_config.tickarray_bitmap_extension()?,
         sqrt_price_x64,
         open_time,
     )?;
 
     // send
     let signers = vec![payer];
-    let recent_hash = rpc_client.get_latest_blockhash()?;
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
     let txn = Transaction::new_signed_with_payer(
         &create_pool_instr,
         Some(&payer.pubkey()),
         &signers,
         recent_hash,
     );
-    let signature = send_txn(&rpc_client, &txn, true)?;
-    println!("{}", signature);
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
 
-    Ok(())
+    Ok(CreatePoolResult {
+        pool_id,
+        amm_config: amm_config_key,
+        token_mint_0: mint0,
+        token_mint_1: mint1,
+        token_vault_0: get_pool_vault_key(&pool_config.raydium_v3_program, &pool_id, &mint0),
+        token_vault_1: get_pool_vault_key(&pool_config.raydium_v3_program, &pool_id, &mint1),
+        observation: get_observation_key(&pool_config.raydium_v3_program, &pool_id),
+        price,
+        sqrt_price_x64,
+        tick,
+        open_time,
+        transaction,
+    })
 }
 
-pub(crate) async fn increase_liquidity_tx(
-    program: Program<Arc<Keypair>>,
-    rpc_client: RpcClient,
-    pool_config: ClientConfig,
-    payer: Keypair,
+pub async fn increase_liquidity_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
     tick_lower_price: f64,
     tick_upper_price: f64,
     is_base_0: bool,
     input_amount: u64,
-) -> anyhow::Result<()> {
+) -> Result<DepositResult> {
     // load pool to get observation
-    dbg!(pool_config.pool_id_account.unwrap());
+    let pool_id = pool_config.pool_id()?;
     // FIXME: create the pool first.
-    let pool: raydium_amm_v3::states::PoolState = program
-        .account(pool_config.pool_id_account.unwrap())
-        .await?;
-
-    // load position
-    let (_nft_tokens, positions) = get_nft_account_and_position_by_owner(
-        &rpc_client,
-        &payer.pubkey(),
-        &pool_config.raydium_v3_program,
-    );
-    let rsps = rpc_client.get_multiple_accounts(&positions)?;
-    let mut user_positions = Vec::new();
-    for rsp in rsps {
-        match rsp {
-            None => continue,
-            Some(rsp) => {
-                let position = deserialize_anchor_account::<
-                    raydium_amm_v3::states::PersonalPositionState,
-                >(&rsp)?;
-                user_positions.push(position);
-            }
-        }
-    }
+    let pool: raydium_amm_v3::states::PoolState = get_anchor_account(rpc_client, &pool_id).await?;
+    let mint_infos = get_mint_infos(rpc_client, &[pool.token_mint_0, pool.token_mint_1]).await?;
 
-    let tick_lower_price_x64 =
-        price_to_sqrt_price_x64(tick_lower_price, pool.mint_decimals_0, pool.mint_decimals_1);
-    let tick_upper_price_x64 =
-        price_to_sqrt_price_x64(tick_upper_price, pool.mint_decimals_0, pool.mint_decimals_1);
-    let tick_lower_index = tick_with_spacing(
-        tick_math::get_tick_at_sqrt_price(tick_lower_price_x64)?,
-        pool.tick_spacing.into(),
-    );
-    let tick_upper_index = tick_with_spacing(
-        tick_math::get_tick_at_sqrt_price(tick_upper_price_x64)?,
-        pool.tick_spacing.into(),
-    );
-    println!(
-        "tick_lower_index:{}, tick_upper_index:{}",
-        tick_lower_index, tick_upper_index
-    );
+    let (tick_lower_index, tick_upper_index) = price_range_to_ticks(
+        &pool,
+        tick_lower_price,
+        tick_upper_price,
+        ui_price_multiplier(&mint_infos[0], &mint_infos[1], unix_now()?),
+    )?;
     let tick_lower_price_x64 = tick_math::get_sqrt_price_at_tick(tick_lower_index)?;
     let tick_upper_price_x64 = tick_math::get_sqrt_price_at_tick(tick_upper_index)?;
     let liquidity = if is_base_0 {
@@ -153,38 +381,1403 @@ pub(crate) async fn increase_liquidity_tx(
             input_amount,
         )
     };
-    let (amount_0, amount_1) = liquidity_math::get_delta_amounts_signed(
+
+    send_increase_liquidity(
+        rpc_client,
+        pool_config,
+        payer,
+        &pool,
+        (pool.sqrt_price_x64, pool.tick_current),
+        tick_lower_index,
+        tick_upper_index,
+        liquidity,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Deposit up to `amount_0_max` of token0 and `amount_1_max` of token1, using the largest
+/// liquidity both amounts allow. With `zap_in`, the excess token is first swapped through the
+/// pool so that close to 100% of both balances ends up in the position. The liquidity is
+/// then sized at the price the quoted swap leaves, from the swap's minimum output. The
+/// deposit never takes more than the amounts given, the swap input included.
+pub async fn deposit_both_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    tick_lower_price: f64,
+    tick_upper_price: f64,
+    amount_0_max: u64,
+    amount_1_max: u64,
+    zap_in: bool,
+) -> Result<DepositResult> {
+    let pool_id = pool_config.pool_id()?;
+    let pool: raydium_amm_v3::states::PoolState = get_anchor_account(rpc_client, &pool_id).await?;
+    let (mint_infos, epoch) = tokio::try_join!(
+        get_mint_infos(rpc_client, &[pool.token_mint_0, pool.token_mint_1]),
+        get_epoch(rpc_client)
+    )?;
+
+    let (tick_lower_index, tick_upper_index) = price_range_to_ticks(
+        &pool,
+        tick_lower_price,
+        tick_upper_price,
+        ui_price_multiplier(&mint_infos[0], &mint_infos[1], unix_now()?),
+    )?;
+    let tick_lower_price_x64 = tick_math::get_sqrt_price_at_tick(tick_lower_index)?;
+    let tick_upper_price_x64 = tick_math::get_sqrt_price_at_tick(tick_upper_index)?;
+    // wallet amounts, the vaults receive them net of transfer fees
+    let mut amount_0 = amount_0_max;
+    let mut amount_1 = amount_1_max;
+    // price the deposit executes at
+    let mut sqrt_price_x64 = pool.sqrt_price_x64;
+    let mut tick_current = pool.tick_current;
+    let mut zap_in_swap = None;
+    if zap_in {
+        let (amm_config, (_, tick_arrays)) = tokio::try_join!(
+            get_anchor_account::<raydium_amm_v3::states::AmmConfig>(rpc_client, &pool.amm_config),
+            get_pool_tick_arrays(rpc_client, &pool_config.raydium_v3_program, &pool_id)
+        )?;
+        if let Some((zero_for_one, amount_in)) = get_zap_in_swap_amount(
+            pool.sqrt_price_x64,
+            tick_lower_price_x64,
+            tick_upper_price_x64,
+            amm_config.trade_fee_rate,
+            post_fee_amount(&mint_infos[0], epoch, amount_0)?,
+            post_fee_amount(&mint_infos[1], epoch, amount_1)?,
+        ) {
+            let (input_mint_info, output_mint_info) = if zero_for_one {
+                (&mint_infos[0], &mint_infos[1])
+            } else {
+                (&mint_infos[1], &mint_infos[0])
+            };
+            let quote = quote_exact_input(
+                &pool,
+                &tick_arrays,
+                amm_config.trade_fee_rate,
+                post_fee_amount(input_mint_info, epoch, amount_in)?,
+                zero_for_one,
+            )?;
+            let amount_out = post_fee_amount(output_mint_info, epoch, quote.amount_out)?;
+            let other_amount_threshold =
+                amount_with_slippage(amount_out, pool_config.slippage, false);
+            sqrt_price_x64 = quote.sqrt_price_x64;
+            tick_current = quote.tick;
+            log::info!(
+                "zap_in zero_for_one:{}, amount_in:{}, amount_out:{}",
+                zero_for_one,
+                amount_in,
+                amount_out
+            );
+            zap_in_swap = Some(SwapLeg {
+                pool_id,
+                zero_for_one,
+                amount_in,
+                other_amount_threshold,
+            });
+            if zero_for_one {
+                amount_0 -= amount_in;
+                amount_1 += other_amount_threshold;
+            } else {
+                amount_1 -= amount_in;
+                amount_0 += other_amount_threshold;
+            }
+        }
+    }
+
+    let liquidity = liquidity_math::get_liquidity_from_amounts(
+        sqrt_price_x64,
+        tick_lower_price_x64,
+        tick_upper_price_x64,
+        post_fee_amount(&mint_infos[0], epoch, amount_0)?,
+        post_fee_amount(&mint_infos[1], epoch, amount_1)?,
+    );
+    let (used_amount_0, used_amount_1) = liquidity_math::get_delta_amounts_signed(
+        tick_current,
+        sqrt_price_x64,
+        tick_lower_index,
+        tick_upper_index,
+        liquidity as i128,
+    )?;
+    let needed_0 = pre_fee_amount(&mint_infos[0], epoch, used_amount_0)?;
+    let needed_1 = pre_fee_amount(&mint_infos[1], epoch, used_amount_1)?;
+    if needed_0 > amount_0 || needed_1 > amount_1 {
+        return Err(ClientError::InvalidArgument(format!(
+            "the deposit needs {} of token0 and {} of token1, more than the {} and {} given",
+            needed_0, needed_1, amount_0, amount_1
+        )));
+    }
+    let leftover_0 = amount_0 - needed_0;
+    let leftover_1 = amount_1 - needed_1;
+
+    // the swap goes in the deposit's transaction, a failed deposit reverts it
+    let mut deposit = send_increase_liquidity(
+        rpc_client,
+        pool_config,
+        payer,
+        &pool,
+        (sqrt_price_x64, tick_current),
+        tick_lower_index,
+        tick_upper_index,
+        liquidity,
+        zap_in_swap,
+        Some((amount_0, amount_1)),
+    )
+    .await?;
+    deposit.leftover_0 = Some(leftover_0);
+    deposit.leftover_1 = Some(leftover_1);
+    Ok(deposit)
+}
+
+/// Withdraw `percent` (within `(0, 1]`) of a position's liquidity together with its fees, and
+/// swap the other token into the target one in the same transaction, through `swap_pool` when
+/// given or else through the position's own pool. The swap takes what the withdrawal is
+/// quoted to bring of the other token, and slippage is only checked once, on what the wallet
+/// ends up holding of the target token. A fully withdrawn position is closed when
+/// `close_position` is set.
+pub async fn zap_out_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    nft_mint: Pubkey,
+    percent: f64,
+    target_is_token_0: bool,
+    swap_pool: Option<Pubkey>,
+    close_position: bool,
+) -> Result<ZapOutResult> {
+    if percent <= 0.0 || percent > 1.0 {
+        return Err(ClientError::InvalidArgument(format!(
+            "percent must be within (0, 1], got {}",
+            percent
+        )));
+    }
+    let is_full = percent >= 1.0;
+    if close_position && !is_full {
+        return Err(ClientError::InvalidArgument(format!(
+            "closing the position needs all of its liquidity withdrawn, got {}",
+            percent
+        )));
+    }
+    let personal_position_key =
+        get_personal_position_key(&pool_config.raydium_v3_program, &nft_mint);
+    let position: raydium_amm_v3::states::PersonalPositionState =
+        get_anchor_account(rpc_client, &personal_position_key).await?;
+    let swap_pool_id = swap_pool.unwrap_or(position.pool_id);
+    let (pool, (swap_pool_state, swap_tick_arrays)) = tokio::try_join!(
+        get_anchor_account::<raydium_amm_v3::states::PoolState>(rpc_client, &position.pool_id),
+        get_pool_tick_arrays(rpc_client, &pool_config.raydium_v3_program, &swap_pool_id)
+    )?;
+    if swap_pool_state.token_mint_0 != pool.token_mint_0
+        || swap_pool_state.token_mint_1 != pool.token_mint_1
+    {
+        return Err(ClientError::InvalidArgument(format!(
+            "swap pool {} does not trade the position's mints",
+            swap_pool_id
+        )));
+    }
+    let reward_infos = pool
+        .reward_infos
+        .into_iter()
+        .filter(|reward_info| reward_info.initialized())
+        .collect::<Vec<_>>();
+    let mut mints = vec![pool.token_mint_0, pool.token_mint_1];
+    mints.extend(
+        reward_infos
+            .iter()
+            .map(|reward_info| reward_info.token_mint),
+    );
+    let (amm_config, mint_infos, epoch) = tokio::try_join!(
+        get_anchor_account::<raydium_amm_v3::states::AmmConfig>(
+            rpc_client,
+            &swap_pool_state.amm_config
+        ),
+        get_mint_infos(rpc_client, &mints),
+        get_epoch(rpc_client)
+    )?;
+    let token_programs = mint_infos
+        .iter()
+        .map(|mint_info| mint_info.token_program)
+        .collect::<Vec<_>>();
+
+    let liquidity = if is_full {
+        position.liquidity
+    } else {
+        (position.liquidity as f64 * percent) as u128
+    };
+    // what the withdrawal is expected to bring
+    let (liquidity_amount_0, liquidity_amount_1) = liquidity_math::get_delta_amounts_signed(
         pool.tick_current,
         pool.sqrt_price_x64,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        -(liquidity as i128),
+    )?;
+    // the wallet receives them with the fees, net of transfer fees
+    let amount_0 = post_fee_amount(
+        &mint_infos[0],
+        epoch,
+        liquidity_amount_0 + position.token_fees_owed_0,
+    )?;
+    let amount_1 = post_fee_amount(
+        &mint_infos[1],
+        epoch,
+        liquidity_amount_1 + position.token_fees_owed_1,
+    )?;
+    let (target_amount, swap_amount_in, target_index, other_index) = if target_is_token_0 {
+        (amount_0, amount_1, 0, 1)
+    } else {
+        (amount_1, amount_0, 1, 0)
+    };
+    let target_mint_info = &mint_infos[target_index];
+    // the swap takes all of the other token the withdrawal is quoted to bring, and pays
+    // transfer fees both ways
+    let swap_amount_out = if swap_amount_in > 0 {
+        let quote = quote_exact_input(
+            &swap_pool_state,
+            &swap_tick_arrays,
+            amm_config.trade_fee_rate,
+            post_fee_amount(&mint_infos[other_index], epoch, swap_amount_in)?,
+            !target_is_token_0,
+        )?;
+        post_fee_amount(target_mint_info, epoch, quote.amount_out)?
+    } else {
+        0
+    };
+    // slippage is checked on the outcome only, none of the steps has a minimum of its own
+    let swap = (swap_amount_in > 0).then(|| SwapLeg {
+        pool_id: swap_pool_id,
+        zero_for_one: !target_is_token_0,
+        amount_in: swap_amount_in,
+        other_amount_threshold: 0,
+    });
+    let expected_amount = target_amount + swap_amount_out;
+    let minimum_amount = amount_with_slippage(expected_amount, pool_config.slippage, false);
+    log::info!(
+        "liquidity:{}, expected_amount:{}, minimum_amount:{}",
+        liquidity,
+        expected_amount,
+        minimum_amount
+    );
+
+    let user_token_accounts = [0, 1].map(|index| {
+        get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &mints[index],
+            &token_programs[index],
+        )
+    });
+    let uses_sol = mints.iter().any(is_native_mint);
+    let load_wsol_balance = async {
+        if uses_sol {
+            get_wsol_balance(rpc_client, &payer.pubkey()).await
+        } else {
+            Ok(None)
+        }
+    };
+    let (balance_0_before, balance_1_before, wsol_balance, lamports_before) = tokio::try_join!(
+        get_token_balance(rpc_client, &user_token_accounts[0]),
+        get_token_balance(rpc_client, &user_token_accounts[1]),
+        load_wsol_balance,
+        async { Ok::<_, ClientError>(rpc_client.get_balance(&payer.pubkey()).await?) }
+    )?;
+    let target_balance_before = if target_is_token_0 {
+        balance_0_before
+    } else {
+        balance_1_before
+    };
+    // received SOL is unwrapped, unless it goes to a WSOL ATA the payer had already
+    let unwraps_sol = uses_sol && wsol_balance.is_none();
+    let close_position = is_full && close_position;
+    // rent the closed position returns, not part of what the withdrawal brings
+    let closed_lamports = if close_position {
+        get_accounts(
+            rpc_client,
+            &[
+                personal_position_key,
+                spl_associated_token_account::get_associated_token_address(
+                    &payer.pubkey(),
+                    &nft_mint,
+                ),
+            ],
+        )
+        .await?
+        .iter()
+        .flatten()
+        .map(|account| account.lamports)
+        .sum::<u64>()
+    } else {
+        0
+    };
+
+    let remaining_accounts = decrease_liquidity_remaining_accounts(
+        pool_config,
+        payer,
+        position.pool_id,
+        &reward_infos,
+        &token_programs[2..],
+    );
+    let mut instructions = Vec::new();
+    for (mint, token_program) in mints.iter().zip(&token_programs) {
+        instructions.extend(create_ata_token_account_instr(
+            &payer.pubkey(),
+            &payer.pubkey(),
+            mint,
+            token_program,
+            0,
+        )?);
+    }
+    instructions.extend(decrease_liquidity_instr(
+        pool_config,
+        payer.pubkey(),
+        position.pool_id,
+        pool.token_vault_0,
+        pool.token_vault_1,
+        pool.token_mint_0,
+        pool.token_mint_1,
+        nft_mint,
+        user_token_accounts[0],
+        user_token_accounts[1],
+        remaining_accounts,
+        liquidity,
+        0,
+        0,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        raydium_amm_v3::states::TickArrayState::get_array_start_index(
+            position.tick_lower_index,
+            pool.tick_spacing,
+        ),
+        raydium_amm_v3::states::TickArrayState::get_array_start_index(
+            position.tick_upper_index,
+            pool.tick_spacing,
+        ),
+    )?);
+    if let Some(swap) = &swap {
+        let (swap_instructions, _) = swap_leg_instr(
+            rpc_client,
+            pool_config,
+            &payer.pubkey(),
+            &swap_pool_state,
+            swap,
+        )
+        .await?;
+        instructions.extend(swap_instructions);
+    }
+    // the one slippage check, before any WSOL is unwrapped
+    instructions.extend(assert_token_balance_instr(
+        &payer.pubkey(),
+        &user_token_accounts[target_index],
+        &target_mint_info.mint,
+        &target_mint_info.token_program,
+        target_mint_info.decimals,
+        target_balance_before
+            .checked_add(minimum_amount)
+            .ok_or_else(|| ClientError::MathOverflow("target balance".to_string()))?,
+    )?);
+    if close_position {
+        instructions.extend(close_position_instr(pool_config, payer.pubkey(), nft_mint)?);
+    }
+    if unwraps_sol {
+        instructions.extend(close_wsol_account_instr(&payer.pubkey())?);
+    }
+    // send
+    let signers = vec![payer];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    let fee = rpc_client.get_fee_for_message(&txn.message).await?;
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
+
+    let (balance_0_after, balance_1_after, lamports_after) = tokio::try_join!(
+        get_token_balance(rpc_client, &user_token_accounts[0]),
+        get_token_balance(rpc_client, &user_token_accounts[1]),
+        async { Ok::<_, ClientError>(rpc_client.get_balance(&payer.pubkey()).await?) }
+    )?;
+    // unwrapped SOL shows in the payer's lamports, less the fee and the returned rent
+    let received = |mint: &Pubkey, balance_before: u64, balance_after: u64| {
+        if unwraps_sol && is_native_mint(mint) {
+            (lamports_after + fee).saturating_sub(lamports_before + closed_lamports)
+        } else {
+            balance_after.saturating_sub(balance_before)
+        }
+    };
+    let received_0 = received(&pool.token_mint_0, balance_0_before, balance_0_after);
+    let received_1 = received(&pool.token_mint_1, balance_1_before, balance_1_after);
+    log::info!("received_0:{}, received_1:{}", received_0, received_1);
+    Ok(ZapOutResult {
+        nft_mint,
+        pool_id: position.pool_id,
+        liquidity,
+        amount_0,
+        amount_1,
+        expected_amount,
+        minimum_amount,
+        received_0,
+        received_1,
+        position_closed: close_position,
+        transaction,
+        swap,
+    })
+}
+
+/// `pool` and its `tick_arrays` as they are once `liquidity` on
+/// `[tick_lower_index, tick_upper_index)` is withdrawn.
+fn withdraw_from_pool(
+    pool: &mut raydium_amm_v3::states::PoolState,
+    tick_arrays: &mut BTreeMap<i32, raydium_amm_v3::states::TickArrayState>,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    liquidity: u128,
+) {
+    let tick_current = pool.tick_current;
+    if (tick_lower_index..tick_upper_index).contains(&tick_current) {
+        pool.liquidity = pool.liquidity.saturating_sub(liquidity);
+    }
+    let liquidity = liquidity as i128;
+    for tick_array in tick_arrays.values_mut() {
+        // copied out of the packed account
+        let mut tick_states = tick_array.ticks;
+        for tick_state in tick_states
+            .iter_mut()
+            .filter(|tick_state| tick_state.liquidity_gross != 0)
+        {
+            if tick_state.tick == tick_lower_index {
+                tick_state.liquidity_net -= liquidity;
+            } else if tick_state.tick == tick_upper_index {
+                tick_state.liquidity_net += liquidity;
+            }
+        }
+        tick_array.ticks = tick_states;
+    }
+}
+
+/// Move a position to `[tick_lower_index, tick_upper_index]` in one transaction: withdraw all
+/// of its liquidity with its fees and rewards, swap the withdrawn tokens to the ratio of the new
+/// range, open a new position with them and only then close the old one. A failed step reverts
+/// the move as a whole and leaves the old position open.
+pub async fn rebalance_position_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    nft_mint: Pubkey,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    max_slippage: f64,
+) -> Result<RebalanceResult> {
+    let personal_position_key =
+        get_personal_position_key(&pool_config.raydium_v3_program, &nft_mint);
+    let position: raydium_amm_v3::states::PersonalPositionState =
+        get_anchor_account(rpc_client, &personal_position_key).await?;
+    let pool_id = position.pool_id;
+    let pool: raydium_amm_v3::states::PoolState = get_anchor_account(rpc_client, &pool_id).await?;
+    let reward_infos = pool
+        .reward_infos
+        .into_iter()
+        .filter(|reward_info| reward_info.initialized())
+        .collect::<Vec<_>>();
+    let mut mints = vec![pool.token_mint_0, pool.token_mint_1];
+    mints.extend(
+        reward_infos
+            .iter()
+            .map(|reward_info| reward_info.token_mint),
+    );
+    let (amm_config, mint_infos, epoch, (_, mut tick_arrays)) = tokio::try_join!(
+        get_anchor_account::<raydium_amm_v3::states::AmmConfig>(rpc_client, &pool.amm_config),
+        get_mint_infos(rpc_client, &mints),
+        get_epoch(rpc_client),
+        get_pool_tick_arrays(rpc_client, &pool_config.raydium_v3_program, &pool_id)
+    )?;
+    let token_programs = mint_infos
+        .iter()
+        .map(|mint_info| mint_info.token_program)
+        .collect::<Vec<_>>();
+    let user_token_account_0 = get_associated_token_address_with_program_id(
+        &payer.pubkey(),
+        &pool.token_mint_0,
+        &token_programs[0],
+    );
+    let user_token_account_1 = get_associated_token_address_with_program_id(
+        &payer.pubkey(),
+        &pool.token_mint_1,
+        &token_programs[1],
+    );
+
+    // withdraw everything, the withdrawal checks slippage on the liquidity amounts
+    let (liquidity_amount_0, liquidity_amount_1) = liquidity_math::get_delta_amounts_signed(
+        pool.tick_current,
+        pool.sqrt_price_x64,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        -(position.liquidity as i128),
+    )?;
+    let amount_0_min = amount_with_slippage(
+        post_fee_amount(&mint_infos[0], epoch, liquidity_amount_0)?,
+        max_slippage,
+        false,
+    );
+    let amount_1_min = amount_with_slippage(
+        post_fee_amount(&mint_infos[1], epoch, liquidity_amount_1)?,
+        max_slippage,
+        false,
+    );
+    // the swap and the new position are sized on what the withdrawal brings at worst, fees
+    // included, while the deposit may take up to what it is expected to bring
+    let fees_owed_0 = post_fee_amount(&mint_infos[0], epoch, position.token_fees_owed_0)?;
+    let fees_owed_1 = post_fee_amount(&mint_infos[1], epoch, position.token_fees_owed_1)?;
+    let mut amount_0 = amount_0_min + fees_owed_0;
+    let mut amount_1 = amount_1_min + fees_owed_1;
+    let mut expected_amount_0 =
+        post_fee_amount(&mint_infos[0], epoch, liquidity_amount_0)? + fees_owed_0;
+    let mut expected_amount_1 =
+        post_fee_amount(&mint_infos[1], epoch, liquidity_amount_1)? + fees_owed_1;
+    log::info!(
+        "liquidity:{}, amount_0_min:{}, amount_1_min:{}",
+        position.liquidity,
+        amount_0_min,
+        amount_1_min
+    );
+
+    // swap to the ratio of the new range, against the pool the withdrawal leaves
+    let tick_lower_price_x64 = tick_math::get_sqrt_price_at_tick(tick_lower_index)?;
+    let tick_upper_price_x64 = tick_math::get_sqrt_price_at_tick(tick_upper_index)?;
+    let mut swap_pool = pool;
+    withdraw_from_pool(
+        &mut swap_pool,
+        &mut tick_arrays,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        position.liquidity,
+    );
+    // price the deposit executes at
+    let mut sqrt_price_x64 = pool.sqrt_price_x64;
+    let mut tick_current = pool.tick_current;
+    let mut swap = None;
+    if let Some((zero_for_one, amount_in)) = get_zap_in_swap_amount(
+        pool.sqrt_price_x64,
+        tick_lower_price_x64,
+        tick_upper_price_x64,
+        amm_config.trade_fee_rate,
+        post_fee_amount(&mint_infos[0], epoch, amount_0)?,
+        post_fee_amount(&mint_infos[1], epoch, amount_1)?,
+    ) {
+        let (input_mint_info, output_mint_info) = if zero_for_one {
+            (&mint_infos[0], &mint_infos[1])
+        } else {
+            (&mint_infos[1], &mint_infos[0])
+        };
+        let quote = quote_exact_input(
+            &swap_pool,
+            &tick_arrays,
+            amm_config.trade_fee_rate,
+            post_fee_amount(input_mint_info, epoch, amount_in)?,
+            zero_for_one,
+        )?;
+        let amount_out = post_fee_amount(output_mint_info, epoch, quote.amount_out)?;
+        let other_amount_threshold = amount_with_slippage(amount_out, max_slippage, false);
+        sqrt_price_x64 = quote.sqrt_price_x64;
+        tick_current = quote.tick;
+        log::info!(
+            "zero_for_one:{}, amount_in:{}, amount_out:{}",
+            zero_for_one,
+            amount_in,
+            amount_out
+        );
+        if zero_for_one {
+            amount_0 -= amount_in;
+            amount_1 += other_amount_threshold;
+            expected_amount_0 = expected_amount_0.saturating_sub(amount_in);
+            expected_amount_1 += amount_out;
+        } else {
+            amount_1 -= amount_in;
+            amount_0 += other_amount_threshold;
+            expected_amount_1 = expected_amount_1.saturating_sub(amount_in);
+            expected_amount_0 += amount_out;
+        }
+        swap = Some(SwapLeg {
+            pool_id,
+            zero_for_one,
+            amount_in,
+            other_amount_threshold,
+        });
+    }
+
+    // the new position is sized at the post-swap price, its maximums leaving the slippage
+    // between the worst and the expected amounts as headroom
+    let liquidity = liquidity_math::get_liquidity_from_amounts(
+        sqrt_price_x64,
+        tick_lower_price_x64,
+        tick_upper_price_x64,
+        post_fee_amount(&mint_infos[0], epoch, amount_0)?,
+        post_fee_amount(&mint_infos[1], epoch, amount_1)?,
+    );
+    let (used_amount_0, used_amount_1) = liquidity_math::get_delta_amounts_signed(
+        tick_current,
+        sqrt_price_x64,
         tick_lower_index,
         tick_upper_index,
         liquidity as i128,
     )?;
-    println!(
-        "amount_0:{}, amount_1:{}, liquidity:{}",
-        amount_0, amount_1, liquidity
+    let amount_0_max = pre_fee_amount(
+        &mint_infos[0],
+        epoch,
+        amount_with_slippage(used_amount_0, max_slippage, true),
+    )?
+    .min(expected_amount_0);
+    let amount_1_max = pre_fee_amount(
+        &mint_infos[1],
+        epoch,
+        amount_with_slippage(used_amount_1, max_slippage, true),
+    )?
+    .min(expected_amount_1);
+
+    let uses_sol = mints.iter().any(is_native_mint);
+    let wsol_balance = if uses_sol {
+        get_wsol_balance(rpc_client, &payer.pubkey()).await?
+    } else {
+        None
+    };
+    let remaining_accounts = decrease_liquidity_remaining_accounts(
+        pool_config,
+        payer,
+        pool_id,
+        &reward_infos,
+        &token_programs[2..],
     );
-    // calc with slippage
-    let amount_0_with_slippage = amount_with_slippage(amount_0 as u64, pool_config.slippage, true);
-    let amount_1_with_slippage = amount_with_slippage(amount_1 as u64, pool_config.slippage, true);
-    // calc with transfer_fee
-    let transfer_fee = get_pool_mints_inverse_fee(
-        &rpc_client,
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        REBALANCE_COMPUTE_UNITS,
+    )];
+    for (mint, token_program) in mints.iter().zip(&token_programs) {
+        instructions.extend(create_ata_token_account_instr(
+            &payer.pubkey(),
+            &payer.pubkey(),
+            mint,
+            token_program,
+            0,
+        )?);
+    }
+    instructions.extend(decrease_liquidity_instr(
+        pool_config,
+        payer.pubkey(),
+        pool_id,
+        pool.token_vault_0,
+        pool.token_vault_1,
+        pool.token_mint_0,
+        pool.token_mint_1,
+        nft_mint,
+        user_token_account_0,
+        user_token_account_1,
+        remaining_accounts,
+        position.liquidity,
+        amount_0_min,
+        amount_1_min,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        raydium_amm_v3::states::TickArrayState::get_array_start_index(
+            position.tick_lower_index,
+            pool.tick_spacing,
+        ),
+        raydium_amm_v3::states::TickArrayState::get_array_start_index(
+            position.tick_upper_index,
+            pool.tick_spacing,
+        ),
+    )?);
+    if let Some(swap) = &swap {
+        let (swap_instructions, _) =
+            swap_leg_instr(rpc_client, pool_config, &payer.pubkey(), &pool, swap).await?;
+        instructions.extend(swap_instructions);
+    }
+    let new_nft_mint = Keypair::new();
+    instructions.extend(open_position_instr(
+        pool_config,
+        payer.pubkey(),
+        pool_id,
+        pool.token_vault_0,
+        pool.token_vault_1,
         pool.token_mint_0,
         pool.token_mint_1,
-        amount_0_with_slippage,
-        amount_1_with_slippage,
+        new_nft_mint.pubkey(),
+        user_token_account_0,
+        user_token_account_1,
+        vec![AccountMeta::new_readonly(
+            get_tickarray_bitmap_extension_key(&pool_config.raydium_v3_program, &pool_id),
+            false,
+        )],
+        liquidity,
+        amount_0_max,
+        amount_1_max,
+        tick_lower_index,
+        tick_upper_index,
+        raydium_amm_v3::states::TickArrayState::get_array_start_index(
+            tick_lower_index,
+            pool.tick_spacing,
+        ),
+        raydium_amm_v3::states::TickArrayState::get_array_start_index(
+            tick_upper_index,
+            pool.tick_spacing,
+        ),
+    )?);
+    instructions.extend(close_position_instr(pool_config, payer.pubkey(), nft_mint)?);
+    // unwrap the SOL left over, unless it goes to a WSOL ATA the payer had already
+    if uses_sol && wsol_balance.is_none() {
+        instructions.extend(close_wsol_account_instr(&payer.pubkey())?);
+    }
+    if !fits_in_transaction(&instructions, &payer.pubkey()) {
+        return Err(ClientError::InvalidArgument(format!(
+            "moving position {} does not fit in one transaction",
+            nft_mint
+        )));
+    }
+    // send
+    let signers = vec![payer, &new_nft_mint];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
+
+    Ok(RebalanceResult {
+        nft_mint,
+        new_nft_mint: new_nft_mint.pubkey(),
+        pool_id,
+        tick_lower_index,
+        tick_upper_index,
+        amount_0_min,
+        amount_1_min,
+        liquidity,
+        amount_0_max,
+        amount_1_max,
+        swap,
+        transaction,
+    })
+}
+
+/// Remaining accounts of `DecreaseLiquidityV2`: the bitmap extension, then the vault, the
+/// payer's token account and the mint of each reward.
+fn decrease_liquidity_remaining_accounts(
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    pool_id: Pubkey,
+    reward_infos: &[raydium_amm_v3::states::RewardInfo],
+    reward_token_programs: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let mut remaining_accounts = Vec::new();
+    remaining_accounts.push(AccountMeta::new_readonly(
+        get_tickarray_bitmap_extension_key(&pool_config.raydium_v3_program, &pool_id),
+        false,
+    ));
+    for (reward_info, token_program) in reward_infos.iter().zip(reward_token_programs) {
+        remaining_accounts.push(AccountMeta::new(reward_info.token_vault, false));
+        remaining_accounts.push(AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                &payer.pubkey(),
+                &reward_info.token_mint,
+                token_program,
+            ),
+            false,
+        ));
+        remaining_accounts.push(AccountMeta::new_readonly(reward_info.token_mint, false));
+    }
+    remaining_accounts
+}
+
+/// Align the price range, given between UI amounts, to ticks usable by the pool, the mints'
+/// `ui_price_multiplier` turning it into a raw one.
+fn price_range_to_ticks(
+    pool: &raydium_amm_v3::states::PoolState,
+    tick_lower_price: f64,
+    tick_upper_price: f64,
+    ui_price_multiplier: f64,
+) -> Result<(i32, i32)> {
+    let tick_lower_price_x64 = price_to_sqrt_price_x64(
+        tick_lower_price * ui_price_multiplier,
+        pool.mint_decimals_0,
+        pool.mint_decimals_1,
     );
-    println!(
-        "transfer_fee_0:{}, transfer_fee_1:{}",
-        transfer_fee.0.transfer_fee, transfer_fee.1.transfer_fee
+    let tick_upper_price_x64 = price_to_sqrt_price_x64(
+        tick_upper_price * ui_price_multiplier,
+        pool.mint_decimals_0,
+        pool.mint_decimals_1,
     );
-    let amount_0_max = (amount_0_with_slippage as u64)
+    let tick_lower_index = tick_with_spacing(
+        tick_math::get_tick_at_sqrt_price(tick_lower_price_x64)?,
+        pool.tick_spacing.into(),
+    );
+    let tick_upper_index = tick_with_spacing(
+        tick_math::get_tick_at_sqrt_price(tick_upper_price_x64)?,
+        pool.tick_spacing.into(),
+    );
+    Ok((tick_lower_index, tick_upper_index))
+}
+
+/// Current unix timestamp from the system clock.
+pub(crate) fn unix_now() -> Result<i64> {
+    Ok(SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|err| ClientError::InvalidArgument(format!("system clock: {}", err)))?
+        .as_secs() as i64)
+}
+
+/// Swap `amount_in` of `input_mint` into `output_mint` along the best direct or two hop
+/// route across every fee tier, or split across routes when `split` gives more.
+pub async fn swap_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount_in: u64,
+    split: bool,
+) -> Result<RoutedSwapResult> {
+    let router = Router::load(
+        rpc_client,
+        &pool_config.raydium_v3_program,
+        input_mint,
+        output_mint,
+    )
+    .await?;
+    let routes = if split {
+        router.best_split(amount_in)?
+    } else {
+        vec![router.best_route(amount_in)?]
+    };
+    let wsol_balance = get_wsol_balance(rpc_client, &payer.pubkey()).await?;
+    let (routes, instructions) = router.fitting_swap_instructions(
+        pool_config,
+        &payer.pubkey(),
+        routes,
+        pool_config.slippage,
+        wsol_balance,
+    )?;
+    let route_summaries = routes
+        .iter()
+        .map(|route| RouteSummary {
+            amount_in: route.amount_in,
+            amount_out: route.amount_out,
+            pools: route.hops.iter().map(|hop| hop.pool_id).collect(),
+        })
+        .collect::<Vec<_>>();
+    // send
+    let signers = vec![payer];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
+    Ok(RoutedSwapResult {
+        input_mint,
+        output_mint,
+        amount_in,
+        amount_out: routes.iter().map(|route| route.amount_out).sum(),
+        transaction,
+        routes: route_summaries,
+    })
+}
+
+/// Start a reward of `reward_mint` on `pool_id`, emitting `emissions_per_second` (UI amount)
+/// over `[open_time, end_time]`. The whole emission is transferred from the payer's ATA.
+pub async fn initialize_reward_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    pool_id: Pubkey,
+    reward_mint: Pubkey,
+    emissions_per_second: f64,
+    open_time: u64,
+    end_time: u64,
+) -> Result<RewardResult> {
+    if end_time <= open_time || emissions_per_second <= 0.0 {
+        return Err(ClientError::InvalidArgument(format!(
+            "expected a positive emission over a non empty period, got {} from {} to {}",
+            emissions_per_second, open_time, end_time
+        )));
+    }
+    let (pool, mint_infos, epoch) = tokio::try_join!(
+        get_anchor_account::<raydium_amm_v3::states::PoolState>(rpc_client, &pool_id),
+        get_mint_infos(rpc_client, &[reward_mint]),
+        get_epoch(rpc_client)
+    )?;
+    let mint_info = &mint_infos[0];
+    let emissions_per_second_raw = emissions_per_second * multipler(mint_info.decimals);
+    let reward_amount = (emissions_per_second_raw * (end_time - open_time) as f64).ceil() as u64;
+    let funding = pre_fee_amount(mint_info, epoch, reward_amount)?;
+    let emissions_per_second_x64 = price_to_x64(emissions_per_second_raw);
+    let instructions = initialize_reward_instr(
+        pool_config,
+        payer.pubkey(),
+        pool_id,
+        pool.amm_config,
+        get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &reward_mint,
+            &mint_info.token_program,
+        ),
+        reward_mint,
+        mint_info.token_program,
+        open_time,
+        end_time,
+        emissions_per_second_x64,
+    )?;
+    // send
+    let signers = vec![payer];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
+    Ok(RewardResult {
+        pool_id,
+        reward_index: None,
+        reward_mint,
+        emissions_per_second_x64,
+        open_time,
+        end_time,
+        funding: Some(funding),
+        transaction,
+    })
+}
+
+/// Extend or top up reward `reward_index` of `pool_id`, keeping the current rate and period
+/// for what is not given. The extra emission is transferred from the payer's ATA.
+pub async fn set_reward_params_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    pool_id: Pubkey,
+    reward_index: u8,
+    emissions_per_second: Option<f64>,
+    open_time: Option<u64>,
+    end_time: Option<u64>,
+) -> Result<RewardResult> {
+    let pool: raydium_amm_v3::states::PoolState = get_anchor_account(rpc_client, &pool_id).await?;
+    // copied out of the packed account
+    let reward_infos = pool.reward_infos;
+    let reward_info = reward_infos
+        .get(usize::from(reward_index))
+        .filter(|reward_info| reward_info.token_mint != Pubkey::default())
+        .ok_or_else(|| {
+            ClientError::InvalidArgument(format!("pool {} has no reward {}", pool_id, reward_index))
+        })?;
+    let reward_mint = reward_info.token_mint;
+    let mint_info = get_mint_infos(rpc_client, &[reward_mint]).await?.remove(0);
+    let emissions_per_second_x64 = match emissions_per_second {
+        Some(emissions_per_second) => {
+            price_to_x64(emissions_per_second * multipler(mint_info.decimals))
+        }
+        None => reward_info.emissions_per_second_x64,
+    };
+    let open_time = open_time.unwrap_or(reward_info.open_time);
+    let end_time = end_time.unwrap_or(reward_info.end_time);
+    // only read when the pool owner, rather than an admin, funds the change
+    let remaining_accounts = vec![
+        AccountMeta::new(reward_info.token_vault, false),
+        AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                &payer.pubkey(),
+                &reward_mint,
+                &mint_info.token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new_readonly(reward_mint, false),
+    ];
+    let instructions = set_reward_params_instr(
+        pool_config,
+        payer.pubkey(),
+        pool_id,
+        pool.amm_config,
+        remaining_accounts,
+        reward_index,
+        emissions_per_second_x64,
+        open_time,
+        end_time,
+    )?;
+    // send
+    let signers = vec![payer];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
+    Ok(RewardResult {
+        pool_id,
+        reward_index: Some(reward_index),
+        reward_mint,
+        emissions_per_second_x64,
+        open_time,
+        end_time,
+        funding: None,
+        transaction,
+    })
+}
+
+/// Accrue the rewards of `pool_id` up to now.
+pub async fn update_reward_infos_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    pool_id: Pubkey,
+) -> Result<SentTransaction> {
+    let instructions = update_reward_infos_instr(pool_config, pool_id)?;
+    // send
+    let signers = vec![payer];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await
+}
+
+/// Report the `kind` fees accrued on the pools of `scope` with their total by mint, then,
+/// unless `dry_run`, sweep them to the ATAs of the configured recipient, a few pools per
+/// transaction signed by the admin.
+pub async fn collect_fees_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    admin: &Keypair,
+    kind: FeeKind,
+    scope: FeeScope,
+    dry_run: bool,
+) -> Result<CollectFeesResult> {
+    let pool_fees = get_pool_fees(rpc_client, &pool_config.raydium_v3_program, scope)
+        .await?
+        .into_iter()
+        .filter(|pool_fees| pool_fees.amounts(kind) != (0, 0))
+        .collect::<Vec<_>>();
+    let mut totals = BTreeMap::<Pubkey, u64>::new();
+    for pool_fees in &pool_fees {
+        let (amount_0, amount_1) = pool_fees.amounts(kind);
+        for (mint, amount) in [
+            (pool_fees.token_mint_0, amount_0),
+            (pool_fees.token_mint_1, amount_1),
+        ] {
+            let total = totals.entry(mint).or_default();
+            *total = total.saturating_add(amount);
+        }
+    }
+    let recipient = kind.recipient(pool_config, &admin.pubkey());
+    let mut result = CollectFeesResult {
+        recipient,
+        pools: pool_fees
+            .iter()
+            .map(|pool_fees| {
+                let (amount_0, amount_1) = pool_fees.amounts(kind);
+                PoolFeeAmounts {
+                    pool_id: pool_fees.pool_id,
+                    token_mint_0: pool_fees.token_mint_0,
+                    amount_0,
+                    token_mint_1: pool_fees.token_mint_1,
+                    amount_1,
+                }
+            })
+            .collect(),
+        totals: totals
+            .iter()
+            .map(|(mint, total)| MintTotal {
+                mint: *mint,
+                total: *total,
+            })
+            .collect(),
+        transactions: Vec::new(),
+    };
+    if dry_run || pool_fees.is_empty() {
+        return Ok(result);
+    }
+
+    let mints = totals.keys().copied().collect::<Vec<_>>();
+    let token_programs = mints
+        .iter()
+        .copied()
+        .zip(
+            get_mint_infos(rpc_client, &mints)
+                .await?
+                .into_iter()
+                .map(|mint_info| mint_info.token_program),
+        )
+        .collect::<BTreeMap<_, _>>();
+    let batches = collect_fees_instructions(
+        pool_config,
+        &admin.pubkey(),
+        &recipient,
+        kind,
+        &pool_fees,
+        &token_programs,
+    )?;
+
+    for instructions in batches {
+        // send
+        let signers = vec![admin];
+        let recent_hash = rpc_client.get_latest_blockhash().await?;
+        let txn = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&admin.pubkey()),
+            &signers,
+            recent_hash,
+        );
+        result
+            .transactions
+            .push(send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?);
+    }
+    Ok(result)
+}
+
+/// Enable and disable operations of `pool_id`, leaving the others as they are.
+pub async fn update_pool_status_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    admin: &Keypair,
+    pool_id: Pubkey,
+    enable: &[PoolOperation],
+    disable: &[PoolOperation],
+) -> Result<PoolStatusUpdate> {
+    let pool: raydium_amm_v3::states::PoolState = get_anchor_account(rpc_client, &pool_id).await?;
+    let status = update_pool_status(pool.status, enable, disable)?;
+    let mut result = PoolStatusUpdate {
+        pool_id,
+        previous_status: pool.status,
+        status,
+        previous_operations: OperationFlags(pool.status),
+        operations: OperationFlags(status),
+        transaction: None,
+    };
+    if status == pool.status {
+        return Ok(result);
+    }
+    let instructions = update_pool_status_instr(pool_config, admin.pubkey(), pool_id, status)?;
+    // send
+    let signers = vec![admin];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&admin.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    result.transaction =
+        Some(send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?);
+    Ok(result)
+}
+
+/// Add or remove operation owners or whitelisted mints, creating the operation account first
+/// when it does not exist yet.
+pub async fn update_operation_account_tx(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    admin: &Keypair,
+    update: OperationUpdate,
+    keys: Vec<Pubkey>,
+) -> Result<SentTransaction> {
+    if keys.is_empty() {
+        return Err(ClientError::InvalidArgument(
+            "expected at least one key".to_string(),
+        ));
+    }
+    let operation_key = get_operation_key(&pool_config.raydium_v3_program);
+    let mut instructions = Vec::new();
+    if get_accounts(rpc_client, &[operation_key]).await?[0].is_none() {
+        instructions.extend(create_operation_account_instr(pool_config, admin.pubkey())?);
+    }
+    instructions.extend(update_operation_account_instr(
+        pool_config,
+        admin.pubkey(),
+        update as u8,
+        keys,
+    )?);
+    // send
+    let signers = vec![admin];
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&admin.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await
+}
+
+/// Send `txn` and fetch the events it emitted, with the amounts it actually moved. The
+/// transaction is confirmed already, so failing to fetch them is only logged.
+async fn send_transaction(
+    rpc_client: &RpcClient,
+    raydium_v3_program: &Pubkey,
+    txn: &Transaction,
+) -> Result<SentTransaction> {
+    let signature = send_txn(rpc_client, txn, true).await?;
+    log::info!("signature:{}", signature);
+    let events = match get_transaction_events(rpc_client, raydium_v3_program, &signature).await {
+        Ok(events) => events,
+        Err(err) => {
+            log::warn!("events of {} unavailable: {}", signature, err);
+            Vec::new()
+        }
+    };
+    Ok(SentTransaction { signature, events })
+}
+
+/// `SwapV2` instruction of `swap` between `owner`'s ATAs, which must exist and hold the input,
+/// with the token programs of the input and output mints.
+async fn swap_leg_instr(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    owner: &Pubkey,
+    pool: &raydium_amm_v3::states::PoolState,
+    swap: &SwapLeg,
+) -> Result<(Vec<Instruction>, (Pubkey, Pubkey))> {
+    let (input_vault, output_vault, input_vault_mint, output_vault_mint) = if swap.zero_for_one {
+        (
+            pool.token_vault_0,
+            pool.token_vault_1,
+            pool.token_mint_0,
+            pool.token_mint_1,
+        )
+    } else {
+        (
+            pool.token_vault_1,
+            pool.token_vault_0,
+            pool.token_mint_1,
+            pool.token_mint_0,
+        )
+    };
+    let tickarray_bitmap_extension_key =
+        get_tickarray_bitmap_extension_key(&pool_config.raydium_v3_program, &swap.pool_id);
+    let (tickarray_bitmap_extension, mint_infos) = tokio::try_join!(
+        get_anchor_account::<raydium_amm_v3::states::TickArrayBitmapExtension>(
+            rpc_client,
+            &tickarray_bitmap_extension_key
+        ),
+        get_mint_infos(rpc_client, &[input_vault_mint, output_vault_mint])
+    )?;
+    let token_programs = (mint_infos[0].token_program, mint_infos[1].token_program);
+
+    let mut remaining_accounts = Vec::new();
+    remaining_accounts.push(AccountMeta::new_readonly(
+        tickarray_bitmap_extension_key,
+        false,
+    ));
+    let tick_array_keys = get_cur_and_next_tick_array_keys(
+        &pool_config.raydium_v3_program,
+        &swap.pool_id,
+        pool,
+        &tickarray_bitmap_extension,
+        swap.zero_for_one,
+        3,
+    )?;
+    remaining_accounts.extend(
+        tick_array_keys
+            .into_iter()
+            .map(|tick_array_key| AccountMeta::new(tick_array_key, false)),
+    );
+    let user_input_token =
+        get_associated_token_address_with_program_id(owner, &input_vault_mint, &token_programs.0);
+    let user_output_token =
+        get_associated_token_address_with_program_id(owner, &output_vault_mint, &token_programs.1);
+    let instructions = swap_v2_instr(
+        pool_config,
+        *owner,
+        pool.amm_config,
+        swap.pool_id,
+        input_vault,
+        output_vault,
+        pool.observation_key,
+        user_input_token,
+        user_output_token,
+        input_vault_mint,
+        output_vault_mint,
+        remaining_accounts,
+        swap.amount_in,
+        swap.other_amount_threshold,
+        None,
+        true,
+    )?;
+    Ok((instructions, token_programs))
+}
+
+/// Add `liquidity` to the payer's position over the range, opening one when there is none,
+/// after the `zap_in` swap when given, all in one transaction. The amounts are taken at
+/// `(sqrt_price_x64, tick_current)`, the price the deposit is expected to execute at, and the
+/// maximums the deposit may take are capped at `budget` when given.
+async fn send_increase_liquidity(
+    rpc_client: &RpcClient,
+    pool_config: &ClientConfig,
+    payer: &Keypair,
+    pool: &raydium_amm_v3::states::PoolState,
+    (sqrt_price_x64, tick_current): (u128, i32),
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    liquidity: u128,
+    zap_in: Option<SwapLeg>,
+    budget: Option<(u64, u64)>,
+) -> Result<DepositResult> {
+    let pool_id = pool_config.pool_id()?;
+    let (amount_0, amount_1) = liquidity_math::get_delta_amounts_signed(
+        tick_current,
+        sqrt_price_x64,
+        tick_lower_index,
+        tick_upper_index,
+        liquidity as i128,
+    )?;
+    // calc with slippage
+    let amount_0_with_slippage = amount_with_slippage(amount_0 as u64, pool_config.slippage, true);
+    let amount_1_with_slippage = amount_with_slippage(amount_1 as u64, pool_config.slippage, true);
+    // load positions, and calc with transfer_fee
+    let load_positions = async {
+        let (_nft_tokens, positions) = get_nft_account_and_position_by_owner(
+            rpc_client,
+            &payer.pubkey(),
+            &pool_config.raydium_v3_program,
+        )
+        .await?;
+        get_anchor_accounts::<raydium_amm_v3::states::PersonalPositionState>(rpc_client, &positions)
+            .await
+    };
+    let uses_sol = is_native_mint(&pool.token_mint_0) || is_native_mint(&pool.token_mint_1);
+    let load_wsol_balance = async {
+        if uses_sol {
+            get_wsol_balance(rpc_client, &payer.pubkey()).await
+        } else {
+            Ok(None)
+        }
+    };
+    let (user_positions, wsol_balance, transfer_fee) = tokio::try_join!(
+        load_positions,
+        load_wsol_balance,
+        get_pool_mints_inverse_fee(
+            rpc_client,
+            pool.token_mint_0,
+            pool.token_mint_1,
+            amount_0_with_slippage,
+            amount_1_with_slippage,
+        )
+    )?;
+    let amount_0_max = amount_0_with_slippage
         .checked_add(transfer_fee.0.transfer_fee)
-        .unwrap();
-    let amount_1_max = (amount_1_with_slippage as u64)
+        .ok_or_else(|| ClientError::MathOverflow("amount_0_max".to_string()))?;
+    let amount_1_max = amount_1_with_slippage
         .checked_add(transfer_fee.1.transfer_fee)
-        .unwrap();
+        .ok_or_else(|| ClientError::MathOverflow("amount_1_max".to_string()))?;
+    let (amount_0_max, amount_1_max) = match budget {
+        Some((budget_0, budget_1)) => (amount_0_max.min(budget_0), amount_1_max.min(budget_1)),
+        None => (amount_0_max, amount_1_max),
+    };
 
     let tick_array_lower_start_index =
         raydium_amm_v3::states::TickArrayState::get_array_start_index(
@@ -196,41 +1789,71 @@ pub(crate) async fn increase_liquidity_tx(
             tick_upper_index,
             pool.tick_spacing.into(),
         );
-    let mut find_position = raydium_amm_v3::states::PersonalPositionState::default();
-    for position in user_positions {
-        if position.pool_id == pool_config.pool_id_account.unwrap()
+    // known before anything is sent, so that a missing position is opened instead
+    let find_position = user_positions.into_iter().flatten().rfind(|position| {
+        position.pool_id == pool_id
             && position.tick_lower_index == tick_lower_index
             && position.tick_upper_index == tick_upper_index
-        {
-            find_position = position.clone();
+    });
+    let (nft_mint, new_nft_mint) = match &find_position {
+        Some(position) => (position.nft_mint, None),
+        None => {
+            let new_nft_mint = Keypair::new();
+            (new_nft_mint.pubkey(), Some(new_nft_mint))
         }
-    }
-    if find_position.nft_mint != Pubkey::default()
-        && find_position.pool_id == pool_config.pool_id_account.unwrap()
-    {
-        // personal position exist
-        let mut remaining_accounts = Vec::new();
-        remaining_accounts.push(AccountMeta::new_readonly(
-            pool_config.tickarray_bitmap_extension.unwrap(),
-            false,
-        ));
+    };
+    let remaining_accounts = vec![AccountMeta::new_readonly(
+        pool_config.tickarray_bitmap_extension()?,
+        false,
+    )];
+    let user_token_account_0 = get_associated_token_address_with_program_id(
+        &payer.pubkey(),
+        &pool.token_mint_0,
+        &transfer_fee.0.owner,
+    );
+    let user_token_account_1 = get_associated_token_address_with_program_id(
+        &payer.pubkey(),
+        &pool.token_mint_1,
+        &transfer_fee.1.owner,
+    );
 
-        let increase_instr = increase_liquidity_instr(
-            &pool_config.clone(),
-            pool_config.pool_id_account.unwrap(),
+    // the swap input is wrapped along with the deposit, less what the WSOL ATA holds already
+    let (swap_in_0, swap_in_1) = match &zap_in {
+        Some(swap) if swap.zero_for_one => (swap.amount_in, 0),
+        Some(swap) => (0, swap.amount_in),
+        None => (0, 0),
+    };
+    let mut instructions = create_ata_token_account_instr(
+        &payer.pubkey(),
+        &payer.pubkey(),
+        &pool.token_mint_0,
+        &transfer_fee.0.owner,
+        wsol_shortfall(amount_0_max.saturating_add(swap_in_0), wsol_balance),
+    )?;
+    instructions.extend(create_ata_token_account_instr(
+        &payer.pubkey(),
+        &payer.pubkey(),
+        &pool.token_mint_1,
+        &transfer_fee.1.owner,
+        wsol_shortfall(amount_1_max.saturating_add(swap_in_1), wsol_balance),
+    )?);
+    if let Some(swap) = &zap_in {
+        let (swap_instructions, _) =
+            swap_leg_instr(rpc_client, pool_config, &payer.pubkey(), pool, swap).await?;
+        instructions.extend(swap_instructions);
+    }
+    if find_position.is_some() {
+        instructions.extend(increase_liquidity_instr(
+            pool_config,
+            payer.pubkey(),
+            pool_id,
             pool.token_vault_0,
             pool.token_vault_1,
             pool.token_mint_0,
             pool.token_mint_1,
-            find_position.nft_mint,
-            spl_associated_token_account::get_associated_token_address(
-                &payer.pubkey(),
-                &pool_config.mint0.unwrap(),
-            ),
-            spl_associated_token_account::get_associated_token_address(
-                &payer.pubkey(),
-                &pool_config.mint1.unwrap(),
-            ),
+            nft_mint,
+            user_token_account_0,
+            user_token_account_1,
             remaining_accounts,
             liquidity,
             amount_0_max,
@@ -239,22 +1862,66 @@ pub(crate) async fn increase_liquidity_tx(
             tick_upper_index,
             tick_array_lower_start_index,
             tick_array_upper_start_index,
-        )?;
-        // send
-        let signers = vec![&payer];
-        let recent_hash = rpc_client.get_latest_blockhash()?;
-        let txn = Transaction::new_signed_with_payer(
-            &increase_instr,
-            Some(&payer.pubkey()),
-            &signers,
-            recent_hash,
-        );
-        let signature = send_txn(&rpc_client, &txn, true)?;
-        println!("{}", signature);
+        )?);
     } else {
-        // personal position not exist
-        println!("personal position exist:{:?}", find_position);
+        log::info!(
+            "no position over [{}, {}], opening {}",
+            tick_lower_index,
+            tick_upper_index,
+            nft_mint
+        );
+        instructions.extend(open_position_instr(
+            pool_config,
+            payer.pubkey(),
+            pool_id,
+            pool.token_vault_0,
+            pool.token_vault_1,
+            pool.token_mint_0,
+            pool.token_mint_1,
+            nft_mint,
+            user_token_account_0,
+            user_token_account_1,
+            remaining_accounts,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+            tick_lower_index,
+            tick_upper_index,
+            tick_array_lower_start_index,
+            tick_array_upper_start_index,
+        )?);
+    }
+    if uses_sol && wsol_balance.is_none() {
+        instructions.extend(close_wsol_account_instr(&payer.pubkey())?);
     }
+    // send
+    let mut signers = vec![payer];
+    signers.extend(&new_nft_mint);
+    let recent_hash = rpc_client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        recent_hash,
+    );
+    let transaction = send_transaction(rpc_client, &pool_config.raydium_v3_program, &txn).await?;
 
-    Ok(())
+    Ok(DepositResult {
+        pool_id,
+        nft_mint,
+        position_opened: new_nft_mint.is_some(),
+        tick_lower_index,
+        tick_upper_index,
+        liquidity,
+        amount_0,
+        amount_1,
+        transfer_fee_0: transfer_fee.0.transfer_fee,
+        transfer_fee_1: transfer_fee.1.transfer_fee,
+        amount_0_max,
+        amount_1_max,
+        leftover_0: None,
+        leftover_1: None,
+        transaction,
+        zap_in,
+    })
 }
@@ -0,0 +1,238 @@
+use crate::client::get_epoch;
+use crate::error::{ClientError, Result};
+use crate::token::{get_mint_infos, MintInfo};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::{TransferFee, MAX_FEE_BASIS_POINTS};
+
+/// Fee schedule of `mint_info` in force at `epoch`, the newer one once its epoch is reached.
+pub fn get_epoch_fee(mint_info: &MintInfo, epoch: u64) -> Option<TransferFee> {
+    mint_info
+        .transfer_fee_config
+        .map(|transfer_fee_config| *transfer_fee_config.get_epoch_fee(epoch))
+}
+
+/// Fee withheld when transferring `pre_fee_amount`, capped at the maximum fee.
+pub fn get_transfer_fee(mint_info: &MintInfo, epoch: u64, pre_fee_amount: u64) -> Result<u64> {
+    match get_epoch_fee(mint_info, epoch) {
+        Some(transfer_fee) => transfer_fee.calculate_fee(pre_fee_amount).ok_or_else(|| {
+            ClientError::MathOverflow(format!(
+                "transfer fee of {} for mint {}",
+                pre_fee_amount, mint_info.mint
+            ))
+        }),
+        None => Ok(0),
+    }
+}
+
+/// Fee to add on top of `post_fee_amount` so that the recipient receives exactly it.
+pub fn get_transfer_inverse_fee(
+    mint_info: &MintInfo,
+    epoch: u64,
+    post_fee_amount: u64,
+) -> Result<u64> {
+    match get_epoch_fee(mint_info, epoch) {
+        // the whole transfer is withheld up to the maximum fee
+        Some(transfer_fee)
+            if u16::from(transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS =>
+        {
+            Ok(u64::from(transfer_fee.maximum_fee))
+        }
+        Some(transfer_fee) => transfer_fee
+            .calculate_inverse_fee(post_fee_amount)
+            .ok_or_else(|| {
+                ClientError::MathOverflow(format!(
+                    "inverse transfer fee of {} for mint {}",
+                    post_fee_amount, mint_info.mint
+                ))
+            }),
+        None => Ok(0),
+    }
+}
+
+/// Amount received when `pre_fee_amount` is transferred.
+pub fn post_fee_amount(mint_info: &MintInfo, epoch: u64, pre_fee_amount: u64) -> Result<u64> {
+    Ok(pre_fee_amount - get_transfer_fee(mint_info, epoch, pre_fee_amount)?)
+}
+
+/// Amount to transfer so that `post_fee_amount` is received.
+pub fn pre_fee_amount(mint_info: &MintInfo, epoch: u64, post_fee_amount: u64) -> Result<u64> {
+    post_fee_amount
+        .checked_add(get_transfer_inverse_fee(mint_info, epoch, post_fee_amount)?)
+        .ok_or_else(|| {
+            ClientError::MathOverflow(format!("amount {} plus transfer fee", post_fee_amount))
+        })
+}
+
+#[derive(Debug)]
+pub struct TransferFeeInfo {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub transfer_fee: u64,
+}
+
+pub async fn get_pool_mints_inverse_fee(
+    rpc_client: &RpcClient,
+    token_mint_0: Pubkey,
+    token_mint_1: Pubkey,
+    post_fee_amount_0: u64,
+    post_fee_amount_1: u64,
+) -> Result<(TransferFeeInfo, TransferFeeInfo)> {
+    let (mint_infos, epoch) = tokio::try_join!(
+        get_mint_infos(rpc_client, &[token_mint_0, token_mint_1]),
+        get_epoch(rpc_client)
+    )?;
+    Ok((
+        TransferFeeInfo {
+            mint: token_mint_0,
+            owner: mint_infos[0].token_program,
+            transfer_fee: get_transfer_inverse_fee(&mint_infos[0], epoch, post_fee_amount_0)?,
+        },
+        TransferFeeInfo {
+            mint: token_mint_1,
+            owner: mint_infos[1].token_program,
+            transfer_fee: get_transfer_inverse_fee(&mint_infos[1], epoch, post_fee_amount_1)?,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::account::Account;
+    use solana_sdk::program_pack::Pack;
+    use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+    use spl_token_2022::extension::{ExtensionType, StateWithExtensionsMut};
+    use spl_token_2022::state::Mint;
+
+    fn transfer_fee(epoch: u64, transfer_fee_basis_points: u16, maximum_fee: u64) -> TransferFee {
+        TransferFee {
+            epoch: epoch.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: transfer_fee_basis_points.into(),
+        }
+    }
+
+    fn transfer_fee_mint(
+        older_transfer_fee: TransferFee,
+        newer_transfer_fee: TransferFee,
+    ) -> MintInfo {
+        let account_len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; account_len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let transfer_fee_config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        transfer_fee_config.older_transfer_fee = older_transfer_fee;
+        transfer_fee_config.newer_transfer_fee = newer_transfer_fee;
+        state.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let account = Account {
+            lamports: 1,
+            data,
+            owner: spl_token_2022::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        MintInfo::unpack(Pubkey::new_unique(), &account).unwrap()
+    }
+
+    fn plain_mint() -> MintInfo {
+        let mut data = vec![0; Mint::LEN];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            decimals: 9,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        let account = Account {
+            lamports: 1,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        MintInfo::unpack(Pubkey::new_unique(), &account).unwrap()
+    }
+
+    #[test]
+    fn no_fee_without_extension() {
+        let mint_info = plain_mint();
+        assert_eq!(get_transfer_fee(&mint_info, 0, 1_000_000).unwrap(), 0);
+        assert_eq!(
+            get_transfer_inverse_fee(&mint_info, 0, 1_000_000).unwrap(),
+            0
+        );
+        assert_eq!(pre_fee_amount(&mint_info, 0, 1_000_000).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn forward_fee_rounds_up() {
+        // 1%
+        let mint_info = transfer_fee_mint(
+            transfer_fee(0, 100, u64::MAX),
+            transfer_fee(0, 100, u64::MAX),
+        );
+        assert_eq!(get_transfer_fee(&mint_info, 0, 10_000).unwrap(), 100);
+        assert_eq!(get_transfer_fee(&mint_info, 0, 10_001).unwrap(), 101);
+        assert_eq!(post_fee_amount(&mint_info, 0, 10_000).unwrap(), 9_900);
+    }
+
+    #[test]
+    fn inverse_fee_matches_forward_fee() {
+        let mint_info = transfer_fee_mint(
+            transfer_fee(0, 250, u64::MAX),
+            transfer_fee(0, 250, u64::MAX),
+        );
+        for post_fee in [1, 99, 10_000, 123_456_789] {
+            let pre_fee = pre_fee_amount(&mint_info, 0, post_fee).unwrap();
+            assert_eq!(post_fee_amount(&mint_info, 0, pre_fee).unwrap(), post_fee);
+        }
+    }
+
+    #[test]
+    fn fees_are_capped_at_maximum_fee() {
+        let mint_info = transfer_fee_mint(transfer_fee(0, 500, 1_000), transfer_fee(0, 500, 1_000));
+        assert_eq!(get_transfer_fee(&mint_info, 0, 1_000_000).unwrap(), 1_000);
+        assert_eq!(
+            get_transfer_inverse_fee(&mint_info, 0, 1_000_000).unwrap(),
+            1_000
+        );
+        assert_eq!(pre_fee_amount(&mint_info, 0, 1_000_000).unwrap(), 1_001_000);
+    }
+
+    #[test]
+    fn full_fee_charges_maximum_fee() {
+        let mint_info = transfer_fee_mint(
+            transfer_fee(0, MAX_FEE_BASIS_POINTS, 5_000),
+            transfer_fee(0, MAX_FEE_BASIS_POINTS, 5_000),
+        );
+        assert_eq!(
+            get_transfer_inverse_fee(&mint_info, 0, 1_000_000).unwrap(),
+            5_000
+        );
+    }
+
+    #[test]
+    fn newer_fee_applies_from_its_epoch() {
+        let mint_info = transfer_fee_mint(
+            transfer_fee(0, 100, u64::MAX),
+            transfer_fee(10, 300, u64::MAX),
+        );
+        assert_eq!(get_transfer_fee(&mint_info, 9, 10_000).unwrap(), 100);
+        assert_eq!(get_transfer_fee(&mint_info, 10, 10_000).unwrap(), 300);
+        assert_eq!(get_transfer_fee(&mint_info, 11, 10_000).unwrap(), 300);
+        assert_eq!(get_transfer_inverse_fee(&mint_info, 9, 9_900).unwrap(), 100);
+        assert_eq!(
+            get_transfer_inverse_fee(&mint_info, 10, 9_700).unwrap(),
+            300
+        );
+    }
+}
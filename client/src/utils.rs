@@ -1,14 +1,48 @@
 use raydium_amm_v3::libraries::fixed_point_64;
-use solana_client::rpc_client::RpcClient;
+use raydium_amm_v3::states::FEE_RATE_DENOMINATOR_VALUE;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::packet::PACKET_DATA_SIZE;
 use solana_sdk::pubkey::Pubkey;
-use spl_token_2022::extension::transfer_fee::{TransferFeeConfig, MAX_FEE_BASIS_POINTS};
-use spl_token_2022::extension::BaseStateWithExtensions;
-use spl_token_2022::extension::{BaseState, StateWithExtensionsMut};
-use spl_token_2022::state::Mint;
+use solana_sdk::transaction::Transaction;
 use std::ops::Mul;
 
 pub fn multipler(decimals: u8) -> f64 {
-    (10_i32).checked_pow(decimals.try_into().unwrap()).unwrap() as f64
+    10f64.powi(decimals as i32)
+}
+
+/// Serialize a pubkey as base58, e.g. with `#[serde(serialize_with = "serialize_pubkey")]`.
+pub fn serialize_pubkey<S: serde::Serializer>(
+    pubkey: &Pubkey,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+/// Serialize pubkeys as a list of base58 strings.
+pub fn serialize_pubkeys<S: serde::Serializer>(
+    pubkeys: &[Pubkey],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(pubkeys.iter().map(Pubkey::to_string))
+}
+
+/// Serialize a value through its `Display`, e.g. a `u128` as a decimal string, which JSON
+/// numbers cannot hold exactly.
+pub fn serialize_display<T: std::fmt::Display, S: serde::Serializer>(
+    value: &T,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id()
+}
+
+/// SOL to wrap so that a WSOL ATA holding `wsol_balance`, `None` when it does not exist yet,
+/// holds `amount`.
+pub fn wsol_shortfall(amount: u64, wsol_balance: Option<u64>) -> u64 {
+    amount.saturating_sub(wsol_balance.unwrap_or(0))
 }
 
 pub fn price_to_x64(price: f64) -> u128 {
@@ -28,66 +62,85 @@ pub fn tick_with_spacing(tick: i32, tick_spacing: i32) -> i32 {
     compressed * tick_spacing
 }
 
-pub fn amount_with_slippage(amount: u64, slippage: f64, round_up: bool) -> u64 {
-    if round_up {
-        (amount as f64).mul(1_f64 + slippage).ceil() as u64
+pub fn sqrt_price_x64_to_f64(sqrt_price_x64: u128) -> f64 {
+    sqrt_price_x64 as f64 / fixed_point_64::Q64 as f64
+}
+
+/// Amount of the excess token to swap so that what remains matches the token ratio of the
+/// `[sqrt_price_lower_x64, sqrt_price_upper_x64]` range, assuming the swap does not move the
+/// price. Returns `(zero_for_one, amount_in)`, or `None` when nothing needs to be swapped.
+pub fn get_zap_in_swap_amount(
+    sqrt_price_x64: u128,
+    sqrt_price_lower_x64: u128,
+    sqrt_price_upper_x64: u128,
+    trade_fee_rate: u32,
+    amount_0: u64,
+    amount_1: u64,
+) -> Option<(bool, u64)> {
+    let swap = if sqrt_price_x64 <= sqrt_price_lower_x64 {
+        // range only holds token0
+        (false, amount_1 as f64)
+    } else if sqrt_price_x64 >= sqrt_price_upper_x64 {
+        // range only holds token1
+        (true, amount_0 as f64)
     } else {
-        (amount as f64).mul(1_f64 - slippage).floor() as u64
+        let sqrt_price = sqrt_price_x64_to_f64(sqrt_price_x64);
+        let sqrt_price_lower = sqrt_price_x64_to_f64(sqrt_price_lower_x64);
+        let sqrt_price_upper = sqrt_price_x64_to_f64(sqrt_price_upper_x64);
+        let price = sqrt_price * sqrt_price;
+        let fee_factor = 1.0 - trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64;
+        // token0 needed per token1 deposited
+        let ratio = (1.0 / sqrt_price - 1.0 / sqrt_price_upper) / (sqrt_price - sqrt_price_lower);
+        let (amount_0, amount_1) = (amount_0 as f64, amount_1 as f64);
+        if amount_0 > ratio * amount_1 {
+            (
+                true,
+                (amount_0 - ratio * amount_1) / (1.0 + ratio * price * fee_factor),
+            )
+        } else {
+            (
+                false,
+                (ratio * amount_1 - amount_0) / (ratio + fee_factor / price),
+            )
+        }
+    };
+    let amount_in = swap.1.floor() as u64;
+    if amount_in == 0 {
+        None
+    } else {
+        Some((swap.0, amount_in))
     }
 }
 
-/// Calculate the fee for output amount
-pub fn get_transfer_inverse_fee<'data, S: BaseState>(
-    account_state: &StateWithExtensionsMut<'data, S>,
-    epoch: u64,
-    post_fee_amount: u64,
+/// Output of swapping `amount_in` at the current price, net of the trade fee.
+pub fn estimate_swap_amount_out(
+    sqrt_price_x64: u128,
+    trade_fee_rate: u32,
+    amount_in: u64,
+    zero_for_one: bool,
 ) -> u64 {
-    let fee = if let Ok(transfer_fee_config) = account_state.get_extension::<TransferFeeConfig>() {
-        let transfer_fee = transfer_fee_config.get_epoch_fee(epoch);
-        if u16::from(transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
-            u64::from(transfer_fee.maximum_fee)
-        } else {
-            transfer_fee_config
-                .calculate_inverse_epoch_fee(epoch, post_fee_amount)
-                .unwrap()
-        }
+    let sqrt_price = sqrt_price_x64_to_f64(sqrt_price_x64);
+    let price = sqrt_price * sqrt_price;
+    let amount_in_less_fee =
+        amount_in as f64 * (1.0 - trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64);
+    if zero_for_one {
+        (amount_in_less_fee * price).floor() as u64
     } else {
-        0
-    };
-    fee
+        (amount_in_less_fee / price).floor() as u64
+    }
 }
 
-#[derive(Debug)]
-pub struct TransferFeeInfo {
-    pub mint: Pubkey,
-    pub owner: Pubkey,
-    pub transfer_fee: u64,
+pub fn amount_with_slippage(amount: u64, slippage: f64, round_up: bool) -> u64 {
+    if round_up {
+        (amount as f64).mul(1_f64 + slippage).ceil() as u64
+    } else {
+        (amount as f64).mul(1_f64 - slippage).floor() as u64
+    }
 }
 
-pub fn get_pool_mints_inverse_fee(
-    rpc_client: &RpcClient,
-    token_mint_0: Pubkey,
-    token_mint_1: Pubkey,
-    post_fee_amount_0: u64,
-    post_fee_amount_1: u64,
-) -> (TransferFeeInfo, TransferFeeInfo) {
-    let load_accounts = vec![token_mint_0, token_mint_1];
-    let rsps = rpc_client.get_multiple_accounts(&load_accounts).unwrap();
-    let epoch = rpc_client.get_epoch_info().unwrap().epoch;
-    let mut mint0_account = rsps[0].clone().ok_or("load mint0 rps error!").unwrap();
-    let mut mint1_account = rsps[1].clone().ok_or("load mint0 rps error!").unwrap();
-    let mint0_state = StateWithExtensionsMut::<Mint>::unpack(&mut mint0_account.data).unwrap();
-    let mint1_state = StateWithExtensionsMut::<Mint>::unpack(&mut mint1_account.data).unwrap();
-    (
-        TransferFeeInfo {
-            mint: token_mint_0,
-            owner: mint0_account.owner,
-            transfer_fee: get_transfer_inverse_fee(&mint0_state, post_fee_amount_0, epoch),
-        },
-        TransferFeeInfo {
-            mint: token_mint_1,
-            owner: mint1_account.owner,
-            transfer_fee: get_transfer_inverse_fee(&mint1_state, post_fee_amount_1, epoch),
-        },
-    )
+/// Whether `instructions` paid by `payer` fit in one transaction once signed.
+pub fn fits_in_transaction(instructions: &[Instruction], payer: &Pubkey) -> bool {
+    // unsigned transactions already hold a placeholder for every signature
+    bincode::serialized_size(&Transaction::new_with_payer(instructions, Some(payer)))
+        .is_ok_and(|size| size <= PACKET_DATA_SIZE as u64)
 }